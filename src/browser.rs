@@ -0,0 +1,561 @@
+use crate::favourite::Favourite;
+use crate::find::get_finished_description;
+use crate::progress::WadProgress;
+use crate::wad::{MapInfo, WadEntry};
+use tuikit::prelude::*;
+
+/// The help for the key bindings, which is shown at the bottom of the browser.
+pub const KEY_HELP: &str =
+    "Enter/p: play  r: record  f: favourite  Tab: switch list  PgUp/PgDn: scroll text  q: quit";
+
+/// An imported WAD, along with everything the browser shows for it.
+pub struct LibraryWad {
+    pub entry: WadEntry,
+    pub progress: WadProgress,
+    /// The text file that came with the WAD from idgames. IWADs and WADs that weren't downloaded
+    /// from idgames won't have one.
+    pub text_file: Option<String>,
+}
+
+/// The list in the browser that has the focus, and so is moved through with the arrow keys.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pane {
+    Wads,
+    Maps,
+}
+
+/// The state of the library browser: the WADs, the favourites, and what's selected.
+///
+/// This doesn't know anything about the terminal, so the commands that read the repositories and
+/// launch the game can be kept apart from the navigation.
+pub struct Library {
+    wads: Vec<LibraryWad>,
+    favourites: Vec<Favourite>,
+    focus: Pane,
+    selected_wad: usize,
+    selected_map: usize,
+    details_offset: usize,
+    /// A message for the status bar, like the result of the last game, which replaces the key
+    /// help until the next key is pressed.
+    pub status: Option<String>,
+}
+
+impl Library {
+    pub fn new(wads: Vec<LibraryWad>, favourites: Vec<Favourite>) -> Library {
+        Library {
+            wads,
+            favourites,
+            focus: Pane::Wads,
+            selected_wad: 0,
+            selected_map: 0,
+            details_offset: 0,
+            status: None,
+        }
+    }
+
+    /// Replaces the WADs after something has changed, like a map being finished, keeping the same
+    /// WAD and map selected if they're still there.
+    pub fn set_wads(&mut self, wads: Vec<LibraryWad>) {
+        let selected_id = self.get_selected_wad().map(|x| x.entry.id.clone());
+        self.wads = wads;
+        match selected_id.and_then(|id| self.wads.iter().position(|x| x.entry.id == id)) {
+            Some(index) => {
+                self.selected_wad = index;
+                self.selected_map = self
+                    .selected_map
+                    .min(self.get_map_count().saturating_sub(1));
+            }
+            None => self.select_wad(0),
+        }
+    }
+
+    pub fn set_favourites(&mut self, favourites: Vec<Favourite>) {
+        self.favourites = favourites;
+    }
+
+    pub fn get_wads(&self) -> &[LibraryWad] {
+        &self.wads
+    }
+
+    pub fn get_focus(&self) -> Pane {
+        self.focus
+    }
+
+    /// The map list can't have the focus if the selected WAD doesn't have any maps.
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Pane::Wads if self.get_map_count() > 0 => Pane::Maps,
+            _ => Pane::Wads,
+        };
+    }
+
+    /// Moves the selection in the list with the focus, stopping at either end. Moving to another
+    /// WAD goes back to its first map and the top of its text file.
+    pub fn move_selection(&mut self, delta: isize) {
+        match self.focus {
+            Pane::Wads => {
+                let index = get_moved_index(self.selected_wad, delta, self.wads.len());
+                if index != self.selected_wad {
+                    self.select_wad(index);
+                }
+            }
+            Pane::Maps => {
+                self.selected_map = get_moved_index(self.selected_map, delta, self.get_map_count());
+            }
+        }
+    }
+
+    pub fn scroll_details(&mut self, delta: isize) {
+        let line_count = self
+            .get_selected_wad()
+            .map_or(0, |x| self.get_details(x).len());
+        self.details_offset = get_moved_index(self.details_offset, delta, line_count);
+    }
+
+    pub fn get_selected_wad(&self) -> Option<&LibraryWad> {
+        self.wads.get(self.selected_wad)
+    }
+
+    pub fn get_selected_map(&self) -> Option<&MapInfo> {
+        self.get_selected_wad()
+            .and_then(|x| x.entry.maps.get(self.selected_map))
+    }
+
+    /// The WAD and map to play, or to add to the favourites. With the focus on the WAD list, it's
+    /// the WAD itself, without a map.
+    pub fn get_selection(&self) -> Option<(String, Option<String>)> {
+        let wad = self.get_selected_wad()?;
+        let map = match self.focus {
+            Pane::Wads => None,
+            Pane::Maps => Some(self.get_selected_map()?.number.clone()),
+        };
+        Some((wad.entry.id.clone(), map))
+    }
+
+    /// A WAD is only a favourite if it was added itself, but a map is also a favourite if its WAD
+    /// is.
+    pub fn is_favourite(&self, wad_id: &str, map: Option<&str>) -> bool {
+        match map {
+            Some(map) => self.favourites.iter().any(|x| x.matches(wad_id, map)),
+            None => self
+                .favourites
+                .iter()
+                .any(|x| x.wad_id == wad_id && x.map.is_none()),
+        }
+    }
+
+    pub fn get_wad_line(&self, wad: &LibraryWad) -> String {
+        let finished = wad
+            .entry
+            .maps
+            .iter()
+            .filter(|x| wad.progress.is_finished(&x.number))
+            .count();
+        format!(
+            "{} {} {} {} {}/{}",
+            get_favourite_marker(self.is_favourite(&wad.entry.id, None)),
+            fit(&wad.entry.title, 24),
+            fit(&wad.entry.author, 16),
+            fit(&wad.entry.release_date, 10),
+            finished,
+            wad.entry.maps.len()
+        )
+    }
+
+    pub fn get_map_line(&self, wad: &LibraryWad, map: &MapInfo) -> String {
+        let stats = map.stats.as_ref().map_or_else(String::new, |x| {
+            format!("{:>4} monsters {:>3} secrets", x.hard_monsters, x.secrets)
+        });
+        format!(
+            "{} {} {} {}{}",
+            get_favourite_marker(self.is_favourite(&wad.entry.id, Some(&map.number))),
+            fit(&map.number, 6),
+            fit(&map.name, 24),
+            fit(&stats, 25),
+            get_finished_description(&wad.progress.get_map_progress(&map.number))
+        )
+    }
+
+    /// The information from the WAD entry, followed by the text file.
+    pub fn get_details(&self, wad: &LibraryWad) -> Vec<String> {
+        let entry = &wad.entry;
+        let mut details = vec![
+            format!("Title: {}", entry.title),
+            format!("Author: {}", entry.author),
+            format!("Released: {}", entry.release_date),
+            format!("File: {}", entry.name),
+        ];
+        if let Some(iwad) = &entry.iwad {
+            details.push(format!("IWAD: {}", iwad));
+        }
+        if let Some(complevel) = &entry.complevel {
+            details.push(format!("Complevel: {}", complevel));
+        }
+        details.push(String::new());
+        match &wad.text_file {
+            Some(text_file) => details.extend(text_file.lines().map(|x| x.replace('\t', "    "))),
+            None => details.push("There's no text file for this WAD.".to_string()),
+        }
+        details
+    }
+
+    fn select_wad(&mut self, index: usize) {
+        self.selected_wad = index;
+        self.selected_map = 0;
+        self.details_offset = 0;
+        self.focus = Pane::Wads;
+    }
+
+    fn get_map_count(&self) -> usize {
+        self.get_selected_wad().map_or(0, |x| x.entry.maps.len())
+    }
+}
+
+fn get_moved_index(index: usize, delta: isize, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    if delta < 0 {
+        index.saturating_sub(delta.unsigned_abs())
+    } else {
+        index.saturating_add(delta as usize).min(len - 1)
+    }
+}
+
+/// Works out the first line to show in a list, so that the selected line stays on screen.
+fn get_list_offset(selected: usize, height: usize) -> usize {
+    if height == 0 || selected < height {
+        0
+    } else {
+        selected + 1 - height
+    }
+}
+
+fn get_favourite_marker(is_favourite: bool) -> char {
+    if is_favourite {
+        '*'
+    } else {
+        ' '
+    }
+}
+
+/// Pads or truncates text so that the columns in the lists line up.
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted = text.chars().take(width).collect::<String>();
+    let len = fitted.chars().count();
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+//
+// The widgets for drawing the browser with tuikit
+//
+
+pub struct WadList<'a>(pub &'a Library);
+
+pub struct MapList<'a>(pub &'a Library);
+
+pub struct Details<'a>(pub &'a Library);
+
+pub struct StatusBar<'a>(pub &'a Library);
+
+impl Draw for WadList<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> DrawResult<()> {
+        let library = self.0;
+        let lines = library
+            .wads
+            .iter()
+            .map(|x| library.get_wad_line(x))
+            .collect::<Vec<String>>();
+        draw_list(
+            canvas,
+            &lines,
+            library.selected_wad,
+            library.focus == Pane::Wads,
+        )
+    }
+}
+
+impl Widget for WadList<'_> {}
+
+impl Draw for MapList<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> DrawResult<()> {
+        let library = self.0;
+        let wad = match library.get_selected_wad() {
+            Some(wad) => wad,
+            None => return Ok(()),
+        };
+        if wad.entry.maps.is_empty() {
+            let _ = canvas.print(0, 0, "This WAD doesn't have any maps.");
+            return Ok(());
+        }
+        let lines = wad
+            .entry
+            .maps
+            .iter()
+            .map(|x| library.get_map_line(wad, x))
+            .collect::<Vec<String>>();
+        draw_list(
+            canvas,
+            &lines,
+            library.selected_map,
+            library.focus == Pane::Maps,
+        )
+    }
+}
+
+impl Widget for MapList<'_> {}
+
+impl Draw for Details<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> DrawResult<()> {
+        let library = self.0;
+        let wad = match library.get_selected_wad() {
+            Some(wad) => wad,
+            None => return Ok(()),
+        };
+        let (_, height) = canvas.size()?;
+        let details = library.get_details(wad);
+        for (row, line) in details
+            .iter()
+            .skip(library.details_offset)
+            .take(height)
+            .enumerate()
+        {
+            let _ = canvas.print(row, 0, line);
+        }
+        Ok(())
+    }
+}
+
+impl Widget for Details<'_> {}
+
+impl Draw for StatusBar<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> DrawResult<()> {
+        let (width, _) = canvas.size()?;
+        let text = self.0.status.as_deref().unwrap_or(KEY_HELP);
+        let _ = canvas.print_with_attr(0, 0, &fit(text, width), Effect::REVERSE.into());
+        Ok(())
+    }
+}
+
+impl Widget for StatusBar<'_> {}
+
+/// Draws the visible part of a list. The selected line is highlighted across the whole width when
+/// the list has the focus, and is only in bold otherwise.
+fn draw_list(
+    canvas: &mut dyn Canvas,
+    lines: &[String],
+    selected: usize,
+    focused: bool,
+) -> DrawResult<()> {
+    let (width, height) = canvas.size()?;
+    let offset = get_list_offset(selected, height);
+    for (row, line) in lines.iter().skip(offset).take(height).enumerate() {
+        let attr = if offset + row != selected {
+            Attr::default()
+        } else if focused {
+            Effect::REVERSE.into()
+        } else {
+            Effect::BOLD.into()
+        };
+        let _ = canvas.print_with_attr(row, 0, &fit(line, width), attr);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod library {
+    use super::{Library, LibraryWad, Pane};
+    use crate::favourite::Favourite;
+    use crate::progress::WadProgress;
+    use crate::wad::{MapInfo, WadEntry};
+
+    fn get_wad(id: &str, map_count: usize) -> LibraryWad {
+        let maps = (1..=map_count)
+            .map(|x| MapInfo::new(format!("MAP{:02}", x), format!("Map {}", x)).unwrap())
+            .collect();
+        LibraryWad {
+            entry: WadEntry {
+                id: id.to_string(),
+                name: format!("{}.wad", id.to_lowercase()),
+                title: format!("The {} Megawad", id),
+                release_date: "2021-03-01".to_string(),
+                author: "Someone".to_string(),
+                maps,
+                episodes: Vec::new(),
+                game: None,
+                version: None,
+                iwad: Some("DOOM2".to_string()),
+                files: Vec::new(),
+                complevel: None,
+            },
+            progress: WadProgress::new(id),
+            text_file: None,
+        }
+    }
+
+    mod move_selection {
+        use super::{get_wad, Library, Pane};
+
+        #[test]
+        fn should_stop_at_either_end_of_the_list() {
+            let mut library = Library::new(vec![get_wad("AV", 2), get_wad("SCYTHE", 2)], vec![]);
+            library.move_selection(-1);
+            assert_eq!(library.get_selected_wad().unwrap().entry.id, "AV");
+            library.move_selection(5);
+            assert_eq!(library.get_selected_wad().unwrap().entry.id, "SCYTHE");
+        }
+
+        #[test]
+        fn should_go_back_to_the_first_map_when_another_wad_is_selected() {
+            let mut library = Library::new(vec![get_wad("AV", 3), get_wad("SCYTHE", 3)], vec![]);
+            library.toggle_focus();
+            library.move_selection(2);
+            assert_eq!(library.get_selected_map().unwrap().number, "MAP03");
+            library.toggle_focus();
+            library.move_selection(1);
+            assert_eq!(library.get_focus(), Pane::Wads);
+            assert_eq!(library.get_selected_map().unwrap().number, "MAP01");
+        }
+    }
+
+    mod toggle_focus {
+        use super::{get_wad, Library, Pane};
+
+        #[test]
+        fn should_not_focus_the_map_list_for_a_wad_without_maps() {
+            let mut library = Library::new(vec![get_wad("EMPTY", 0)], vec![]);
+            library.toggle_focus();
+            assert_eq!(library.get_focus(), Pane::Wads);
+        }
+    }
+
+    mod get_selection {
+        use super::{get_wad, Library};
+
+        #[test]
+        fn should_only_select_the_wad_when_the_wad_list_has_the_focus() {
+            let mut library = Library::new(vec![get_wad("AV", 3)], vec![]);
+            assert_eq!(library.get_selection(), Some(("AV".to_string(), None)));
+            library.toggle_focus();
+            library.move_selection(1);
+            assert_eq!(
+                library.get_selection(),
+                Some(("AV".to_string(), Some("MAP02".to_string())))
+            );
+        }
+    }
+
+    mod set_wads {
+        use super::{get_wad, Library};
+
+        #[test]
+        fn should_keep_the_same_wad_and_map_selected() {
+            let mut library = Library::new(vec![get_wad("AV", 3), get_wad("SCYTHE", 3)], vec![]);
+            library.move_selection(1);
+            library.toggle_focus();
+            library.move_selection(1);
+            library.set_wads(vec![
+                get_wad("ALIENS", 1),
+                get_wad("AV", 3),
+                get_wad("SCYTHE", 3),
+            ]);
+            assert_eq!(
+                library.get_selection(),
+                Some(("SCYTHE".to_string(), Some("MAP02".to_string())))
+            );
+        }
+    }
+
+    mod is_favourite {
+        use super::{get_wad, Favourite, Library};
+
+        #[test]
+        fn should_only_mark_a_wad_that_was_added_itself() {
+            let library = Library::new(
+                vec![get_wad("AV", 3)],
+                vec![Favourite::new("AV", Some("MAP02"))],
+            );
+            assert!(!library.is_favourite("AV", None));
+            assert!(library.is_favourite("AV", Some("MAP02")));
+            assert!(!library.is_favourite("AV", Some("MAP01")));
+        }
+
+        #[test]
+        fn should_mark_every_map_in_a_favourite_wad() {
+            let library = Library::new(vec![get_wad("AV", 3)], vec![Favourite::new("AV", None)]);
+            assert!(library.is_favourite("AV", None));
+            assert!(library.is_favourite("AV", Some("MAP03")));
+        }
+    }
+
+    mod get_wad_line {
+        use super::{get_wad, Library, WadProgress};
+        use crate::progress::LevelStat;
+        use crate::source_port::Skill;
+
+        #[test]
+        fn should_show_how_many_maps_have_been_finished() {
+            let mut wad = get_wad("AV", 3);
+            let mut progress = WadProgress::new("AV");
+            let stat = &LevelStat::parse("MAP02 - 0:31.97 (0:31)  K: 14/14  I: 4/9  S: 2/5")[0];
+            progress.add_result(stat, &Skill::UltraViolence);
+            wad.progress = progress;
+            let library = Library::new(vec![], vec![]);
+            let line = library.get_wad_line(&wad);
+            assert!(line.starts_with("  The AV Megawad"));
+            assert!(line.contains("Someone"));
+            assert!(line.contains("2021-03-01"));
+            assert!(line.ends_with("1/3"));
+        }
+    }
+
+    mod get_details {
+        use super::{get_wad, Library};
+
+        #[test]
+        fn should_show_the_text_file_after_the_wad_information() {
+            let mut wad = get_wad("AV", 1);
+            wad.text_file = Some("Title : Alien Vendetta\n\tAuthors : Various".to_string());
+            let library = Library::new(vec![], vec![]);
+            let details = library.get_details(&wad);
+            assert_eq!(details[0], "Title: The AV Megawad");
+            assert_eq!(details[4], "IWAD: DOOM2");
+            assert_eq!(details[6], "Title : Alien Vendetta");
+            assert_eq!(details[7], "    Authors : Various");
+        }
+
+        #[test]
+        fn should_say_when_there_is_no_text_file() {
+            let library = Library::new(vec![], vec![]);
+            let details = library.get_details(&get_wad("AV", 1));
+            assert_eq!(
+                details.last().unwrap(),
+                "There's no text file for this WAD."
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod get_list_offset {
+    use super::get_list_offset;
+
+    #[test]
+    fn should_scroll_to_keep_the_selected_line_on_screen() {
+        assert_eq!(get_list_offset(3, 10), 0);
+        assert_eq!(get_list_offset(10, 10), 1);
+        assert_eq!(get_list_offset(25, 10), 16);
+    }
+}
+
+#[cfg(test)]
+mod fit {
+    use super::fit;
+
+    #[test]
+    fn should_pad_or_truncate_the_text_to_the_width() {
+        assert_eq!(fit("MAP01", 6), "MAP01 ");
+        assert_eq!(fit("Alien Vendetta", 5), "Alien");
+    }
+}
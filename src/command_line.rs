@@ -0,0 +1,725 @@
+use crate::complevel::CompLevel;
+use crate::iwad::Game;
+use crate::package::PackageType;
+use crate::profile::Profile;
+use crate::source_port::{Skill, SourcePort};
+use crate::wad::MapInfo;
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+/// Everything needed to launch a game, other than the options that come from the profile.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchRequest {
+    pub iwad: PathBuf,
+    /// The WADs and packages to load, in the order they're loaded.
+    pub files: Vec<PathBuf>,
+    /// The DeHackEd and BEX patches to apply, in the order they're applied.
+    pub patches: Vec<PathBuf>,
+    /// The map to start on. If there isn't one, the game starts at the title screen.
+    pub map: Option<MapInfo>,
+    /// The compatibility level the PWAD is made for.
+    pub complevel: Option<CompLevel>,
+    /// The game the IWAD is for, which decides the number for the vanilla complevel.
+    pub game: Option<Game>,
+    /// The path to record a demo to.
+    pub record: Option<PathBuf>,
+    /// The path of a demo to play back. The demo decides the skill and map.
+    pub playdemo: Option<PathBuf>,
+    /// Plays the demo back as fast as possible to time it, rather than at normal speed.
+    pub timedemo: bool,
+    /// The path to render the demo being played back to, as a video.
+    pub viddump: Option<PathBuf>,
+    /// A config file to use instead of the port's own.
+    pub config: Option<PathBuf>,
+    /// Writes the statistics for each map that's finished to a file when the game exits.
+    pub levelstat: bool,
+}
+
+/// Turns a profile and a launch request into the arguments for a particular source port.
+///
+/// The source ports all started out with the same arguments as vanilla DOOM, but they've since
+/// diverged, and some of them don't support every option, so each family of ports gets its own
+/// builder. The default methods produce the arguments that are common to most of them. A builder
+/// should return an error for anything the port can't do, rather than passing through an argument
+/// the port would ignore.
+pub trait CommandLineBuilder {
+    fn get_source_port(&self) -> SourcePort;
+
+    /// The arguments that are put together in the order the source ports expect: the IWAD and the
+    /// other files, then the options, then the map and the demo to record or play back.
+    fn build(&self, profile: &Profile, request: &LaunchRequest) -> Result<Vec<String>, Report> {
+        let mut args = self.get_iwad_args(&request.iwad);
+        args.extend(self.get_file_args(&request.files)?);
+        args.extend(self.get_patch_args(&request.patches)?);
+        if let Some(config) = &request.config {
+            args.extend(self.get_config_args(config));
+        }
+        if let Some(complevel) = request.complevel {
+            args.extend(self.get_complevel_args(complevel, request.game)?);
+        }
+        if request.playdemo.is_none() {
+            args.extend(self.get_skill_args(&profile.skill));
+        }
+        args.extend(self.get_music_args(profile.music)?);
+        args.extend(self.get_fullscreen_args(profile.fullscreen)?);
+        if let Some(map) = &request.map {
+            args.extend(self.get_map_args(map)?);
+        }
+        if let Some(demo) = &request.record {
+            args.extend(self.get_record_args(demo)?);
+        }
+        if request.levelstat {
+            args.extend(self.get_levelstat_args()?);
+        }
+        if let Some(demo) = &request.playdemo {
+            args.extend(self.get_playdemo_args(demo, request.timedemo));
+        }
+        if let Some(video) = &request.viddump {
+            args.extend(self.get_viddump_args(video)?);
+        }
+        Ok(args)
+    }
+
+    fn get_iwad_args(&self, iwad: &Path) -> Vec<String> {
+        vec!["-iwad".to_string(), path_to_arg(iwad)]
+    }
+
+    /// Loads the files with `-file`, after checking any packages can be loaded.
+    fn get_file_args(&self, files: &[PathBuf]) -> Result<Vec<String>, Report> {
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+        let source_port = self.get_source_port();
+        if !source_port.supports_pk3() {
+            for file in files {
+                if PackageType::from_path(file)? == PackageType::Pk3 {
+                    return Err(eyre!(
+                        "{} is a PK3 package, which {} can't load",
+                        file.display(),
+                        source_port
+                    )
+                    .suggestion(suggest_compatible_ports(|x| x.supports_pk3())));
+                }
+            }
+        }
+        let mut args = vec!["-file".to_string()];
+        args.extend(files.iter().map(|x| path_to_arg(x)));
+        Ok(args)
+    }
+
+    fn get_patch_args(&self, patches: &[PathBuf]) -> Result<Vec<String>, Report> {
+        Ok(get_deh_args(patches))
+    }
+
+    /// Most ports work out the compatibility they need for themselves, so nothing is passed by
+    /// default.
+    fn get_complevel_args(
+        &self,
+        _complevel: CompLevel,
+        _game: Option<Game>,
+    ) -> Result<Vec<String>, Report> {
+        Ok(Vec::new())
+    }
+
+    fn get_skill_args(&self, skill: &Skill) -> Vec<String> {
+        let skill = match skill {
+            Skill::Nightmare => "5",
+            Skill::UltraViolence => "4",
+            Skill::HurtMePlenty => "3",
+            Skill::HeyNotTooRough => "2",
+            Skill::TooYoungToDie => "1",
+        };
+        vec!["-skill".to_string(), skill.to_string()]
+    }
+
+    fn get_music_args(&self, music: bool) -> Result<Vec<String>, Report> {
+        if music {
+            return Ok(Vec::new());
+        }
+        Ok(vec!["-nomusic".to_string()])
+    }
+
+    /// Every port starts in fullscreen by default, so an argument is only needed for a window.
+    fn get_fullscreen_args(&self, fullscreen: bool) -> Result<Vec<String>, Report> {
+        if fullscreen {
+            return Ok(Vec::new());
+        }
+        Ok(vec!["-nofullscreen".to_string()])
+    }
+
+    /// Starts the map with `-warp`. Maps that can't be reached with `-warp` are loaded by their
+    /// lump name, which not every source port can do.
+    fn get_map_args(&self, map: &MapInfo) -> Result<Vec<String>, Report> {
+        if let Some(warp_args) = map.warp.get_warp_args() {
+            let mut args = vec!["-warp".to_string()];
+            args.extend(warp_args);
+            return Ok(args);
+        }
+        Err(eyre!(
+            "{} can't be started on {}, since it can only be loaded by its lump name",
+            self.get_source_port(),
+            map.number
+        )
+        .suggestion("Use a profile with a source port that can load maps by name, like GZDoom"))
+    }
+
+    fn get_record_args(&self, demo: &Path) -> Result<Vec<String>, Report> {
+        let source_port = self.get_source_port();
+        if !source_port.supports_demo_recording() {
+            return Err(eyre!("{} can't record demos", source_port)
+                .suggestion(suggest_compatible_ports(|x| x.supports_demo_recording())));
+        }
+        Ok(vec!["-record".to_string(), path_to_arg(demo)])
+    }
+
+    fn get_levelstat_args(&self) -> Result<Vec<String>, Report> {
+        let source_port = self.get_source_port();
+        if !source_port.supports_levelstat() {
+            return Err(eyre!(
+                "{} can't write the statistics for the maps that are finished",
+                source_port
+            ));
+        }
+        Ok(vec!["-levelstat".to_string()])
+    }
+
+    fn get_config_args(&self, config: &Path) -> Vec<String> {
+        vec!["-config".to_string(), path_to_arg(config)]
+    }
+
+    fn get_viddump_args(&self, video: &Path) -> Result<Vec<String>, Report> {
+        let source_port = self.get_source_port();
+        if !source_port.supports_video_dump() {
+            return Err(eyre!("{} can't render demos to video", source_port)
+                .suggestion(suggest_compatible_ports(|x| x.supports_video_dump())));
+        }
+        Ok(vec!["-viddump".to_string(), path_to_arg(video)])
+    }
+
+    /// Checking whether the port can play the demo needs the demo's header, so it's up to the
+    /// caller to pick a port that can.
+    fn get_playdemo_args(&self, demo: &Path, timedemo: bool) -> Vec<String> {
+        let arg = if timedemo { "-timedemo" } else { "-playdemo" };
+        vec![arg.to_string(), path_to_arg(demo)]
+    }
+}
+
+/// For Chocolate Doom and Crispy Doom, which keep to the vanilla arguments.
+pub struct VanillaCommandLineBuilder {
+    source_port: SourcePort,
+    /// Chocolate Doom only supports the original DeHackEd format, without the BEX extensions.
+    supports_bex: bool,
+}
+
+/// For the ports descended from Boom, like PrBoom+ and DSDA-Doom, which the default arguments are
+/// based on.
+pub struct BoomCommandLineBuilder {
+    source_port: SourcePort,
+    /// Whether the port emulates the other executables with `-complevel`. Ports like Doom Retro
+    /// and Eternity have their own compatibility settings instead.
+    supports_complevel: bool,
+}
+
+/// For the ZDoom family and Odamex, which are configured with console variables.
+pub struct ZDoomCommandLineBuilder {
+    source_port: SourcePort,
+    /// The console variable for fullscreen mode, which was renamed in GZDoom 4.
+    fullscreen_cvar: &'static str,
+}
+
+impl CommandLineBuilder for VanillaCommandLineBuilder {
+    fn get_source_port(&self) -> SourcePort {
+        self.source_port
+    }
+
+    fn get_patch_args(&self, patches: &[PathBuf]) -> Result<Vec<String>, Report> {
+        if !self.supports_bex {
+            let bex = patches.iter().find(|x| {
+                x.extension()
+                    .is_some_and(|x| x.to_string_lossy().eq_ignore_ascii_case("bex"))
+            });
+            if let Some(bex) = bex {
+                return Err(eyre!(
+                    "{} is a BEX patch, which {} can't apply",
+                    bex.display(),
+                    self.source_port
+                )
+                .suggestion(
+                    "Use a profile with Crispy Doom or one of the Boom-based source ports",
+                ));
+            }
+        }
+        Ok(get_deh_args(patches))
+    }
+
+    fn get_fullscreen_args(&self, fullscreen: bool) -> Result<Vec<String>, Report> {
+        if fullscreen {
+            return Ok(Vec::new());
+        }
+        Ok(vec!["-window".to_string()])
+    }
+
+    /// These ports can only play vanilla maps, so anything made for Boom or later is rejected.
+    fn get_complevel_args(
+        &self,
+        complevel: CompLevel,
+        _game: Option<Game>,
+    ) -> Result<Vec<String>, Report> {
+        if complevel > CompLevel::Vanilla {
+            return Err(eyre!(
+                "This WAD is made for {}, which {} doesn't support",
+                complevel,
+                self.source_port
+            )
+            .suggestion("Use a profile with one of the Boom-based source ports, like DSDA Doom"));
+        }
+        Ok(Vec::new())
+    }
+}
+
+impl CommandLineBuilder for BoomCommandLineBuilder {
+    fn get_source_port(&self) -> SourcePort {
+        self.source_port
+    }
+
+    fn get_complevel_args(
+        &self,
+        complevel: CompLevel,
+        game: Option<Game>,
+    ) -> Result<Vec<String>, Report> {
+        if !self.supports_complevel {
+            return Ok(Vec::new());
+        }
+        Ok(vec![
+            "-complevel".to_string(),
+            complevel.get_number(game).to_string(),
+        ])
+    }
+}
+
+impl CommandLineBuilder for ZDoomCommandLineBuilder {
+    fn get_source_port(&self) -> SourcePort {
+        self.source_port
+    }
+
+    fn get_fullscreen_args(&self, fullscreen: bool) -> Result<Vec<String>, Report> {
+        if fullscreen {
+            return Ok(Vec::new());
+        }
+        Ok(vec![
+            "+set".to_string(),
+            self.fullscreen_cvar.to_string(),
+            "0".to_string(),
+        ])
+    }
+
+    /// The map is started with the `map` console command if it can't be reached with `-warp`.
+    fn get_map_args(&self, map: &MapInfo) -> Result<Vec<String>, Report> {
+        if let Some(warp_args) = map.warp.get_warp_args() {
+            let mut args = vec!["-warp".to_string()];
+            args.extend(warp_args);
+            return Ok(args);
+        }
+        Ok(vec!["+map".to_string(), map.number.clone()])
+    }
+}
+
+impl SourcePort {
+    pub fn get_command_line_builder(&self) -> Box<dyn CommandLineBuilder> {
+        let source_port = *self;
+        match self {
+            Self::Chocolate => Box::new(VanillaCommandLineBuilder {
+                source_port,
+                supports_bex: false,
+            }),
+            Self::Crispy => Box::new(VanillaCommandLineBuilder {
+                source_port,
+                supports_bex: true,
+            }),
+            Self::DoomRetro => Box::new(BoomCommandLineBuilder {
+                source_port,
+                supports_complevel: false,
+            }),
+            Self::Dsda => Box::new(BoomCommandLineBuilder {
+                source_port,
+                supports_complevel: true,
+            }),
+            Self::EternityEngine => Box::new(BoomCommandLineBuilder {
+                source_port,
+                supports_complevel: false,
+            }),
+            Self::GzDoom => Box::new(ZDoomCommandLineBuilder {
+                source_port,
+                fullscreen_cvar: "vid_fullscreen",
+            }),
+            Self::LzDoom => Box::new(ZDoomCommandLineBuilder {
+                source_port,
+                fullscreen_cvar: "fullscreen",
+            }),
+            Self::Odamex => Box::new(ZDoomCommandLineBuilder {
+                source_port,
+                fullscreen_cvar: "vid_fullscreen",
+            }),
+            Self::PrBoomPlus => Box::new(BoomCommandLineBuilder {
+                source_port,
+                supports_complevel: true,
+            }),
+            Self::Rude => Box::new(BoomCommandLineBuilder {
+                source_port,
+                supports_complevel: false,
+            }),
+            Self::Woof => Box::new(BoomCommandLineBuilder {
+                source_port,
+                supports_complevel: true,
+            }),
+            Self::Zandronum => Box::new(ZDoomCommandLineBuilder {
+                source_port,
+                fullscreen_cvar: "fullscreen",
+            }),
+        }
+    }
+}
+
+/// Builds the suggestion for an error about something the source port in the profile can't do,
+/// which lists the source ports that can.
+pub fn suggest_compatible_ports(pred: impl Fn(&SourcePort) -> bool) -> String {
+    let compatible = SourcePort::iter()
+        .filter(|x| pred(x))
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>();
+    format!(
+        "Use a profile with one of these source ports: {}",
+        compatible.join(", ")
+    )
+}
+
+fn get_deh_args(patches: &[PathBuf]) -> Vec<String> {
+    if patches.is_empty() {
+        return Vec::new();
+    }
+    let mut args = vec!["-deh".to_string()];
+    args.extend(patches.iter().map(|x| path_to_arg(x)));
+    args
+}
+
+fn path_to_arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod commandlinebuilder {
+    mod build {
+        use super::super::LaunchRequest;
+        use crate::complevel::CompLevel;
+        use crate::iwad::Game;
+        use crate::profile::Profile;
+        use crate::source_port::{Skill, SourcePort};
+        use crate::wad::MapInfo;
+        use std::path::PathBuf;
+
+        fn get_profile(source_port: SourcePort, fullscreen: bool, music: bool) -> Profile {
+            Profile::new(
+                "default",
+                source_port,
+                "1.0".to_string(),
+                Skill::UltraViolence,
+                fullscreen,
+                music,
+                true,
+            )
+            .unwrap()
+        }
+
+        fn get_request(map: &str) -> LaunchRequest {
+            LaunchRequest {
+                iwad: PathBuf::from("DOOM2.WAD"),
+                map: Some(MapInfo::new(map.to_string(), "Test".to_string()).unwrap()),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn should_use_the_boom_arguments_for_prboom_plus() {
+            let profile = get_profile(SourcePort::PrBoomPlus, false, false);
+            let args = SourcePort::PrBoomPlus
+                .get_command_line_builder()
+                .build(&profile, &get_request("MAP07"))
+                .unwrap();
+            assert_eq!(
+                args,
+                vec![
+                    "-iwad",
+                    "DOOM2.WAD",
+                    "-skill",
+                    "4",
+                    "-nomusic",
+                    "-nofullscreen",
+                    "-warp",
+                    "7"
+                ]
+            );
+        }
+
+        #[test]
+        fn should_use_a_window_argument_for_chocolate_doom() {
+            let profile = get_profile(SourcePort::Chocolate, false, true);
+            let args = SourcePort::Chocolate
+                .get_command_line_builder()
+                .build(&profile, &get_request("E1M7"))
+                .unwrap();
+            assert_eq!(
+                args,
+                vec![
+                    "-iwad",
+                    "DOOM2.WAD",
+                    "-skill",
+                    "4",
+                    "-window",
+                    "-warp",
+                    "1",
+                    "7"
+                ]
+            );
+        }
+
+        #[test]
+        fn should_use_console_variables_for_the_zdoom_family() {
+            let profile = get_profile(SourcePort::GzDoom, false, true);
+            let args = SourcePort::GzDoom
+                .get_command_line_builder()
+                .build(&profile, &get_request("START"))
+                .unwrap();
+            assert_eq!(
+                args,
+                vec![
+                    "-iwad",
+                    "DOOM2.WAD",
+                    "-skill",
+                    "4",
+                    "+set",
+                    "vid_fullscreen",
+                    "0",
+                    "+map",
+                    "START"
+                ]
+            );
+
+            let profile = get_profile(SourcePort::Zandronum, false, true);
+            let args = SourcePort::Zandronum
+                .get_command_line_builder()
+                .build(&profile, &get_request("MAP01"))
+                .unwrap();
+            assert!(args.ends_with(&[
+                "+set".to_string(),
+                "fullscreen".to_string(),
+                "0".to_string(),
+                "-warp".to_string(),
+                "1".to_string()
+            ]));
+        }
+
+        #[test]
+        fn should_reject_a_map_that_can_only_be_loaded_by_its_lump_name() {
+            let profile = get_profile(SourcePort::Dsda, true, true);
+            let result = SourcePort::Dsda
+                .get_command_line_builder()
+                .build(&profile, &get_request("START"));
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "DSDA Doom can't be started on START, since it can only be loaded by its lump name"
+            );
+        }
+
+        #[test]
+        fn should_reject_a_bex_patch_for_chocolate_doom() {
+            let mut request = get_request("MAP01");
+            request.patches = vec![PathBuf::from("patch.deh"), PathBuf::from("patch.bex")];
+
+            let profile = get_profile(SourcePort::Chocolate, true, true);
+            let result = SourcePort::Chocolate
+                .get_command_line_builder()
+                .build(&profile, &request);
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "patch.bex is a BEX patch, which Chocolate Doom can't apply"
+            );
+
+            let profile = get_profile(SourcePort::Crispy, true, true);
+            let args = SourcePort::Crispy
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert!(args.contains(&"patch.bex".to_string()));
+        }
+
+        #[test]
+        fn should_pass_the_complevel_to_the_ports_that_use_it() {
+            let mut request = get_request("MAP01");
+            request.complevel = Some(CompLevel::Vanilla);
+            request.game = Some(Game::Plutonia);
+
+            let profile = get_profile(SourcePort::Dsda, true, true);
+            let args = SourcePort::Dsda
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert_eq!(
+                args,
+                vec![
+                    "-iwad",
+                    "DOOM2.WAD",
+                    "-complevel",
+                    "4",
+                    "-skill",
+                    "4",
+                    "-warp",
+                    "1"
+                ]
+            );
+
+            request.complevel = Some(CompLevel::Mbf21);
+            let profile = get_profile(SourcePort::Woof, true, true);
+            let args = SourcePort::Woof
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert!(args.windows(2).any(|x| x == ["-complevel", "21"]));
+
+            let profile = get_profile(SourcePort::EternityEngine, true, true);
+            let args = SourcePort::EternityEngine
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert!(!args.contains(&"-complevel".to_string()));
+        }
+
+        #[test]
+        fn should_reject_a_boom_wad_for_the_vanilla_ports() {
+            let mut request = get_request("MAP01");
+            request.complevel = Some(CompLevel::Boom);
+
+            let profile = get_profile(SourcePort::Crispy, true, true);
+            let result = SourcePort::Crispy
+                .get_command_line_builder()
+                .build(&profile, &request);
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "This WAD is made for Boom, which Crispy Doom doesn't support"
+            );
+
+            request.complevel = Some(CompLevel::Vanilla);
+            assert!(SourcePort::Crispy
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .is_ok());
+        }
+
+        #[test]
+        fn should_play_back_a_demo_with_the_skill_and_map_from_the_demo() {
+            let request = LaunchRequest {
+                iwad: PathBuf::from("DOOM2.WAD"),
+                playdemo: Some(PathBuf::from("doom207m-001.lmp")),
+                ..Default::default()
+            };
+            let profile = get_profile(SourcePort::Dsda, true, true);
+            let args = SourcePort::Dsda
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert_eq!(
+                args,
+                vec!["-iwad", "DOOM2.WAD", "-playdemo", "doom207m-001.lmp"]
+            );
+
+            let request = LaunchRequest {
+                timedemo: true,
+                ..request
+            };
+            let args = SourcePort::Dsda
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert_eq!(
+                args,
+                vec!["-iwad", "DOOM2.WAD", "-timedemo", "doom207m-001.lmp"]
+            );
+        }
+
+        #[test]
+        fn should_render_a_demo_with_the_ports_that_support_it() {
+            let request = LaunchRequest {
+                iwad: PathBuf::from("DOOM2.WAD"),
+                playdemo: Some(PathBuf::from("doom207m-001.lmp")),
+                timedemo: true,
+                viddump: Some(PathBuf::from("doom207m-001.mkv")),
+                config: Some(PathBuf::from("render.cfg")),
+                ..Default::default()
+            };
+            let profile = get_profile(SourcePort::PrBoomPlus, true, true);
+            let args = SourcePort::PrBoomPlus
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert_eq!(
+                args,
+                vec![
+                    "-iwad",
+                    "DOOM2.WAD",
+                    "-config",
+                    "render.cfg",
+                    "-timedemo",
+                    "doom207m-001.lmp",
+                    "-viddump",
+                    "doom207m-001.mkv"
+                ]
+            );
+
+            let profile = get_profile(SourcePort::Woof, true, true);
+            let result = SourcePort::Woof
+                .get_command_line_builder()
+                .build(&profile, &request);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "Woof! can't render demos to video"
+            );
+        }
+
+        #[test]
+        fn should_write_the_levelstat_file_with_the_ports_that_support_it() {
+            let request = LaunchRequest {
+                levelstat: true,
+                ..get_request("MAP07")
+            };
+            let profile = get_profile(SourcePort::Dsda, true, true);
+            let args = SourcePort::Dsda
+                .get_command_line_builder()
+                .build(&profile, &request)
+                .unwrap();
+            assert_eq!(
+                args,
+                vec![
+                    "-iwad",
+                    "DOOM2.WAD",
+                    "-skill",
+                    "4",
+                    "-warp",
+                    "7",
+                    "-levelstat"
+                ]
+            );
+
+            let profile = get_profile(SourcePort::Chocolate, true, true);
+            let result = SourcePort::Chocolate
+                .get_command_line_builder()
+                .build(&profile, &request);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "Chocolate Doom can't write the statistics for the maps that are finished"
+            );
+        }
+    }
+}
@@ -0,0 +1,201 @@
+#[cfg(target_family = "unix")]
+pub use self::unix::run_browse_cmd;
+
+/// The browser is built on tuikit, which only supports Unix terminals.
+#[cfg(target_family = "windows")]
+pub fn run_browse_cmd(
+    _repository: &crate::storage::AppSettingsRepository,
+) -> color_eyre::Result<(), color_eyre::Report> {
+    use color_eyre::{eyre::eyre, Help};
+    Err(eyre!("The library browser isn't available on Windows yet")
+        .suggestion("Use the `play` command to pick a map with the finder"))
+}
+
+#[cfg(target_family = "unix")]
+mod unix {
+    use crate::browser::{Details, Library, LibraryWad, MapList, Pane, StatusBar, WadList};
+    use crate::commands::play::run_play_cmd;
+    use crate::favourite::{get_favourite_repository, get_favourites, Favourite};
+    use crate::progress::get_wad_progress;
+    use crate::settings::{get_app_settings_dir_path, get_user_settings};
+    use crate::storage::{AppSettingsRepository, ObjectRepository};
+    use crate::wad::WadEntry;
+    use color_eyre::{eyre::eyre, Help, Report, Result};
+    use log::LevelFilter;
+    use std::io::IsTerminal;
+    use tuikit::prelude::*;
+
+    /// The number of lines the text file is scrolled by with page up and page down.
+    const PAGE_SIZE: isize = 10;
+
+    /// Runs the full screen library browser, which lists the WADs, their maps, and the text file
+    /// for the selected WAD.
+    ///
+    /// The terminal is handed over while a game is being played, and the browser comes back once
+    /// it's exited, with the progress for the WAD updated.
+    pub fn run_browse_cmd(repository: &AppSettingsRepository) -> Result<(), Report> {
+        if !std::io::stdin().is_terminal() {
+            return Err(
+                eyre!("The library browser can only be used from a terminal")
+                    .suggestion("Use the `wad info` command to see the details of a WAD"),
+            );
+        }
+        let mut library = Library::new(get_library_wads()?, get_favourites()?);
+        if library.get_wads().is_empty() {
+            return Err(eyre!("You haven't imported any WADs yet")
+                .suggestion("Use the `wad import` or `iwad import` command to import one"));
+        }
+
+        // The repositories log what they save, which would be written over the browser.
+        let log_level = log::max_level();
+        log::set_max_level(log_level.min(LevelFilter::Warn));
+        let term: Term<()> = Term::with_height(TermHeight::Percent(100))?;
+        loop {
+            draw(&term, &library)?;
+            let key = match term.poll_event()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+            library.status = None;
+            match key {
+                Key::Char('q') | Key::ESC | Key::Ctrl('c') => break,
+                Key::Up | Key::Char('k') => library.move_selection(-1),
+                Key::Down | Key::Char('j') => library.move_selection(1),
+                Key::Tab | Key::BackTab => library.toggle_focus(),
+                Key::Right if library.get_focus() == Pane::Wads => library.toggle_focus(),
+                Key::Left if library.get_focus() == Pane::Maps => library.toggle_focus(),
+                Key::PageUp => library.scroll_details(-PAGE_SIZE),
+                Key::PageDown => library.scroll_details(PAGE_SIZE),
+                Key::Enter | Key::Char('p') => {
+                    play_selection(&term, &mut library, false, log_level, repository)?
+                }
+                Key::Char('r') => play_selection(&term, &mut library, true, log_level, repository)?,
+                Key::Char('f') => {
+                    if let Err(report) = toggle_favourite(&mut library) {
+                        library.status = Some(format!("Error: {}", report));
+                    }
+                }
+                _ => {}
+            }
+        }
+        log::set_max_level(log_level);
+        Ok(())
+    }
+
+    fn draw(term: &Term<()>, library: &Library) -> Result<(), Report> {
+        let wad_list = WadList(library);
+        let map_list = MapList(library);
+        let details = Details(library);
+        let status_bar = StatusBar(library);
+        let map_title = library
+            .get_selected_wad()
+            .map_or_else(|| "Maps".to_string(), |x| format!("Maps in {}", x.entry.id));
+        let layout = VSplit::default()
+            .split(
+                HSplit::default()
+                    .split(
+                        Win::new(&wad_list)
+                            .border(true)
+                            .title("WADs")
+                            .basis(Size::Percent(50)),
+                    )
+                    .split(
+                        Win::new(&map_list)
+                            .border(true)
+                            .title(map_title)
+                            .basis(Size::Percent(50)),
+                    ),
+            )
+            .split(
+                Win::new(&details)
+                    .border(true)
+                    .title("Details")
+                    .basis(Size::Percent(40)),
+            )
+            .split(
+                Win::new(&status_bar)
+                    .basis(Size::Fixed(1))
+                    .grow(0)
+                    .shrink(0),
+            );
+        term.draw(&layout)?;
+        term.present()?;
+        Ok(())
+    }
+
+    /// The game is played with the default profile, the same as the `play` command does when no
+    /// profile is given. The usual logging is back on while the terminal is handed over, so the
+    /// output is the same as the `play` command's. Something going wrong with the game is shown in
+    /// the status bar, rather than closing the browser.
+    fn play_selection(
+        term: &Term<()>,
+        library: &mut Library,
+        record: bool,
+        log_level: LevelFilter,
+        repository: &AppSettingsRepository,
+    ) -> Result<(), Report> {
+        let (wad_id, map) = match library.get_selection() {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+        let description = Favourite::get_description(&wad_id, map.as_deref());
+        term.pause()?;
+        let browser_log_level = log::max_level();
+        log::set_max_level(log_level);
+        let result = run_play_cmd(wad_id, map, None, record, None, false, repository);
+        log::set_max_level(browser_log_level);
+        term.restart()?;
+        library.status = Some(match result {
+            Ok(()) => format!("Finished playing {}", description),
+            Err(report) => format!("Error: {}", report),
+        });
+        library.set_wads(get_library_wads()?);
+        Ok(())
+    }
+
+    /// Adds the selection to the favourites, or removes it if it's already there. With the focus on
+    /// the map list, it's the map on its own that's added or removed.
+    fn toggle_favourite(library: &mut Library) -> Result<(), Report> {
+        let (wad_id, map) = match library.get_selection() {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+        let description = Favourite::get_description(&wad_id, map.as_deref());
+        let repository = get_favourite_repository()?;
+        let id = Favourite::get_id(&wad_id, map.as_deref());
+        library.status = if repository.exists(&id) {
+            repository.delete(&id)?;
+            Some(format!("Removed {} from your favourites", description))
+        } else {
+            repository.save(&id, &Favourite::new(&wad_id, map.as_deref()))?;
+            Some(format!("Added {} to your favourites", description))
+        };
+        library.set_favourites(get_favourites()?);
+        Ok(())
+    }
+
+    /// Reads every imported WAD, along with its progress and text file, sorted by title.
+    fn get_library_wads() -> Result<Vec<LibraryWad>, Report> {
+        let mut wads_path = get_app_settings_dir_path()?;
+        wads_path.push("wads");
+        let user_settings = get_user_settings()?;
+        let mut entries: Vec<WadEntry> = ObjectRepository::new(&wads_path)?.get_all()?;
+        entries.sort_by_key(|x| x.title.to_lowercase());
+        let mut wads = Vec::new();
+        for entry in entries {
+            // The text files aren't always UTF-8, especially the older ones.
+            let text_file_path = user_settings.wads_path.join(format!("{}.txt", entry.id));
+            let text_file = if text_file_path.exists() {
+                Some(String::from_utf8_lossy(&std::fs::read(&text_file_path)?).into_owned())
+            } else {
+                None
+            };
+            wads.push(LibraryWad {
+                progress: get_wad_progress(&entry.id)?,
+                entry,
+                text_file,
+            });
+        }
+        Ok(wads)
+    }
+}
@@ -0,0 +1,495 @@
+use crate::commands::play::{get_demo_path, get_launch_request, get_profile, launch_source_port};
+use crate::commands::{get_wad_entry, wad_entry_exists};
+use crate::demo::{format_duration, get_encoded_tics, DemoEntry, DemoHeader};
+use crate::profile::Profile;
+use crate::session::{
+    check_exit_status, describe_exit_status, get_session_log_path, show_last_output,
+};
+use crate::settings::{get_app_settings_dir_path, get_user_settings};
+use crate::settings::{AppSettings, UserSettings};
+use crate::source_port::{InstalledSourcePort, SourcePort};
+use crate::storage::{AppSettingsRepository, ObjectRepository};
+use crate::wad::{WadEntry, Warp, UNKNOWN_VALUE};
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info};
+use prettytable::{row, Table};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use strum::IntoEnumIterator;
+
+#[derive(Debug, StructOpt)]
+pub enum DemoCommand {
+    #[structopt(name = "info")]
+    /// Shows the information in the header of a demo, e.g. the skill and map it was recorded on
+    Info {
+        /// The path to the demo
+        path: PathBuf,
+    },
+    #[structopt(name = "ls")]
+    /// Lists all the demos that have been recorded
+    Ls,
+    #[structopt(name = "render")]
+    /// Renders a demo to a video, using the encoder options from the profile
+    Render {
+        /// The ID of a recorded demo, e.g. doom207m-001, or the path to a demo file
+        demo: String,
+        #[structopt(short, long)]
+        /// The path to write the video to, e.g. demo.mkv
+        output: PathBuf,
+        #[structopt(short, long)]
+        /// The WAD the demo was recorded with, e.g. DOOM2. This only needs to be specified for
+        /// demos that weren't recorded with TDL.
+        megawad: Option<String>,
+        #[structopt(short, long)]
+        /// The profile with the encoder options. If its source port can't render the demo,
+        /// another installed source port that can is used.
+        profile: Option<String>,
+    },
+    #[structopt(name = "play")]
+    /// Plays back a demo, using a source port that supports the version it was recorded with
+    Play {
+        /// The ID of a recorded demo, e.g. doom207m-001, or the path to a demo file
+        demo: String,
+        #[structopt(short, long)]
+        /// The WAD the demo was recorded with, e.g. DOOM2. This only needs to be specified for
+        /// demos that weren't recorded with TDL.
+        megawad: Option<String>,
+        #[structopt(short, long)]
+        /// The profile to play the demo back with. If its source port can't play the demo,
+        /// another installed source port that can is used.
+        profile: Option<String>,
+        #[structopt(short, long)]
+        /// Play the demo back as fast as possible, to time how long it takes
+        timedemo: bool,
+    },
+}
+
+pub fn run_demo_cmd(cmd: DemoCommand, repository: AppSettingsRepository) -> Result<(), Report> {
+    match cmd {
+        DemoCommand::Info { path } => print_demo_info(&path),
+        DemoCommand::Ls => list_demos(),
+        DemoCommand::Play {
+            demo,
+            megawad,
+            profile,
+            timedemo,
+        } => play_demo(&demo, megawad, profile, timedemo, repository),
+        DemoCommand::Render {
+            demo,
+            output,
+            megawad,
+            profile,
+        } => render_demo(&demo, &output, megawad, profile, repository),
+    }
+}
+
+/// Gets the repository the entries for recorded demos are saved to.
+pub fn get_demo_repository() -> Result<ObjectRepository, Report> {
+    let mut demos_entry_path = get_app_settings_dir_path()?;
+    demos_entry_path.push("demos");
+    Ok(ObjectRepository::new(&demos_entry_path)?)
+}
+
+/// Prints the header of the demo. If it's one that was recorded by TDL, the WAD it was recorded
+/// with is known, which also means the map number can be given in the right form.
+fn print_demo_info(path: &Path) -> Result<(), Report> {
+    let header = DemoHeader::from_path(path)?;
+    let demo_entry = find_demo_entry(path)?;
+    let wad_entry = match &demo_entry {
+        Some(demo) if wad_entry_exists(&demo.wad_id)? => Some(get_wad_entry(&demo.wad_id)?),
+        _ => None,
+    };
+
+    info!("Demo: {}", path.display());
+    if let Some(demo) = &demo_entry {
+        info!("WAD: {}", demo.wad_id);
+        info!("Category: {}", demo.category);
+        info!(
+            "Source port: {} {}",
+            demo.source_port, demo.source_port_version
+        );
+        info!("Recorded: {}", demo.recorded.format("%Y-%m-%d %H:%M"));
+    }
+    info!("Format: {}", header.format);
+    info!("Version: {}", header.get_version_name());
+    info!("Complevel: {}", header.complevel);
+    info!("Skill: {}", header.skill);
+    info!("Map: {}", get_map_number(&header, wad_entry.as_ref()));
+    info!("Mode: {}", get_mode(header.deathmatch));
+    if header.respawn {
+        info!("Respawn: on");
+    }
+    if header.fast {
+        info!("Fast monsters: on");
+    }
+    if header.no_monsters {
+        info!("No monsters: on");
+    }
+    let players = header
+        .players
+        .iter()
+        .map(|x| (x + 1).to_string())
+        .collect::<Vec<String>>();
+    info!("Players: {}", players.join(", "));
+    info!("Console player: {}", header.console_player + 1);
+    info!("Longtics: {}", if header.longtics { "yes" } else { "no" });
+    if !header.options.is_empty() {
+        let options = header
+            .options
+            .iter()
+            .map(|(name, value)| format!("{} {}", name, value))
+            .collect::<Vec<String>>();
+        info!("Options: {}", options.join(", "));
+    }
+    info!("Tics: {}", header.tics);
+    info!("Duration: {}", format_duration(header.get_duration()));
+    Ok(())
+}
+
+/// Finds the entry for a demo file that was recorded with TDL, which is saved under the same name.
+fn find_demo_entry(path: &Path) -> Result<Option<DemoEntry>, Report> {
+    let id = match path.file_stem().and_then(|x| x.to_str()) {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let repository = get_demo_repository()?;
+    if !repository.exists(id) {
+        return Ok(None);
+    }
+    Ok(Some(repository.get(id)?))
+}
+
+/// Plays the demo back with the WAD it was recorded with. The demo's header has everything else,
+/// including the complevel, skill and map, so none of those are passed.
+fn play_demo(
+    demo: &str,
+    megawad: Option<String>,
+    profile: Option<String>,
+    timedemo: bool,
+    repository: AppSettingsRepository,
+) -> Result<(), Report> {
+    let settings = repository.get()?;
+    let profile = get_profile(&settings, profile)?;
+    let user_settings = get_user_settings()?;
+    let (path, demo_entry) = get_demo(demo, &user_settings)?;
+    let header = DemoHeader::from_path(&path)?;
+    let wad_entry = get_demo_wad_entry(megawad, demo_entry, &path)?;
+    let source_port = get_source_port_for_demo(&settings, profile, &header, &path, false)?;
+
+    let mut request = get_launch_request(&wad_entry, &user_settings)?;
+    request.complevel = None;
+    request.playdemo = Some(path);
+    request.timedemo = timedemo;
+    let args = source_port
+        .name
+        .get_command_line_builder()
+        .build(profile, &request)?;
+    let log_path = get_session_log_path(&wad_entry.id)?;
+    let status = launch_source_port(source_port, &args, &log_path)?;
+    check_exit_status(source_port.name, status, &log_path)
+}
+
+/// Renders the demo to a video with the encoder options from the profile.
+///
+/// The demo is played with `-timedemo`, so the game runs as fast as the encoding allows, and with
+/// SDL's offscreen video driver, so no window is opened. The encoders write a progress line for
+/// each frame or so, which is used to show how far through the demo the render is.
+fn render_demo(
+    demo: &str,
+    output: &Path,
+    megawad: Option<String>,
+    profile: Option<String>,
+    repository: AppSettingsRepository,
+) -> Result<(), Report> {
+    let settings = repository.get()?;
+    let profile = get_profile(&settings, profile)?;
+    let user_settings = get_user_settings()?;
+    let (path, demo_entry) = get_demo(demo, &user_settings)?;
+    let header = DemoHeader::from_path(&path)?;
+    let wad_entry = get_demo_wad_entry(megawad, demo_entry, &path)?;
+    let source_port = get_source_port_for_demo(&settings, profile, &header, &path, true)?;
+    let output = std::env::current_dir()?.join(output);
+    let mut source_port_dir = source_port.path.to_owned();
+    source_port_dir.pop();
+
+    // The ports only read the encoder options from their config file, and they only read one, so
+    // the options go in a copy of the port's own config.
+    let config_file = if profile.render.get_config().is_empty() {
+        None
+    } else {
+        let port_config = match source_port.name.get_config_path(&source_port_dir) {
+            Some(config_path) => {
+                debug!("Using the settings from {}", config_path.display());
+                String::from_utf8_lossy(&std::fs::read(config_path)?).into_owned()
+            }
+            None => String::new(),
+        };
+        let mut config_file = tempfile::Builder::new().suffix(".cfg").tempfile()?;
+        config_file.write_all(profile.render.merge_config(&port_config).as_bytes())?;
+        Some(config_file)
+    };
+    // A video left behind by an earlier render would look like this one had worked.
+    if output.is_file() {
+        info!("Replacing {}", output.display());
+        std::fs::remove_file(&output)?;
+    }
+
+    let mut request = get_launch_request(&wad_entry, &user_settings)?;
+    request.complevel = None;
+    request.playdemo = Some(path.clone());
+    request.timedemo = true;
+    request.viddump = Some(output.clone());
+    request.config = config_file.as_ref().map(|x| x.path().to_path_buf());
+    let args = source_port
+        .name
+        .get_command_line_builder()
+        .build(profile, &request)?;
+
+    info!("Rendering {} with {}", path.display(), source_port.name);
+    info!("Using arguments: {}", args.join(" "));
+    let reader = duct::cmd(&source_port.path, &args)
+        .dir(source_port_dir)
+        .env("SDL_VIDEODRIVER", "offscreen")
+        .stderr_to_stdout()
+        .unchecked()
+        .reader()?;
+    let bar = ProgressBar::new(u64::from(header.tics));
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{bar:40}] {pos}/{len} tics ({eta})")
+            .progress_chars("=>-"),
+    );
+    // The encoders rewrite their progress line with a carriage return rather than a new line.
+    let fps = profile.render.get_fps();
+    let mut line = Vec::new();
+    let mut output_lines = Vec::new();
+    for byte in BufReader::new(&reader).bytes() {
+        let byte = byte?;
+        if byte != b'\r' && byte != b'\n' {
+            line.push(byte);
+            continue;
+        }
+        let text = String::from_utf8_lossy(&line);
+        match get_encoded_tics(&text, fps) {
+            Some(tics) => bar.set_position(u64::from(tics.min(header.tics))),
+            None if !text.trim().is_empty() => {
+                debug!("{}", text);
+                output_lines.push(text.into_owned());
+            }
+            None => {}
+        }
+        line.clear();
+    }
+    bar.finish();
+
+    // The output is only logged at debug level, so if the port crashed, the last of it is shown.
+    let status = reader
+        .try_wait()?
+        .ok_or_else(|| eyre!("The source port closed its output without exiting"))?
+        .status;
+    if !status.success() {
+        let output_lines = output_lines
+            .iter()
+            .map(|x| x.as_str())
+            .collect::<Vec<&str>>();
+        show_last_output(source_port.name, &output_lines);
+        return Err(eyre!(
+            "{} exited with status {}",
+            source_port.name,
+            describe_exit_status(status)
+        ));
+    }
+
+    if !output.is_file() {
+        return Err(eyre!(
+            "{} finished without writing the video to {}",
+            source_port.name,
+            output.display()
+        )
+        .suggestion(
+            "Check the encoder commands in the profile, and that the encoders are installed",
+        ));
+    }
+    info!("Rendered {} to {}", path.display(), output.display());
+    Ok(())
+}
+
+/// Gets the entry for the WAD the demo was recorded with. It's only known for demos recorded with
+/// TDL, so for any other demo it has to be specified.
+fn get_demo_wad_entry(
+    megawad: Option<String>,
+    demo_entry: Option<DemoEntry>,
+    path: &Path,
+) -> Result<WadEntry, Report> {
+    let wad_id = megawad
+        .or_else(|| demo_entry.map(|x| x.wad_id))
+        .ok_or_else(|| {
+            eyre!(
+                "{} wasn't recorded with TDL, so its WAD isn't known",
+                path.display()
+            )
+            .suggestion("Use the `--megawad` argument to specify the WAD")
+        })?;
+    if !wad_entry_exists(&wad_id)? {
+        return Err(
+            eyre!("The demo is for {}, which hasn't been imported", wad_id)
+                .suggestion("Use the `wad import` or `iwad import` command to import it"),
+        );
+    }
+    get_wad_entry(&wad_id)
+}
+
+/// A demo can be specified by the ID of its entry, if it was recorded with TDL, or by its path.
+fn get_demo(
+    demo: &str,
+    user_settings: &UserSettings,
+) -> Result<(PathBuf, Option<DemoEntry>), Report> {
+    let repository = get_demo_repository()?;
+    if repository.exists(demo) {
+        let demo_entry: DemoEntry = repository.get(demo)?;
+        return Ok((get_demo_path(&demo_entry, user_settings), Some(demo_entry)));
+    }
+    // The source port runs from its own directory, so a relative path would end up there.
+    let path = std::env::current_dir()?.join(demo);
+    if !path.is_file() {
+        return Err(
+            eyre!("There is no demo with the ID {}, or at that path", demo)
+                .suggestion("Use the `demo ls` command to list the recorded demos"),
+        );
+    }
+    let demo_entry = find_demo_entry(&path)?;
+    Ok((path, demo_entry))
+}
+
+/// Picks the source port to play the demo back with, or to render it with. The profile's source
+/// port is used if it can; otherwise it's the first installed source port that can.
+fn get_source_port_for_demo<'a>(
+    settings: &'a AppSettings,
+    profile: &Profile,
+    header: &DemoHeader,
+    path: &Path,
+    render: bool,
+) -> Result<&'a InstalledSourcePort, Report> {
+    let is_compatible =
+        |x: &SourcePort| x.supports_demo(header) && (!render || x.supports_video_dump());
+    let mut compatible = settings
+        .source_ports
+        .iter()
+        .filter(|x| is_compatible(&x.name))
+        .collect::<Vec<&InstalledSourcePort>>();
+    compatible.sort_by_key(|x| x.name != profile.source_port);
+    let action = if render { "render" } else { "play" };
+    match compatible.first() {
+        Some(source_port) => {
+            if source_port.name != profile.source_port {
+                info!(
+                    "{} can't {} this demo, so {} will be used instead",
+                    profile.source_port, action, source_port.name
+                );
+            }
+            Ok(source_port)
+        }
+        None => {
+            let needed = SourcePort::iter()
+                .filter(is_compatible)
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>();
+            Err(eyre!(
+                "None of the installed source ports can {} {}, which is a {} demo",
+                action,
+                path.display(),
+                header.get_version_name()
+            )
+            .suggestion(format!(
+                "Install or add one of these source ports: {}",
+                needed.join(", ")
+            )))
+        }
+    }
+}
+
+fn list_demos() -> Result<(), Report> {
+    let mut demos = get_demo_repository()?.get_all::<DemoEntry>()?;
+    if demos.is_empty() {
+        info!("No demos have been recorded yet.");
+        info!("Use `play --record` to record a demo.");
+        return Ok(());
+    }
+    demos.sort_by(|a, b| a.id.cmp(&b.id));
+    let user_settings = get_user_settings()?;
+    let mut table = Table::new();
+    table.add_row(row![
+        "Demo",
+        "WAD",
+        "Map",
+        "Skill",
+        "Category",
+        "Duration",
+        "Source Port"
+    ]);
+    for demo in demos {
+        let path = get_demo_path(&demo, &user_settings);
+        let wad_entry = if wad_entry_exists(&demo.wad_id)? {
+            Some(get_wad_entry(&demo.wad_id)?)
+        } else {
+            None
+        };
+        // A demo that can't be read shouldn't stop the others from being listed.
+        let (map, skill, duration) = match DemoHeader::from_path(&path) {
+            Ok(header) => (
+                get_map_number(&header, wad_entry.as_ref()),
+                header.skill.to_string(),
+                format_duration(header.get_duration()),
+            ),
+            Err(_) => (
+                demo.map.clone().unwrap_or_default(),
+                UNKNOWN_VALUE.to_string(),
+                UNKNOWN_VALUE.to_string(),
+            ),
+        };
+        table.add_row(row![
+            demo.id,
+            demo.wad_id,
+            map,
+            skill,
+            demo.category,
+            duration,
+            format!("{} {}", demo.source_port, demo.source_port_version)
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// The header has an episode even for the games that don't have them, so it takes the WAD to know
+/// which form the map number should take. Maps that can only be loaded by their lump name don't
+/// say either way. Without the WAD, anything past the first episode must be ExMy, but the first
+/// episode could be either.
+fn get_map_number(header: &DemoHeader, wad_entry: Option<&WadEntry>) -> String {
+    let has_episodes = wad_entry.and_then(|x| {
+        x.maps.iter().find_map(|map| match map.warp {
+            Warp::Map(_) => Some(false),
+            Warp::Episode(..) => Some(true),
+            Warp::LumpName(_) => None,
+        })
+    });
+    match has_episodes {
+        Some(has_episodes) => header.get_map_number(has_episodes),
+        None if header.map_lump_name.is_some() || header.episode > 1 => header.get_map_number(true),
+        None => format!(
+            "{} or {}",
+            header.get_map_number(true),
+            header.get_map_number(false)
+        ),
+    }
+}
+
+fn get_mode(deathmatch: u8) -> &'static str {
+    match deathmatch {
+        0 => "Cooperative",
+        1 => "Deathmatch",
+        _ => "Altdeath",
+    }
+}
@@ -0,0 +1,116 @@
+use crate::commands::{get_wad_entry, wad_entry_exists};
+use crate::favourite::{get_favourite_repository, get_favourites, Favourite};
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use log::info;
+use prettytable::{row, Table};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum FavouriteCommand {
+    #[structopt(name = "add")]
+    /// Adds a WAD, or one of its maps, to your favourites
+    Add {
+        #[structopt(short, long)]
+        /// The ID of the WAD, e.g. DOOM2
+        megawad: String,
+        #[structopt(short = "w", long)]
+        /// The map, e.g. MAP07. If not supplied, the whole WAD is added.
+        map: Option<String>,
+    },
+    #[structopt(name = "rm")]
+    /// Removes a WAD, or one of its maps, from your favourites
+    Rm {
+        #[structopt(short, long)]
+        /// The ID of the WAD, e.g. DOOM2
+        megawad: String,
+        #[structopt(short = "w", long)]
+        /// The map, e.g. MAP07. If not supplied, the WAD itself is removed, but any of its maps
+        /// that were added on their own are kept.
+        map: Option<String>,
+    },
+    #[structopt(name = "ls")]
+    /// Lists your favourite WADs and maps
+    Ls,
+}
+
+pub fn run_favourite_cmd(cmd: FavouriteCommand) -> Result<(), Report> {
+    match cmd {
+        FavouriteCommand::Add { megawad, map } => add_favourite(&megawad, map.as_deref()),
+        FavouriteCommand::Rm { megawad, map } => remove_favourite(&megawad, map.as_deref()),
+        FavouriteCommand::Ls => list_favourites(),
+    }
+}
+
+/// The WAD has to have been imported, and the map has to be in it, so the finder can show the
+/// favourite.
+fn add_favourite(wad_id: &str, map: Option<&str>) -> Result<(), Report> {
+    if !wad_entry_exists(wad_id)? {
+        return Err(eyre!("{} hasn't been imported", wad_id)
+            .suggestion("Use the `wad import` or `iwad import` command to import it"));
+    }
+    let wad_entry = get_wad_entry(wad_id)?;
+    if let Some(map) = map {
+        if !wad_entry.maps.iter().any(|x| x.number == map) {
+            return Err(eyre!("Could not find {} in {}", map, wad_entry.id));
+        }
+    }
+    let description = Favourite::get_description(&wad_entry.id, map);
+    let repository = get_favourite_repository()?;
+    let id = Favourite::get_id(&wad_entry.id, map);
+    if repository.exists(&id) {
+        return Err(eyre!("{} is already one of your favourites", description));
+    }
+    repository.save(&id, &Favourite::new(&wad_entry.id, map))?;
+    info!("Added {} to your favourites", description);
+    Ok(())
+}
+
+fn remove_favourite(wad_id: &str, map: Option<&str>) -> Result<(), Report> {
+    let description = Favourite::get_description(wad_id, map);
+    let repository = get_favourite_repository()?;
+    let id = Favourite::get_id(wad_id, map);
+    if !repository.exists(&id) {
+        return Err(eyre!("{} isn't one of your favourites", description)
+            .suggestion("Use the `fav ls` command to see your favourites"));
+    }
+    repository.delete(&id)?;
+    info!("Removed {} from your favourites", description);
+    Ok(())
+}
+
+fn list_favourites() -> Result<(), Report> {
+    let mut favourites = get_favourites()?;
+    if favourites.is_empty() {
+        info!("You don't have any favourites yet.");
+        info!("Use the `fav add` command to add a WAD or a map.");
+        return Ok(());
+    }
+    favourites.sort_by(|a, b| (&a.wad_id, &a.map).cmp(&(&b.wad_id, &b.map)));
+    let mut table = Table::new();
+    table.add_row(row!["WAD", "Map", "Name", "Added"]);
+    for favourite in favourites {
+        // A favourite whose WAD has since been removed is still listed, so it can be removed.
+        let name = if wad_entry_exists(&favourite.wad_id)? {
+            let wad_entry = get_wad_entry(&favourite.wad_id)?;
+            match &favourite.map {
+                Some(map) => wad_entry
+                    .maps
+                    .iter()
+                    .find(|x| x.number == *map)
+                    .map(|x| x.name.clone())
+                    .unwrap_or_default(),
+                None => wad_entry.title,
+            }
+        } else {
+            String::new()
+        };
+        table.add_row(row![
+            favourite.wad_id,
+            favourite.map.unwrap_or_default(),
+            name,
+            favourite.added.format("%Y-%m-%d")
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
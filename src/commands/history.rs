@@ -0,0 +1,77 @@
+use crate::session::{
+    get_play_session_repository, PlaySession, PlaySessionFilter, NO_EXIT_STATUS_DESCRIPTION,
+};
+use crate::wad::UNKNOWN_VALUE;
+use chrono::{Duration, Local};
+use color_eyre::{Report, Result};
+use log::info;
+use prettytable::{row, Table};
+
+pub fn run_history_cmd(filter: PlaySessionFilter) -> Result<(), Report> {
+    let mut sessions = get_play_session_repository()?
+        .get_all::<PlaySession>()?
+        .into_iter()
+        .filter(|x| filter.matches(x))
+        .collect::<Vec<PlaySession>>();
+    if sessions.is_empty() {
+        info!("No games have been played that match the filters.");
+        return Ok(());
+    }
+    sessions.sort_by_key(|x| x.started);
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Started",
+        "WAD",
+        "Map",
+        "Profile",
+        "Source Port",
+        "Played For",
+        "Exit Code"
+    ]);
+    for session in &sessions {
+        table.add_row(row![
+            session
+                .started
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            session.wad_id,
+            session.map.clone().unwrap_or_default(),
+            session.profile,
+            format!("{} {}", session.source_port, session.source_port_version),
+            session
+                .get_duration()
+                .map(format_play_time)
+                .unwrap_or_else(|| UNKNOWN_VALUE.to_string()),
+            get_exit_code_description(session)
+        ]);
+    }
+    table.printstd();
+
+    let total = sessions
+        .iter()
+        .filter_map(|x| x.get_duration())
+        .fold(Duration::zero(), |total, x| total + x);
+    info!("Total play time: {}", format_play_time(total));
+    Ok(())
+}
+
+/// A session that's ended without an exit code was killed by a signal.
+fn get_exit_code_description(session: &PlaySession) -> String {
+    match (session.ended, session.exit_code) {
+        (None, _) => NO_EXIT_STATUS_DESCRIPTION.to_string(),
+        (Some(_), None) => "None (killed by a signal)".to_string(),
+        (Some(_), Some(code)) => code.to_string(),
+    }
+}
+
+/// Formats the time as h:mm:ss, since a session can go on for hours.
+fn format_play_time(duration: Duration) -> String {
+    let seconds = duration.num_seconds();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
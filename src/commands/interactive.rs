@@ -0,0 +1,207 @@
+use crate::commands::browse::run_browse_cmd;
+use crate::commands::play::{get_last_session, get_session_to_continue, run_play_cmd};
+use crate::favourite::get_favourites;
+use crate::find::{select_item, select_map_to_play};
+use crate::session::PlaySession;
+use crate::storage::AppSettingsRepository;
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use log::{error, info};
+
+/// The things that can be done from the menu.
+#[derive(Debug, PartialEq)]
+enum MenuAction {
+    PlayMap,
+    PlayFavourite,
+    Continue { wad_id: String, map: Option<String> },
+    Next { wad_id: String, map: String },
+    Browse,
+    Quit,
+}
+
+impl std::fmt::Display for MenuAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PlayMap => write!(f, "Play a map"),
+            Self::PlayFavourite => write!(f, "Play one of your favourites"),
+            Self::Continue {
+                wad_id,
+                map: Some(map),
+            } => write!(f, "Continue {} on {}", wad_id, map),
+            Self::Continue { wad_id, map: None } => write!(f, "Continue {}", wad_id),
+            Self::Next { wad_id, map } => write!(f, "Play the map after {} in {}", map, wad_id),
+            Self::Browse => write!(f, "Browse your library"),
+            Self::Quit => write!(f, "Quit"),
+        }
+    }
+}
+
+/// Runs TDL as a menu, for when it's run without a subcommand.
+///
+/// The menu uses the same finder as the `play` command, so a map can be picked, then a profile,
+/// and the game is launched. Once the game has exited, it's back to the menu, so that the next map
+/// is only a couple of keys away. Cancelling the finder on the menu quits, while cancelling it
+/// after that goes back to the menu.
+pub fn run_interactive_cmd(repository: &AppSettingsRepository) -> Result<(), Report> {
+    loop {
+        let actions = get_menu_actions(get_last_session()?.as_ref(), !get_favourites()?.is_empty());
+        let labels = actions
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        let action = match select_item("What would you like to do?", &labels)? {
+            Some(index) => &actions[index],
+            None => return Ok(()),
+        };
+        if *action == MenuAction::Quit {
+            return Ok(());
+        }
+        // Something going wrong with one game, like the port crashing, shouldn't end the session.
+        if let Err(report) = run_menu_action(action, repository) {
+            error!("{:?}", report);
+        }
+    }
+}
+
+/// The continue options are only offered once something has been played, and the option to play
+/// the next map needs the last game to have been started on a map.
+fn get_menu_actions(last: Option<&PlaySession>, has_favourites: bool) -> Vec<MenuAction> {
+    let mut actions = Vec::new();
+    if let Some(last) = last {
+        actions.push(MenuAction::Continue {
+            wad_id: last.wad_id.clone(),
+            map: last.map.clone(),
+        });
+        if let Some(map) = &last.map {
+            actions.push(MenuAction::Next {
+                wad_id: last.wad_id.clone(),
+                map: map.clone(),
+            });
+        }
+    }
+    actions.push(MenuAction::PlayMap);
+    if has_favourites {
+        actions.push(MenuAction::PlayFavourite);
+    }
+    actions.push(MenuAction::Browse);
+    actions.push(MenuAction::Quit);
+    actions
+}
+
+fn run_menu_action(action: &MenuAction, repository: &AppSettingsRepository) -> Result<(), Report> {
+    let (wad_id, map, profile) = match action {
+        MenuAction::PlayMap | MenuAction::PlayFavourite => {
+            let favourites_only = *action == MenuAction::PlayFavourite;
+            let (wad_id, map) = match select_map_to_play(favourites_only)? {
+                Some(selected) => selected,
+                None => return Ok(()),
+            };
+            let profile = match select_profile(repository)? {
+                Some(profile) => profile,
+                None => return Ok(()),
+            };
+            (wad_id, Some(map), profile)
+        }
+        MenuAction::Continue { .. } => get_session_to_continue(false)?,
+        MenuAction::Next { .. } => get_session_to_continue(true)?,
+        MenuAction::Browse => return run_browse_cmd(repository),
+        MenuAction::Quit => return Ok(()),
+    };
+    info!(
+        "Playing {}{} with the {} profile",
+        wad_id,
+        map.as_ref()
+            .map(|x| format!(" on {}", x))
+            .unwrap_or_default(),
+        profile
+    );
+    run_play_cmd(wad_id, map, Some(profile), false, None, false, repository)
+}
+
+/// The profile only needs to be picked if there's more than one. The default profile is listed
+/// first.
+fn select_profile(repository: &AppSettingsRepository) -> Result<Option<String>, Report> {
+    let mut profiles = repository.get()?.profiles;
+    if profiles.is_empty() {
+        return Err(eyre!("You don't have any profiles yet")
+            .suggestion("Use the `profile add` command to add one"));
+    }
+    if profiles.len() == 1 {
+        return Ok(Some(profiles.remove(0).name));
+    }
+    profiles.sort_by_key(|x| !x.default);
+    let labels = profiles
+        .iter()
+        .map(|x| {
+            format!(
+                "{} ({}, {}){}",
+                x.name,
+                x.source_port,
+                x.skill,
+                if x.default { " [Default]" } else { "" }
+            )
+        })
+        .collect::<Vec<String>>();
+    Ok(select_item("Please select a profile", &labels)?.map(|index| profiles.remove(index).name))
+}
+
+#[cfg(test)]
+mod get_menu_actions {
+    use super::{get_menu_actions, MenuAction};
+    use crate::session::PlaySession;
+    use crate::source_port::SourcePort;
+    use chrono::Utc;
+
+    fn get_session(map: Option<&str>) -> PlaySession {
+        PlaySession {
+            id: "20210301-200000-000-doom2".to_string(),
+            wad_id: "DOOM2".to_string(),
+            map: map.map(String::from),
+            profile: "default".to_string(),
+            source_port: SourcePort::Dsda,
+            source_port_version: "0.24.3".to_string(),
+            skill: None,
+            levelstat_path: None,
+            started: Utc::now(),
+            ended: None,
+            exit_code: None,
+        }
+    }
+
+    #[test]
+    fn should_only_offer_to_play_a_map_when_nothing_has_been_played() {
+        let actions = get_menu_actions(None, false);
+        assert_eq!(
+            actions,
+            vec![MenuAction::PlayMap, MenuAction::Browse, MenuAction::Quit]
+        );
+    }
+
+    #[test]
+    fn should_offer_to_continue_or_play_the_next_map_after_the_last_session() {
+        let actions = get_menu_actions(Some(&get_session(Some("MAP07"))), true);
+        assert_eq!(
+            actions,
+            vec![
+                MenuAction::Continue {
+                    wad_id: "DOOM2".to_string(),
+                    map: Some("MAP07".to_string())
+                },
+                MenuAction::Next {
+                    wad_id: "DOOM2".to_string(),
+                    map: "MAP07".to_string()
+                },
+                MenuAction::PlayMap,
+                MenuAction::PlayFavourite,
+                MenuAction::Browse,
+                MenuAction::Quit
+            ]
+        );
+    }
+
+    #[test]
+    fn should_not_offer_the_next_map_after_a_session_started_from_the_title_screen() {
+        let actions = get_menu_actions(Some(&get_session(None)), false);
+        assert_eq!(actions[0].to_string(), "Continue DOOM2");
+        assert!(!actions.iter().any(|x| matches!(x, MenuAction::Next { .. })));
+    }
+}
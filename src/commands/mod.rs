@@ -1,13 +1,31 @@
+pub mod browse;
+pub mod demo;
+pub mod favourite;
+pub mod history;
+pub mod interactive;
 pub mod iwad;
 pub mod play;
 pub mod profile;
+pub mod session_log;
 pub mod source_port;
 pub mod wad;
 
+use crate::commands::demo::DemoCommand;
+use crate::commands::favourite::FavouriteCommand;
 use crate::commands::iwad::IwadCommand;
 use crate::commands::profile::ProfileCommand;
 use crate::commands::source_port::SourcePortCommand;
 use crate::commands::wad::WadCommand;
+use crate::demo::DemoCategory;
+use crate::settings::get_app_settings_dir_path;
+use crate::source_port::SourcePort;
+use crate::storage::ObjectRepository;
+use crate::wad::{MapDefinitions, MapInfo, MapStats, WadEntry, WadReader, UNKNOWN_VALUE};
+use chrono::NaiveDate;
+use color_eyre::{eyre::eyre, Report, Result};
+use log::info;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
 use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(StructOpt, Debug)]
@@ -23,11 +41,86 @@ pub enum Command {
         /// The megawad to play, e.g. DOOM2
         megawad: Option<String>,
         #[structopt(short = "w", long)]
-        /// The map to play. This should be in the form MAPxx for DOOM2 or ExMx for DOOM.
+        /// The map to play. This is usually in the form MAPxx for DOOM2 or ExMx for DOOM, but it
+        /// can be the lump name of any map in the WAD.
         map: Option<String>,
         #[structopt(short, long)]
         /// Specify the profile to play with. If not supplied, the default profile will be used.
         profile: Option<String>,
+        #[structopt(short, long)]
+        /// Record a demo. It's saved in a directory for the WAD, in the demos directory.
+        record: bool,
+        #[structopt(short, long, requires = "record")]
+        /// The category of the demo, which goes in its name, e.g. UvMax, UvSpeed, NmSpeed, Nm100,
+        /// Tyson, Pacifist, NoMo or Other. The default is NmSpeed for the Nightmare skill and
+        /// UvMax for anything else.
+        category: Option<DemoCategory>,
+        #[structopt(long = "continue", conflicts_with_all = &["megawad", "map"])]
+        /// Play the WAD and map from the last session again, with the same profile, unless
+        /// another profile is specified.
+        continue_last: bool,
+        #[structopt(short, long, conflicts_with_all = &["megawad", "map", "continue-last"])]
+        /// Play the map that follows the one from the last session, with the same profile, unless
+        /// another profile is specified. This is the next map defined by the WAD, if it defines
+        /// one, or otherwise the next map in the WAD.
+        next: bool,
+        #[structopt(short, long, conflicts_with_all = &["megawad", "continue-last", "next"])]
+        /// Only list your favourite WADs and maps in the finder, rather than listing them first.
+        favourites: bool,
+        #[structopt(short, long, conflicts_with = "record")]
+        /// Launch the game in the background, rather than waiting for it to exit. The game's
+        /// output and exit status are still logged; use the `log` command to see them.
+        detach: bool,
+    },
+    #[structopt(
+        name = "browse",
+        no_version,
+        global_settings(&[AppSettings::DisableVersion]),
+    )]
+    /// Browses your WADs and maps full screen, with their text files and progress, and plays them
+    Browse,
+    #[structopt(
+        name = "log",
+        no_version,
+        global_settings(&[AppSettings::DisableVersion]),
+    )]
+    /// Shows the log of the most recent game session, with the game's output and exit status
+    Log {
+        #[structopt(short, long)]
+        /// List all the session logs instead
+        list: bool,
+    },
+    #[structopt(
+        name = "history",
+        no_version,
+        global_settings(&[AppSettings::DisableVersion]),
+    )]
+    /// Shows the games that have been played, what they were played with and for how long
+    History {
+        #[structopt(short, long)]
+        /// Only show the sessions for this megawad, e.g. DOOM2
+        megawad: Option<String>,
+        #[structopt(short, long)]
+        /// Only show the sessions played with this source port, e.g. Dsda or PrBoomPlus
+        source_port: Option<SourcePort>,
+        #[structopt(short, long)]
+        /// Only show the sessions started on or after this date, e.g. 2021-03-01
+        from: Option<NaiveDate>,
+        #[structopt(short, long)]
+        /// Only show the sessions started on or before this date, e.g. 2021-03-31
+        to: Option<NaiveDate>,
+    },
+    #[structopt(name = "session", setting = AppSettings::Hidden)]
+    /// Runs the game and writes its session log. This is used to launch the game in the
+    /// background.
+    Session {
+        #[structopt(long)]
+        log: PathBuf,
+        #[structopt(long)]
+        play_session: String,
+        source_port: PathBuf,
+        #[structopt(last = true)]
+        args: Vec<String>,
     },
     #[structopt(
         name = "profile",
@@ -49,6 +142,21 @@ pub enum Command {
         #[structopt(subcommand)]
         cmd: SourcePortCommand,
     },
+    #[structopt(
+        name = "fav",
+        no_version,
+        global_settings(&[AppSettings::DisableVersion]),
+    )]
+    /// Favourite WADs and maps
+    Fav {
+        #[structopt(subcommand)]
+        cmd: FavouriteCommand,
+    },
+    /// Demo Management
+    Demo {
+        #[structopt(subcommand)]
+        cmd: DemoCommand,
+    },
     /// IWAD Management
     Iwad {
         #[structopt(subcommand)]
@@ -60,3 +168,135 @@ pub enum Command {
         cmd: WadCommand,
     },
 }
+
+//
+// Functions shared by the IWAD and WAD import commands
+//
+
+/// Builds the information for a map, including its statistics. The marker is the lump in the WAD
+/// that the map data follows, which, for a map in a package, isn't the same as the number.
+pub fn get_map<R: Read + Seek>(
+    reader: &mut WadReader<R>,
+    marker: &str,
+    number: String,
+    definitions: &MapDefinitions,
+) -> Result<MapInfo, Report> {
+    let format = reader.get_map_format(marker)?;
+    let stats = MapStats::from_wad(reader, marker)?;
+    let mut map = get_map_info(number, definitions)?;
+    map.format = format;
+    map.stats = stats;
+    Ok(map)
+}
+
+/// Builds the map information using the map definition lumps in the WAD, if it has any.
+///
+/// Not every map needs to be defined, so the name falls back to being unknown.
+fn get_map_info(number: String, definitions: &MapDefinitions) -> Result<MapInfo, Report> {
+    let definition = definitions.get(&number);
+    let name = definition
+        .and_then(|x| x.name.clone())
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| UNKNOWN_VALUE.to_string());
+    let mut map = MapInfo::new(number, name)?;
+    if let Some(definition) = definition {
+        map.par_time = definition.par_time;
+        map.next = definition.next.clone();
+        map.next_secret = definition.next_secret.clone();
+    }
+    Ok(map)
+}
+
+pub fn get_wad_entry_id(path: &Path) -> Result<String, Report> {
+    let temp = path.to_owned();
+    let file_name = temp
+        .file_name()
+        .ok_or_else(|| eyre!("Could not retrieve filename from path"))?;
+    let file_name = PathBuf::from(file_name);
+    let id = file_name
+        .file_stem()
+        .ok_or_else(|| eyre!("Could not parse the ID for the WAD"))?;
+    Ok(String::from(id.to_str().unwrap()))
+}
+
+pub fn get_wad_file_name(path: &Path) -> Result<&str, Report> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| eyre!("Could not retrieve filename from path"))?;
+    let file_name = file_name
+        .to_str()
+        .ok_or_else(|| eyre!("Could not retrieve string"))?;
+    Ok(file_name)
+}
+
+pub fn print_wad_info(path: &Path, wad_entry: &WadEntry) {
+    info!("Importing {}", path.display());
+    info!("ID: {}", wad_entry.id);
+    info!("WAD Name: {}", wad_entry.name);
+    info!("Title: {}", wad_entry.title);
+    info!("Released: {}", wad_entry.release_date);
+    info!("Author: {}", wad_entry.author);
+    // Only IWADs have a version, and for those the release isn't always identified.
+    if wad_entry.game.is_some() {
+        info!(
+            "Version: {}",
+            wad_entry.version.as_deref().unwrap_or(UNKNOWN_VALUE)
+        );
+    }
+    if let Some(iwad) = &wad_entry.iwad {
+        info!("IWAD: {}", iwad);
+    }
+    if let Some(complevel) = &wad_entry.complevel {
+        info!("Complevel: {}", complevel);
+    }
+    for file in &wad_entry.files {
+        info!("Supplementary file: {}", file.name);
+    }
+    for episode in &wad_entry.episodes {
+        info!("Episode: {} (starts on {})", episode.name, episode.map);
+    }
+    for map in &wad_entry.maps {
+        info!("{}: {}", map.number, map.name);
+    }
+}
+
+pub fn wad_entry_exists(id: &str) -> Result<bool, Report> {
+    let mut wads_entry_path = get_app_settings_dir_path()?;
+    wads_entry_path.push("wads");
+    let repository = ObjectRepository::new(&wads_entry_path)?;
+    Ok(repository.exists(id))
+}
+
+pub fn get_wad_entry(id: &str) -> Result<WadEntry, Report> {
+    let mut wads_entry_path = get_app_settings_dir_path()?;
+    wads_entry_path.push("wads");
+    let repository = ObjectRepository::new(&wads_entry_path)?;
+    let wad_entry = repository.get(id)?;
+    Ok(wad_entry)
+}
+
+pub fn save_wad_entry(wad_entry: &WadEntry) -> Result<(), Report> {
+    let mut wads_entry_path = get_app_settings_dir_path()?;
+    wads_entry_path.push("wads");
+    let repository = ObjectRepository::new(&wads_entry_path)?;
+    repository.save(&wad_entry.id, &wad_entry)?;
+    Ok(())
+}
+
+/// Copies the WAD file into the directory where the source port will load it from.
+///
+/// IWADs and PWADs are kept in separate directories, so the destination is supplied by the caller.
+pub fn import_wad_file(
+    wad_path: &Path,
+    wad_entry: &WadEntry,
+    destination_dir_path: &Path,
+) -> Result<(), Report> {
+    let wad_import_path = destination_dir_path.join(&wad_entry.name);
+    info!(
+        "Copying {} to {}",
+        wad_path.display(),
+        wad_import_path.as_path().display()
+    );
+    std::fs::copy(wad_path, wad_import_path)?;
+    Ok(())
+}
@@ -1,38 +1,165 @@
+use crate::command_line::{suggest_compatible_ports, LaunchRequest};
+use crate::commands::demo::get_demo_repository;
+use crate::commands::{get_wad_entry, wad_entry_exists};
+use crate::demo::{
+    get_demo_name, get_demo_prefix, get_next_attempt, DemoCategory, DemoEntry, DEMO_EXTENSION,
+};
 use crate::profile::Profile;
-use crate::settings::get_app_settings_dir_path;
+use crate::progress::LEVELSTAT_FILE_NAME;
+use crate::session::get_play_session_repository;
+use crate::session::PlaySession;
+use crate::session::{check_exit_status, get_session_log_path, run_session};
+use crate::session::{discard_play_session, end_play_session, spawn_detached_session};
 use crate::settings::get_user_settings;
-use crate::settings::AppSettings;
+use crate::settings::{AppSettings, UserSettings};
 use crate::source_port::InstalledSourcePort;
-use crate::source_port::Skill;
+use crate::source_port::{Skill, SourcePort};
 use crate::storage::AppSettingsRepository;
-use crate::storage::ObjectRepository;
-use crate::wad::WadEntry;
-use color_eyre::{eyre::eyre, eyre::WrapErr, Report, Result};
-use log::info;
-use std::path::Path;
+use crate::wad::{LoadOrder, MapInfo, SupplementaryFile, WadEntry};
+use chrono::Utc;
+use color_eyre::{eyre::eyre, eyre::WrapErr, Help, Report, Result};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 
 pub fn run_play_cmd(
     megawad: String,
     map: Option<String>,
     profile: Option<String>,
-    repository: AppSettingsRepository,
+    record: bool,
+    category: Option<DemoCategory>,
+    detach: bool,
+    repository: &AppSettingsRepository,
 ) -> Result<(), Report> {
     let settings = repository.get()?;
     let selected_profile = get_profile(&settings, profile)?;
     let source_port = get_source_port(&settings, selected_profile)?;
-    let args = get_args(selected_profile, &megawad, &map)?;
-    let mut source_port_path = source_port.path.to_owned();
-
-    print_play_info(&source_port_path, &args);
-    source_port_path.pop();
-    let output = duct::cmd(&source_port.path, &args)
-        .dir(source_port_path.to_str().unwrap())
-        .read()?;
-    println!("{}", output);
-    Ok(())
+    let wad_entry = get_wad_entry(&megawad)?;
+    check_map_format(&wad_entry, &map, source_port.name)?;
+    let user_settings = get_user_settings()?;
+    let demo = if record {
+        Some(get_demo_entry(
+            selected_profile,
+            &wad_entry,
+            &map,
+            category,
+            &user_settings,
+        )?)
+    } else {
+        None
+    };
+    let args = get_args(
+        selected_profile,
+        &wad_entry,
+        &map,
+        demo.as_ref(),
+        &user_settings,
+    )?;
+    let log_path = get_session_log_path(&wad_entry.id)?;
+    let levelstat_path = get_levelstat_path(source_port)?;
+    let play_session = PlaySession::new(
+        &log_path,
+        &wad_entry.id,
+        get_map_info(&wad_entry, &map)?.map(|x| x.number),
+        selected_profile,
+        source_port,
+        levelstat_path,
+    );
+    get_play_session_repository()?.save(&play_session.id, &play_session)?;
+    if detach {
+        print_play_info(&source_port.path, &args);
+        if let Err(error) =
+            spawn_detached_session(&source_port.path, &args, &log_path, &play_session.id)
+        {
+            discard_play_session(&play_session.id, &log_path)?;
+            return Err(error);
+        }
+        info!(
+            "The game is running in the background, with its output logged to {}",
+            log_path.display()
+        );
+        return Ok(());
+    }
+    let status = match launch_source_port(source_port, &args, &log_path) {
+        Ok(status) => status,
+        Err(error) => {
+            discard_play_session(&play_session.id, &log_path)?;
+            return Err(error);
+        }
+    };
+    // A demo is worth keeping even if the game crashed at the end of it, or the session couldn't
+    // be ended.
+    if let Some(demo) = demo {
+        save_demo_entry(&demo, &user_settings)?;
+    }
+    end_play_session(&play_session.id, status)?;
+    check_exit_status(source_port.name, status, &log_path)
+}
+
+/// Gets the WAD, map and profile to continue from the last session, for the `--continue` and
+/// `--next` options.
+///
+/// With `next`, the map is the one that follows the map from the last session.
+pub fn get_session_to_continue(next: bool) -> Result<(String, Option<String>, String), Report> {
+    let last = get_last_session()?.ok_or_else(|| {
+        eyre!("No games have been played yet, so there's nothing to continue")
+            .suggestion("Use the `--megawad` option to choose what to play")
+    })?;
+    if !next {
+        info!(
+            "Continuing {}{}",
+            last.wad_id,
+            last.map
+                .as_ref()
+                .map(|x| format!(" on {}", x))
+                .unwrap_or_default()
+        );
+        return Ok((last.wad_id, last.map, last.profile));
+    }
+
+    let map = last.map.as_ref().ok_or_else(|| {
+        eyre!(
+            "The last session of {} was started from the title screen, so there's no map to \
+            move on from",
+            last.wad_id
+        )
+        .suggestion("Use `--continue` to play it again")
+    })?;
+    let wad_entry = get_wad_entry(&last.wad_id)?;
+    let next_map = wad_entry
+        .get_next_map(map)
+        .ok_or_else(|| eyre!("{} is the last map in {}", map, wad_entry.id))?;
+    info!(
+        "Moving on from {} to {} in {}",
+        map, next_map.number, wad_entry.id
+    );
+    Ok((
+        last.wad_id.clone(),
+        Some(next_map.number.clone()),
+        last.profile,
+    ))
 }
 
-fn get_profile(settings: &AppSettings, profile: Option<String>) -> Result<&Profile, Report> {
+/// Gets the most recent play session, if anything has been played.
+pub fn get_last_session() -> Result<Option<PlaySession>, Report> {
+    Ok(get_play_session_repository()?
+        .get_all::<PlaySession>()?
+        .into_iter()
+        .max_by_key(|x| x.started))
+}
+
+/// Runs the source port and waits for it to exit, with its output shown in the terminal as well as
+/// being logged.
+pub fn launch_source_port(
+    source_port: &InstalledSourcePort,
+    args: &[String],
+    log_path: &Path,
+) -> Result<ExitStatus, Report> {
+    print_play_info(&source_port.path, args);
+    run_session(&source_port.path, args, log_path, true)
+}
+
+pub fn get_profile(settings: &AppSettings, profile: Option<String>) -> Result<&Profile, Report> {
     if let Some(p) = profile {
         let selected = settings
             .profiles
@@ -76,52 +203,184 @@ fn get_source_port<'a>(
     Ok(source_port)
 }
 
-fn get_args(profile: &Profile, megawad: &str, map: &Option<String>) -> Result<Vec<String>, Report> {
-    let mut wads_path = get_app_settings_dir_path()?;
-    wads_path.push("wads");
+/// Makes sure the source port can play the map, or all the maps in the WAD if no map was
+/// specified, since the source port would otherwise fail to load it, usually with an unhelpful
+/// error.
+fn check_map_format(
+    wad_entry: &WadEntry,
+    map: &Option<String>,
+    source_port: SourcePort,
+) -> Result<(), Report> {
+    let unsupported = wad_entry
+        .maps
+        .iter()
+        .filter(|x| map.as_ref().is_none_or(|map| x.number == *map))
+        .find(|x| !source_port.supports_map_format(&x.format));
+    if let Some(unsupported) = unsupported {
+        return Err(eyre!(
+            "{} in {} is a {} map, which {} can't play",
+            unsupported.number,
+            wad_entry.id,
+            unsupported.format,
+            source_port
+        )
+        .suggestion(suggest_compatible_ports(|x| {
+            x.supports_map_format(&unsupported.format)
+        })));
+    }
+    Ok(())
+}
 
-    let repository = ObjectRepository::new(&wads_path)?;
-    let wad_entry: WadEntry = repository.get(&String::from(megawad))?;
+fn get_args(
+    profile: &Profile,
+    wad_entry: &WadEntry,
+    map: &Option<String>,
+    demo: Option<&DemoEntry>,
+    user_settings: &UserSettings,
+) -> Result<Vec<String>, Report> {
+    let mut request = get_launch_request(wad_entry, user_settings)?;
+    request.map = get_map_info(wad_entry, map)?;
+    request.record = demo.map(|x| get_demo_path(x, user_settings));
+    request.levelstat = profile.source_port.supports_levelstat();
+    profile
+        .source_port
+        .get_command_line_builder()
+        .build(profile, &request)
+}
 
-    let user_settings = get_user_settings()?;
-    let mut iwad_entry_pb = user_settings.iwads_path;
-    iwad_entry_pb.push(wad_entry.name);
-
-    let mut args: Vec<String> = vec![
-        "-iwad".to_string(),
-        iwad_entry_pb.as_path().to_str().unwrap().to_string(),
-        "-skill".to_string(),
-    ];
-    match profile.skill {
-        Skill::Nightmare => args.push("5".to_string()),
-        Skill::UltraViolence => args.push("4".to_string()),
-        Skill::HurtMePlenty => args.push("3".to_string()),
-        Skill::HeyNotTooRough => args.push("2".to_string()),
-        Skill::TooYoungToDie => args.push("1".to_string()),
+/// Gets the path of the file the source port writes the statistics for the finished maps to, if
+/// it can. The ports write it to their working directory, which is the directory the port is in.
+///
+/// Any file left over from a previous game is removed, so the maps in it aren't counted again.
+fn get_levelstat_path(source_port: &InstalledSourcePort) -> Result<Option<PathBuf>, Report> {
+    if !source_port.name.supports_levelstat() {
+        return Ok(None);
     }
-    if !profile.music {
-        args.push("-nomusic".to_string());
+    let path = source_port.path.with_file_name(LEVELSTAT_FILE_NAME);
+    if path.exists() {
+        std::fs::remove_file(&path)?;
     }
-    if !profile.fullscreen {
-        args.push("-nofullscreen".to_string());
+    Ok(Some(path))
+}
+
+fn get_map_info(wad_entry: &WadEntry, map: &Option<String>) -> Result<Option<MapInfo>, Report> {
+    match map {
+        Some(map) => {
+            let map = wad_entry
+                .maps
+                .iter()
+                .find(|x| x.number == *map)
+                .ok_or_else(|| eyre!("Could not find {} in {}", map, wad_entry.id))?;
+            Ok(Some(map.clone()))
+        }
+        None => Ok(None),
     }
-    if map.is_some() {
-        let map = wad_entry
-            .maps
-            .iter()
-            .find(|x| x.number == *map.as_ref().unwrap())
-            .ok_or_else(|| eyre!("Could not find {} in {}", map.as_ref().unwrap(), megawad))?;
-        args.push("-warp".to_string());
-        if map.warp.contains(' ') {
-            // DOOM format.
-            args.push(String::from(map.warp.chars().next().unwrap()));
-            args.push(String::from(map.warp.chars().nth(2).unwrap()));
-        } else {
-            // DOOM2 format.
-            args.push(map.warp.clone());
+}
+
+/// Names the demo after the WAD, the map, the category and the attempt number.
+///
+/// The category defaults to NM-Speed for Nightmare, since that's the only category played on that
+/// skill that doesn't need to be specified, and UV-Max for anything else.
+fn get_demo_entry(
+    profile: &Profile,
+    wad_entry: &WadEntry,
+    map: &Option<String>,
+    category: Option<DemoCategory>,
+    user_settings: &UserSettings,
+) -> Result<DemoEntry, Report> {
+    let category = category.unwrap_or(match profile.skill {
+        Skill::Nightmare => DemoCategory::NmSpeed,
+        _ => DemoCategory::UvMax,
+    });
+    let map = get_map_info(wad_entry, map)?;
+    let demos_path = user_settings.demos_path.join(&wad_entry.id);
+    std::fs::create_dir_all(&demos_path)?;
+    let prefix = get_demo_prefix(&wad_entry.id, map.as_ref(), category);
+    let attempt = get_next_attempt(&demos_path, &prefix)?;
+    let id = get_demo_name(&wad_entry.id, map.as_ref(), category, attempt);
+    Ok(DemoEntry {
+        file_name: format!("{}.{}", id, DEMO_EXTENSION),
+        id,
+        wad_id: wad_entry.id.clone(),
+        map: map.map(|x| x.number),
+        category,
+        attempt,
+        source_port: profile.source_port,
+        source_port_version: profile.source_port_version.clone(),
+        profile: profile.name.clone(),
+        recorded: Utc::now(),
+    })
+}
+
+pub fn get_demo_path(demo: &DemoEntry, user_settings: &UserSettings) -> PathBuf {
+    user_settings
+        .demos_path
+        .join(&demo.wad_id)
+        .join(&demo.file_name)
+}
+
+/// Saves the entry for a demo once the game has exited. There won't be a file if the game was
+/// quit before the demo started recording, in which case there's nothing to save.
+fn save_demo_entry(demo: &DemoEntry, user_settings: &UserSettings) -> Result<(), Report> {
+    let demo_path = get_demo_path(demo, user_settings);
+    if !demo_path.is_file() {
+        warn!("No demo was recorded at {}", demo_path.display());
+        return Ok(());
+    }
+    get_demo_repository()?.save(&demo.id, demo)?;
+    info!("Recorded {} demo at {}", demo.category, demo_path.display());
+    Ok(())
+}
+
+/// Works out the IWAD and any other files being loaded.
+///
+/// An entry without an IWAD is an IWAD itself. Otherwise the WADs are loaded in the order they
+/// override each other: the resources the PWAD depends on, then the PWAD, then anything that
+/// replaces part of it, like a music pack. DeHackEd patches are applied after all the WADs have
+/// been loaded, so they take precedence over any DEHACKED lumps.
+pub fn get_launch_request(
+    wad_entry: &WadEntry,
+    user_settings: &UserSettings,
+) -> Result<LaunchRequest, Report> {
+    let iwad_id = match &wad_entry.iwad {
+        Some(iwad_id) => iwad_id,
+        None => {
+            return Ok(LaunchRequest {
+                iwad: user_settings.iwads_path.join(&wad_entry.name),
+                ..Default::default()
+            });
         }
+    };
+    if !wad_entry_exists(iwad_id)? {
+        return Err(eyre!(
+            "{} is played with the {} IWAD, which hasn't been imported",
+            wad_entry.id,
+            iwad_id
+        )
+        .suggestion("Use the `iwad import` command to import the IWAD"));
     }
-    Ok(args)
+    let iwad_entry = get_wad_entry(iwad_id)?;
+
+    let files_path = user_settings.wads_path.join(&wad_entry.id);
+    let (patches, others): (Vec<&SupplementaryFile>, Vec<&SupplementaryFile>) =
+        wad_entry.files.iter().partition(|x| x.is_dehacked());
+    let get_paths = |load_order: LoadOrder| {
+        others
+            .iter()
+            .filter(move |x| x.load_order == load_order)
+            .map(|x| files_path.join(&x.name))
+    };
+    let mut files: Vec<PathBuf> = get_paths(LoadOrder::BeforeWad).collect();
+    files.push(user_settings.wads_path.join(&wad_entry.name));
+    files.extend(get_paths(LoadOrder::AfterWad));
+    Ok(LaunchRequest {
+        iwad: user_settings.iwads_path.join(&iwad_entry.name),
+        files,
+        patches: patches.iter().map(|x| files_path.join(&x.name)).collect(),
+        complevel: wad_entry.complevel,
+        game: iwad_entry.game,
+        ..Default::default()
+    })
 }
 
 fn print_play_info(source_port_path: impl AsRef<Path>, args: &[String]) {
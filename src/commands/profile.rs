@@ -1,9 +1,9 @@
-use crate::profile::Profile;
+use crate::profile::{Profile, RenderSettings};
 use crate::source_port::{Skill, SourcePort};
 use crate::storage::AppSettingsRepository;
 use color_eyre::{eyre::eyre, eyre::WrapErr, Help, Report, Result};
 use log::{debug, info};
-use prettytable::{cell, row, Table};
+use prettytable::{row, Table};
 use serde_hjson::{Map, Value};
 use std::io::Write;
 use structopt::StructOpt;
@@ -44,6 +44,18 @@ pub enum ProfileCommand {
         /// the default, so if this flag is used, the current default will be overriden with this
         /// new profile.
         default: bool,
+        #[structopt(long)]
+        /// The frame rate for videos rendered with this profile
+        render_fps: Option<u32>,
+        #[structopt(long)]
+        /// The command the source port pipes the frames to when it renders a video
+        video_command: Option<String>,
+        #[structopt(long)]
+        /// The command the source port pipes the sound to when it renders a video
+        sound_command: Option<String>,
+        #[structopt(long)]
+        /// The command that combines the video and sound into the rendered file
+        mux_command: Option<String>,
     },
 }
 
@@ -80,6 +92,10 @@ pub fn run_profile_cmd(
             music,
             skill,
             default,
+            render_fps,
+            video_command,
+            sound_command,
+            mux_command,
         } => {
             debug!("Running add profile command");
             let mut is_default = default;
@@ -93,7 +109,7 @@ pub fn run_profile_cmd(
                 let source_port = source_port.unwrap();
                 let source_port_version = source_port_version.unwrap();
                 let skill = skill.unwrap();
-                Profile::new(
+                let mut profile = Profile::new(
                     &name,
                     source_port,
                     source_port_version,
@@ -101,7 +117,14 @@ pub fn run_profile_cmd(
                     fullscreen,
                     music,
                     is_default,
-                )?
+                )?;
+                profile.render = RenderSettings {
+                    fps: render_fps,
+                    video_command,
+                    sound_command,
+                    mux_command,
+                };
+                profile
             } else {
                 get_profile_in_interactive_mode()?
             };
@@ -128,7 +151,7 @@ pub fn run_profile_cmd(
                 .suggestion("Use the 'source-port ls' command to find a valid source port"));
             }
             if !settings.profiles.is_empty() && default {
-                let mut current = settings.profiles.iter_mut().find(|x| x.default).unwrap();
+                let current = settings.profiles.iter_mut().find(|x| x.default).unwrap();
                 current.default = false;
                 info!("The current default profile is '{}'", current.name);
                 info!("The newly added profile will now be set as the default");
@@ -174,7 +197,7 @@ fn get_profile_in_interactive_mode() -> Result<Profile, Report> {
         .unwrap()
         .parse::<Skill>()
         .map_err(|e| eyre!("Error parsing skill type: {}", e))?;
-    let profile = Profile::new(
+    let mut profile = Profile::new(
         json_profile.get("name").unwrap().as_str().unwrap(),
         source_port_name,
         String::from(json_profile.get("version").unwrap().as_str().unwrap()),
@@ -183,6 +206,22 @@ fn get_profile_in_interactive_mode() -> Result<Profile, Report> {
         json_profile.get("music").unwrap().as_bool().unwrap(),
         json_profile.get("default").unwrap().as_bool().unwrap(),
     )?;
+    // The render settings are optional, so they're commented out in the template.
+    let get_command = |key: &str| {
+        json_profile
+            .get(key)
+            .and_then(|x| x.as_str())
+            .map(String::from)
+    };
+    profile.render = RenderSettings {
+        fps: json_profile
+            .get("render_fps")
+            .and_then(|x| x.as_u64())
+            .map(|x| x as u32),
+        video_command: get_command("video_command"),
+        sound_command: get_command("sound_command"),
+        mux_command: get_command("mux_command"),
+    };
     Ok(profile)
 }
 
@@ -225,6 +264,10 @@ mod add {
             music: true,
             skill: Some(Skill::UltraViolence),
             default: true,
+            render_fps: None,
+            video_command: None,
+            sound_command: None,
+            mux_command: None,
         };
 
         run_profile_cmd(cmd, repo).unwrap();
@@ -270,6 +313,10 @@ mod add {
             music: true,
             skill: Some(Skill::UltraViolence),
             default: false,
+            render_fps: None,
+            video_command: None,
+            sound_command: None,
+            mux_command: None,
         };
 
         run_profile_cmd(cmd, repo).unwrap();
@@ -307,6 +354,7 @@ mod add {
                 fullscreen: true,
                 music: true,
                 default: true,
+                render: Default::default(),
             }],
             release_cache_path: PathBuf::new(),
         };
@@ -320,6 +368,10 @@ mod add {
             music: false,
             skill: Some(Skill::UltraViolence),
             default: false,
+            render_fps: None,
+            video_command: None,
+            sound_command: None,
+            mux_command: None,
         };
 
         run_profile_cmd(cmd, repo).unwrap();
@@ -356,6 +408,7 @@ mod add {
                 fullscreen: true,
                 music: true,
                 default: true,
+                render: Default::default(),
             }],
             release_cache_path: PathBuf::new(),
         };
@@ -369,6 +422,10 @@ mod add {
             music: false,
             skill: Some(Skill::UltraViolence),
             default: true,
+            render_fps: None,
+            video_command: None,
+            sound_command: None,
+            mux_command: None,
         };
 
         run_profile_cmd(cmd, repo).unwrap();
@@ -412,6 +469,10 @@ mod add {
             music: true,
             skill: Some(Skill::UltraViolence),
             default: true,
+            render_fps: None,
+            video_command: None,
+            sound_command: None,
+            mux_command: None,
         };
 
         let result = run_profile_cmd(cmd, repo);
@@ -0,0 +1,38 @@
+use crate::session::{get_session_logs, read_exit_status, NO_EXIT_STATUS_DESCRIPTION};
+use color_eyre::{Report, Result};
+use log::info;
+use prettytable::{row, Table};
+
+pub fn run_log_cmd(list: bool) -> Result<(), Report> {
+    let logs = get_session_logs()?;
+    if logs.is_empty() {
+        info!("No games have been played yet.");
+        return Ok(());
+    }
+    if list {
+        let mut table = Table::new();
+        table.add_row(row!["Log", "Exit Status"]);
+        for log in &logs {
+            table.add_row(row![
+                log.file_name().unwrap().to_string_lossy(),
+                get_exit_status_description(read_exit_status(log)?)
+            ]);
+        }
+        table.printstd();
+        return Ok(());
+    }
+
+    let log = logs.last().unwrap();
+    info!("Showing the session log at {}", log.display());
+    println!("{}", std::fs::read_to_string(log)?);
+    info!(
+        "Exit status: {}",
+        get_exit_status_description(read_exit_status(log)?)
+    );
+    Ok(())
+}
+
+/// Falls back to the description for a game that hasn't exited when the log has no exit status.
+fn get_exit_status_description(status: Option<String>) -> String {
+    status.unwrap_or_else(|| NO_EXIT_STATUS_DESCRIPTION.to_string())
+}
@@ -6,7 +6,7 @@ use crate::source_port::{
 use crate::storage::{AppSettingsRepository, ObjectRepository};
 use color_eyre::{eyre::eyre, Help, Report, Result};
 use log::{debug, info};
-use prettytable::{cell, row, Table};
+use prettytable::{row, Table};
 use std::path::PathBuf;
 use structopt::StructOpt;
 use strum::IntoEnumIterator;
@@ -93,7 +93,7 @@ fn run_add_subcommand(
 
 fn run_install_subcommand(
     source_port: SourcePort,
-    version: Option<String>,
+    _version: Option<String>,
     app_settings_repository: &AppSettingsRepository,
     release_repository: &impl ReleaseRepository,
 ) -> Result<(), Report> {
@@ -122,32 +122,28 @@ fn run_install_subcommand(
             )
         }
         Err(error) => match error {
-            SourcePortError::InstallDestinationExists(_) => {
-                return Err(eyre!(error)
-                    .wrap_err(format!(
-                        "Failed to install the latest version of {}",
-                        source_port
-                    ))
-                    .suggestion(format!(
-                        "Remove the {} directory and run the command again",
-                        sp_dest_path.clone().display().to_string()
-                    )));
-            }
-            SourcePortError::AssetNotFound(_, _, _) => {
-                return Err(eyre!(error)
-                    .wrap_err(format!(
-                        "Failed to install the latest version of {}",
-                        source_port
-                    ))
-                    .suggestion(
-                        "You can try the command again with the --version argument \
+            SourcePortError::InstallDestinationExists(_) => Err(eyre!(error)
+                .wrap_err(format!(
+                    "Failed to install the latest version of {}",
+                    source_port
+                ))
+                .suggestion(format!(
+                    "Remove the {} directory and run the command again",
+                    sp_dest_path.clone().display()
+                ))),
+            SourcePortError::AssetNotFound(_, _, _) => Err(eyre!(error)
+                .wrap_err(format!(
+                    "Failed to install the latest version of {}",
+                    source_port
+                ))
+                .suggestion(
+                    "You can try the command again with the --version argument \
                         to install a specific version",
-                    )
-                    .suggestion(format!(
-                        "Check the Github repository for {} to see what versions are available",
-                        source_port
-                    )));
-            }
+                )
+                .suggestion(format!(
+                    "Check the Github repository for {} to see what versions are available",
+                    source_port
+                ))),
             _ => Err(eyre!(error)),
         },
     }
@@ -1,28 +1,135 @@
-use crate::wad::WadMetadata;
-use color_eyre::{Report, Result};
+use crate::commands::{
+    get_map, get_wad_entry, get_wad_entry_id, get_wad_file_name, import_wad_file, print_wad_info,
+    save_wad_entry,
+};
+use crate::complevel::CompLevel;
+use crate::demo::format_duration;
+use crate::idgames::{get_wad_from_archive_files, IdgamesClient, IdgamesTextFile};
+use crate::package::{PackageType, Pk3Package};
+use crate::progress::get_wad_progress;
+use crate::settings::get_user_settings;
+use crate::wad::{
+    EpisodeDefinition, LoadOrder, MapDefinitions, MapInfo, SupplementaryFile, WadEntry,
+    WadMetadata, WadReader, Warp, UNKNOWN_VALUE,
+};
+use color_eyre::{eyre::eyre, Help, Report, Result};
 use log::{debug, info};
-use prettytable::{cell, row, Table};
-use std::path::PathBuf;
+use prettytable::{row, Table};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
+/// The IWAD and the other files a PWAD needs, as they were given on the command line.
+struct Dependencies {
+    iwad: Option<String>,
+    files: Vec<PathBuf>,
+    resources: Vec<PathBuf>,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum WadCommand {
+    #[structopt(name = "import")]
+    /// Import a PWAD or megawad into your collection
+    Import {
+        #[structopt(short, long, required_unless = "id", conflicts_with = "id")]
+        /// Specify the path to the WAD or PK3 file. If there's an idgames text file alongside it,
+        /// with the same name, it will be used to get the title, author and release date.
+        path: Option<PathBuf>,
+        #[structopt(short, long)]
+        /// Specify the ID of a file in the Doomworld idgames archive. The WAD and its text file
+        /// will be downloaded and imported.
+        id: Option<u32>,
+        #[structopt(long)]
+        /// Specify the ID of the IWAD the WAD is played with, e.g. DOOM. If not supplied, it will
+        /// be DOOM for a WAD with ExMx maps, or DOOM2 otherwise.
+        iwad: Option<String>,
+        #[structopt(long = "file")]
+        /// Specify a file to load after the WAD, like a DeHackEd patch or a music pack. This can
+        /// be used more than once, and the files will be loaded in the order they're given.
+        files: Vec<PathBuf>,
+        #[structopt(long = "resource")]
+        /// Specify a file to load before the WAD, like a texture pack the WAD depends on. This can
+        /// be used more than once, and the files will be loaded in the order they're given.
+        resources: Vec<PathBuf>,
+    },
+    #[structopt(name = "extract")]
+    /// Extract the data for a lump in a WAD to a file
+    Extract {
+        #[structopt(short, long)]
+        /// Specify a path to any WAD file.
+        path: PathBuf,
+        #[structopt(short, long)]
+        /// Specify the name of the lump. If there's more than one lump with the name, the last one
+        /// is used, which is the one the game would use.
+        lump: String,
+        #[structopt(short, long)]
+        /// Specify a map, e.g. MAP01, to extract one of its lumps, like THINGS or LINEDEFS.
+        map: Option<String>,
+        #[structopt(short, long)]
+        /// Specify the path of the file the lump data will be written to.
+        output: PathBuf,
+    },
+    #[structopt(name = "info")]
+    /// Show the information and map statistics for a WAD
+    Info {
+        #[structopt(short, long, required_unless = "path", conflicts_with = "path")]
+        /// Specify the ID of an imported WAD or IWAD, e.g. DOOM2.
+        name: Option<String>,
+        #[structopt(short, long)]
+        /// Specify a path to any WAD or PK3 file. The information will be read from the file
+        /// rather than your collection.
+        path: Option<PathBuf>,
+    },
+    #[structopt(name = "progress")]
+    /// Show the maps in a WAD that have been finished, with the best times. This is only recorded
+    /// when the game is played with a source port that can write the statistics for each map,
+    /// like DSDA-Doom or PrBoom+.
+    Progress {
+        #[structopt(short, long)]
+        /// Specify the ID of an imported WAD or IWAD, e.g. DOOM2.
+        name: String,
+    },
     #[structopt(name = "lsdir")]
-    /// Lists the directories of a given WAD
+    /// Lists the directories of a given WAD, or the files in a PK3 package
     LsDir {
         #[structopt(short, long)]
         /// Specify the name of an imported WAD.
         name: Option<String>,
         #[structopt(short, long)]
-        /// Specify a path to any WAD file.
+        /// Specify a path to any WAD or PK3 file.
         path: Option<PathBuf>,
     },
 }
 
-pub fn run_wad_cmd(cmd: WadCommand) -> Result<(), Report> {
+pub fn run_wad_cmd(cmd: WadCommand, idgames_client: &IdgamesClient) -> Result<(), Report> {
     match cmd {
+        WadCommand::Import {
+            path,
+            id,
+            iwad,
+            files,
+            resources,
+        } => {
+            let dependencies = Dependencies {
+                iwad,
+                files,
+                resources,
+            };
+            run_import_subcommand(path, id, &dependencies, idgames_client)?
+        }
+        WadCommand::Extract {
+            path,
+            lump,
+            map,
+            output,
+        } => run_extract_subcommand(&path, &lump, map.as_deref(), &output)?,
+        WadCommand::Info { name, path } => run_info_subcommand(name, path)?,
+        WadCommand::Progress { name } => run_progress_subcommand(&name)?,
         WadCommand::LsDir { name: _, path } => {
             if let Some(path) = path {
+                if PackageType::from_path(&path)? == PackageType::Pk3 {
+                    return list_package_files(&path);
+                }
                 let wad = WadMetadata::from_path(path)?;
                 debug!(
                     "WAD header information: type: {}, directory entries: {}, directory offset: {} ",
@@ -40,3 +147,428 @@ pub fn run_wad_cmd(cmd: WadCommand) -> Result<(), Report> {
     }
     Ok(())
 }
+
+fn run_import_subcommand(
+    path: Option<PathBuf>,
+    id: Option<u32>,
+    dependencies: &Dependencies,
+    idgames_client: &IdgamesClient,
+) -> Result<(), Report> {
+    if let Some(path) = path {
+        let text_file_path = get_text_file_path(&path);
+        let text_file = if let Some(text_file_path) = &text_file_path {
+            debug!("Using text file at {}", text_file_path.display());
+            IdgamesTextFile::from_path(text_file_path)?
+        } else {
+            debug!("No text file found for {}", path.display());
+            IdgamesTextFile::default()
+        };
+        let id = get_wad_entry_id(&path)?;
+        import_pwad(
+            &path,
+            text_file.title.unwrap_or(id),
+            text_file
+                .release_date
+                .unwrap_or_else(|| UNKNOWN_VALUE.to_string()),
+            text_file
+                .author
+                .unwrap_or_else(|| UNKNOWN_VALUE.to_string()),
+            text_file_path,
+            dependencies,
+        )
+    } else {
+        let id = id.ok_or_else(|| eyre!("Either a path or an idgames ID must be supplied"))?;
+        let file = idgames_client.get_file(id)?;
+        let download_dir = tempfile::tempdir()?;
+        let paths = idgames_client.download_file(&file, download_dir.path())?;
+        let wad_path = get_wad_from_archive_files(&file, &paths)?;
+        // The archive will usually also have the text file, but it doesn't always have the same
+        // name as the WAD, so the copy from the API response is used instead.
+        let text_file_path = wad_path.with_extension("idgames.txt");
+        std::fs::write(&text_file_path, &file.textfile)?;
+        import_pwad(
+            &wad_path,
+            file.title,
+            file.date,
+            file.author,
+            Some(text_file_path),
+            dependencies,
+        )
+    }
+}
+
+fn run_extract_subcommand(
+    path: &Path,
+    lump_name: &str,
+    map: Option<&str>,
+    output_path: &Path,
+) -> Result<(), Report> {
+    let mut reader = WadReader::open(path)?;
+    let index = if let Some(map) = map {
+        reader
+            .metadata
+            .get_map_lump_index(map, lump_name)
+            .ok_or_else(|| {
+                eyre!(
+                    "{} does not have a {} lump for {}",
+                    path.display(),
+                    lump_name,
+                    map
+                )
+            })?
+    } else {
+        reader
+            .metadata
+            .get_lump_index(lump_name)
+            .ok_or_else(|| eyre!("{} does not have a {} lump", path.display(), lump_name))
+            .suggestion("Use the `wad lsdir` command to see the lumps in the WAD")?
+    };
+    // The lump is streamed to the file, since some lumps, like music, can be quite large.
+    let mut output = std::fs::File::create(output_path)?;
+    let written = std::io::copy(&mut reader.get_lump_reader(index)?, &mut output)?;
+    info!(
+        "Extracted {} bytes from {} to {}",
+        written,
+        lump_name,
+        output_path.display()
+    );
+    Ok(())
+}
+
+fn run_info_subcommand(name: Option<String>, path: Option<PathBuf>) -> Result<(), Report> {
+    let entry = if let Some(path) = path {
+        let (maps, episodes) = read_maps(&path, None)?;
+        let mut entry = WadEntry::new(
+            get_wad_entry_id(&path)?,
+            get_wad_file_name(&path)?.to_string(),
+            get_wad_entry_id(&path)?,
+            UNKNOWN_VALUE.to_string(),
+            UNKNOWN_VALUE.to_string(),
+            maps,
+        )?;
+        entry.episodes = episodes;
+        entry
+    } else {
+        let name = name.ok_or_else(|| eyre!("Either a name or a path must be supplied"))?;
+        get_wad_entry(&name)?
+    };
+    info!("ID: {}", entry.id);
+    info!("WAD Name: {}", entry.name);
+    info!("Title: {}", entry.title);
+    info!("Released: {}", entry.release_date);
+    info!("Author: {}", entry.author);
+    if let Some(iwad) = &entry.iwad {
+        info!("IWAD: {}", iwad);
+    }
+    if let Some(complevel) = &entry.complevel {
+        info!("Complevel: {}", complevel);
+    }
+    for episode in &entry.episodes {
+        info!("Episode: {} (starts on {})", episode.name, episode.map);
+    }
+    let mut table = Table::new();
+    table.add_row(row![
+        "Map",
+        "Name",
+        "Format",
+        "Monsters (HNTR/HMP/UV)",
+        "Items",
+        "Secrets",
+        "Lines",
+        "Sectors",
+        "Par"
+    ]);
+    for map in entry.maps {
+        let par_time = map
+            .par_time
+            .map_or_else(|| "-".to_string(), |x| format!("{}:{:02}", x / 60, x % 60));
+        // Stats aren't available for incomplete maps, or for entries that were imported before the
+        // stats were added.
+        if let Some(stats) = map.stats {
+            table.add_row(row![
+                map.number,
+                map.name,
+                map.format,
+                format!(
+                    "{}/{}/{}",
+                    stats.easy_monsters, stats.medium_monsters, stats.hard_monsters
+                ),
+                stats.items,
+                stats.secrets,
+                stats.linedefs,
+                stats.sectors,
+                par_time
+            ]);
+        } else {
+            table.add_row(row![
+                map.number, map.name, map.format, "-", "-", "-", "-", "-", par_time
+            ]);
+        }
+    }
+    table.printstd();
+    Ok(())
+}
+
+fn run_progress_subcommand(name: &str) -> Result<(), Report> {
+    let entry = get_wad_entry(name)?;
+    let progress = get_wad_progress(&entry.id)?;
+    let mut table = Table::new();
+    table.add_row(row![
+        "Map",
+        "Name",
+        "Skill",
+        "Best Time",
+        "100% Kills",
+        "100% Secrets",
+        "Completions"
+    ]);
+    let yes_no = |x: bool| if x { "Yes" } else { "No" };
+    for map in &entry.maps {
+        let results = progress.get_map_progress(&map.number);
+        if results.is_empty() {
+            table.add_row(row![
+                map.number,
+                map.name,
+                "Not finished",
+                "-",
+                "-",
+                "-",
+                "-"
+            ]);
+        }
+        for result in results {
+            table.add_row(row![
+                map.number,
+                map.name,
+                result.skill,
+                format_duration(result.best_time),
+                yes_no(result.all_kills),
+                yes_no(result.all_secrets),
+                result.completions
+            ]);
+        }
+    }
+    table.printstd();
+    let finished = entry
+        .maps
+        .iter()
+        .filter(|x| progress.is_finished(&x.number))
+        .count();
+    info!(
+        "Finished {} of the {} maps in {}",
+        finished,
+        entry.maps.len(),
+        entry.id
+    );
+    Ok(())
+}
+
+fn import_pwad(
+    path: &Path,
+    title: String,
+    release_date: String,
+    author: String,
+    text_file_path: Option<PathBuf>,
+    dependencies: &Dependencies,
+) -> Result<(), Report> {
+    if PackageType::from_path(path)? == PackageType::Wad
+        && WadMetadata::from_path(path)?.header.wad_type == "IWAD"
+    {
+        return Err(eyre!(format!("{} is an IWAD", path.display()))
+            .suggestion("Use the `iwad import` command to import an IWAD"));
+    }
+    let id = get_wad_entry_id(path)?;
+    let file_name = get_wad_file_name(path)?;
+    let (maps, episodes) = read_maps(path, dependencies.iwad.as_deref())?;
+    let mut entry = WadEntry::new(id, file_name.to_string(), title, release_date, author, maps)?;
+    entry.episodes = episodes;
+    entry.iwad = Some(
+        dependencies
+            .iwad
+            .clone()
+            .unwrap_or_else(|| get_default_iwad(&entry.maps)),
+    );
+    entry.files = get_supplementary_files(dependencies)?;
+    entry.complevel = get_complevel(path, text_file_path.as_deref(), &entry.files, dependencies)?;
+    print_wad_info(path, &entry);
+    save_wad_entry(&entry)?;
+    let settings = get_user_settings()?;
+    import_wad_file(path, &entry, &settings.wads_path)?;
+    import_supplementary_files(&entry, dependencies, &settings.wads_path)?;
+    if let Some(text_file_path) = text_file_path {
+        let text_file_import_path = settings.wads_path.join(format!("{}.txt", entry.id));
+        std::fs::copy(text_file_path, text_file_import_path)?;
+    }
+    Ok(())
+}
+
+/// Works out which IWAD a PWAD is for from its maps. DOOM2 is the most common, so it's used for
+/// anything that doesn't only have DOOM's ExMx maps, including WADs that don't have any maps.
+fn get_default_iwad(maps: &[MapInfo]) -> String {
+    if !maps.is_empty() && maps.iter().all(|x| matches!(x.warp, Warp::Episode(_, _))) {
+        "DOOM".to_string()
+    } else {
+        "DOOM2".to_string()
+    }
+}
+
+/// Works out the compatibility level for a PWAD.
+///
+/// A COMPLVL lump is the most reliable, since it's there for the source port to read, followed by
+/// the text file, where the author says what the WAD was made for. Otherwise it comes from the
+/// features used in the WAD and in any patches that are loaded with it. PK3 packages are for the
+/// ZDoom family, which doesn't use complevels.
+fn get_complevel(
+    path: &Path,
+    text_file_path: Option<&Path>,
+    files: &[SupplementaryFile],
+    dependencies: &Dependencies,
+) -> Result<Option<CompLevel>, Report> {
+    if PackageType::from_path(path)? == PackageType::Pk3 {
+        return Ok(None);
+    }
+    let mut reader = WadReader::open(path)?;
+    if let Some(complevel) = CompLevel::from_complvl_lump(&mut reader)? {
+        debug!("Using complevel {} from the COMPLVL lump", complevel);
+        return Ok(Some(complevel));
+    }
+    if let Some(text_file_path) = text_file_path {
+        if let Some(complevel) = IdgamesTextFile::from_path(text_file_path)?.complevel {
+            debug!("Using complevel {} from the text file", complevel);
+            return Ok(Some(complevel));
+        }
+    }
+    let mut complevel = CompLevel::from_wad(&mut reader)?;
+    // The files are in the same order as the paths they came from.
+    let paths = dependencies
+        .resources
+        .iter()
+        .chain(dependencies.files.iter());
+    for (path, _) in paths.zip(files).filter(|(_, file)| file.is_dehacked()) {
+        let contents = String::from_utf8_lossy(&std::fs::read(path)?).to_string();
+        // A WAD with Hexen or UDMF maps has no level, and a patch doesn't give it one.
+        complevel = complevel.map(|x| x.max(CompLevel::from_dehacked(&contents)));
+    }
+    Ok(complevel)
+}
+
+fn get_supplementary_files(dependencies: &Dependencies) -> Result<Vec<SupplementaryFile>, Report> {
+    let mut files = Vec::new();
+    for (paths, load_order) in [
+        (&dependencies.resources, LoadOrder::BeforeWad),
+        (&dependencies.files, LoadOrder::AfterWad),
+    ] {
+        for path in paths {
+            files.push(SupplementaryFile {
+                name: get_wad_file_name(path)?.to_string(),
+                load_order,
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Copies the supplementary files into a directory named after the entry.
+fn import_supplementary_files(
+    entry: &WadEntry,
+    dependencies: &Dependencies,
+    wads_path: &Path,
+) -> Result<(), Report> {
+    if entry.files.is_empty() {
+        return Ok(());
+    }
+    let files_path = wads_path.join(&entry.id);
+    std::fs::create_dir_all(&files_path)?;
+    for path in dependencies
+        .resources
+        .iter()
+        .chain(dependencies.files.iter())
+    {
+        let import_path = files_path.join(get_wad_file_name(path)?);
+        info!("Copying {} to {}", path.display(), import_path.display());
+        std::fs::copy(path, import_path)?;
+    }
+    Ok(())
+}
+
+/// Reads the maps and episodes from a WAD or a PK3 package.
+///
+/// A package can have map information in its root, as well as in any of its WADs, so it's all
+/// merged together before any of the maps are built. The definitions in the root take precedence.
+///
+/// The IWAD is needed to pick the right map names from a DEHACKED lump. When it isn't known, the
+/// Doom and Doom II names are used.
+fn read_maps(
+    path: &Path,
+    iwad: Option<&str>,
+) -> Result<(Vec<MapInfo>, Vec<EpisodeDefinition>), Report> {
+    match PackageType::from_path(path)? {
+        PackageType::Wad => {
+            let mut reader = WadReader::open(path)?;
+            let definitions = MapDefinitions::from_wad(&mut reader, iwad)?;
+            let maps = get_maps(&mut reader, &definitions)?;
+            Ok((maps, definitions.episodes))
+        }
+        PackageType::Pk3 => {
+            let mut package = Pk3Package::open(path)?;
+            let mut definitions = package.get_map_definitions(iwad)?;
+            let mut wads = package.get_wads()?;
+            for wad in wads.iter_mut() {
+                definitions.merge(MapDefinitions::from_wad(&mut wad.reader, iwad)?);
+            }
+            let mut maps = Vec::new();
+            for wad in wads.iter_mut() {
+                if let Some(map_name) = &wad.map_name {
+                    debug!("Reading {} from {}", map_name, wad.path);
+                    // The marker inside the WAD is ignored, since the map is named after the file.
+                    let marker = wad.reader.metadata.get_map_lump_names().into_iter().next();
+                    if let Some(marker) = marker {
+                        maps.push(get_map(
+                            &mut wad.reader,
+                            &marker,
+                            map_name.clone(),
+                            &definitions,
+                        )?);
+                    }
+                } else {
+                    debug!("Reading the maps in {}", wad.path);
+                    maps.extend(get_maps(&mut wad.reader, &definitions)?);
+                }
+            }
+            Ok((maps, definitions.episodes))
+        }
+    }
+}
+
+/// Builds the information for each map in the WAD.
+fn get_maps<R: Read + Seek>(
+    reader: &mut WadReader<R>,
+    definitions: &MapDefinitions,
+) -> Result<Vec<MapInfo>, Report> {
+    let mut maps = Vec::new();
+    for number in reader.metadata.get_map_lump_names() {
+        maps.push(get_map(reader, &number, number.clone(), definitions)?);
+    }
+    Ok(maps)
+}
+
+fn list_package_files(path: &Path) -> Result<(), Report> {
+    let mut package = Pk3Package::open(path)?;
+    let files = package.list_files()?;
+    info!("Package has {} files", files.len());
+    let mut table = Table::new();
+    table.add_row(row!["File Name", "Size", "Compressed Size"]);
+    for file in files {
+        table.add_row(row![file.path, file.size, file.compressed_size]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// Gets the path of the idgames text file that sits alongside the WAD, if there is one.
+fn get_text_file_path(wad_path: &Path) -> Option<PathBuf> {
+    ["txt", "TXT"]
+        .iter()
+        .map(|extension| wad_path.with_extension(extension))
+        .find(|text_file_path| text_file_path.is_file())
+}
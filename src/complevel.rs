@@ -0,0 +1,467 @@
+use crate::iwad::Game;
+use crate::wad::{
+    MapData, MapFormat, WadReader, THING_FLAG_FRIENDLY, THING_FLAG_NOT_COOP,
+    THING_FLAG_NOT_DEATHMATCH,
+};
+use color_eyre::{Report, Result};
+use lazy_static::lazy_static;
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek};
+
+lazy_static! {
+    static ref BOSS_ACTION_REGEX: Regex =
+        Regex::new(r"(?i)bossaction\s*=\s*[a-z0-9_]+\s*,\s*([0-9]+)").unwrap();
+    /// The codepointers in the `[CODEPTR]` section of a BEX patch, e.g. `FRAME 1 = Spawn`. The
+    /// `A_` prefix is optional.
+    static ref CODEPOINTER_REGEX: Regex =
+        Regex::new(r"(?im)^\s*frame\s+[0-9]+\s*=\s*(?:a_)?([a-z0-9_]+)").unwrap();
+}
+
+/// The codepointers MBF added to DeHackEd, without their `A_` prefix.
+const MBF_CODEPOINTERS: [&str; 12] = [
+    "spawn",
+    "turn",
+    "face",
+    "scratch",
+    "playsound",
+    "randomjump",
+    "lineeffect",
+    "die",
+    "detonate",
+    "mushroom",
+    "betaskullattack",
+    "fireoldbfg",
+];
+
+/// The compatibility levels that PWADs are made for.
+///
+/// The Boom family of source ports have a level for each of the executables they can emulate, but
+/// most of those only matter for demos, so PWADs target one of these. They're in order, so a
+/// feature from a later level means the WAD needs at least that level.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum CompLevel {
+    Vanilla,
+    Boom,
+    Mbf,
+    Mbf21,
+}
+
+impl CompLevel {
+    /// Gets the level from the names used in the COMPLVL lump and text files.
+    pub fn from_name(name: &str) -> Option<CompLevel> {
+        match name.trim().to_lowercase().as_str() {
+            "vanilla" | "limit-removing" => Some(CompLevel::Vanilla),
+            "boom" => Some(CompLevel::Boom),
+            "mbf" => Some(CompLevel::Mbf),
+            "mbf21" => Some(CompLevel::Mbf21),
+            _ => None,
+        }
+    }
+
+    /// Gets the level from the engine an idgames text file says the WAD needs. This is free text,
+    /// so it's just looking for the names of the formats, and anything that mentions a port from
+    /// another family, like GZDoom, doesn't have a level.
+    pub fn from_engine(engine: &str) -> Option<CompLevel> {
+        let engine = engine.to_lowercase();
+        if engine.contains("mbf21") {
+            Some(CompLevel::Mbf21)
+        } else if engine.contains("mbf") {
+            Some(CompLevel::Mbf)
+        } else if engine.contains("boom") {
+            Some(CompLevel::Boom)
+        } else if ["none", "vanilla", "limit"]
+            .iter()
+            .any(|x| engine.contains(x))
+        {
+            Some(CompLevel::Vanilla)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the level from a `-complevel` number. The levels in between are for the other
+    /// executables, like DOSDoom, the early versions of Boom and PrBoom, which are treated as the
+    /// level they're based on.
+    pub fn from_number(number: u32) -> Option<CompLevel> {
+        match number {
+            0..=6 => Some(CompLevel::Vanilla),
+            7..=10 => Some(CompLevel::Boom),
+            11..=17 => Some(CompLevel::Mbf),
+            21 => Some(CompLevel::Mbf21),
+            _ => None,
+        }
+    }
+
+    /// Gets the number used with `-complevel`. There were different executables for each of the
+    /// vanilla IWADs, so the vanilla level depends on the game.
+    pub fn get_number(&self, game: Option<Game>) -> u32 {
+        match self {
+            CompLevel::Vanilla => match game {
+                Some(Game::UltimateDoom) => 3,
+                Some(Game::Plutonia) | Some(Game::Tnt) => 4,
+                _ => 2,
+            },
+            CompLevel::Boom => 9,
+            CompLevel::Mbf => 11,
+            CompLevel::Mbf21 => 21,
+        }
+    }
+
+    /// Reads the level from a COMPLVL lump, which is how a WAD can state the level it's made for.
+    pub fn from_complvl_lump<R: Read + Seek>(
+        reader: &mut WadReader<R>,
+    ) -> Result<Option<CompLevel>, Report> {
+        match reader.metadata.get_lump_index("COMPLVL") {
+            Some(index) => {
+                let lump = reader.read_lump(index)?;
+                Ok(CompLevel::from_name(&String::from_utf8_lossy(&lump)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Works out the level a WAD needs from the features it uses.
+    ///
+    /// This is the highest level of any of the features in the maps, the DeHackEd patches, or the
+    /// boss actions in UMAPINFO. A WAD without any maps can still need a level for its DeHackEd
+    /// patch. There's no level if any of the maps are in the Hexen or UDMF formats, since those are
+    /// for source ports outside the Boom family.
+    ///
+    /// A map that can't be decoded is skipped, the same as it is for the statistics, rather than
+    /// failing the import.
+    pub fn from_wad<R: Read + Seek>(
+        reader: &mut WadReader<R>,
+    ) -> Result<Option<CompLevel>, Report> {
+        let mut level = CompLevel::Vanilla;
+        for map_lump_name in &reader.metadata.get_map_lump_names() {
+            if reader.get_map_format(map_lump_name)? != MapFormat::Doom {
+                return Ok(None);
+            }
+            // The map data can't be decoded for incomplete maps.
+            if !reader.is_map_complete(map_lump_name)? {
+                continue;
+            }
+            match MapData::from_wad(reader, map_lump_name) {
+                Ok(map) => level = level.max(CompLevel::from_map(&map)),
+                Err(report) => debug!("Skipping {} for the complevel: {}", map_lump_name, report),
+            }
+        }
+        let lump_indexes = reader
+            .metadata
+            .directory
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.lump_name == "DEHACKED" || x.lump_name == "UMAPINFO")
+            .map(|(index, x)| (index, x.lump_name.clone()))
+            .collect::<Vec<(usize, String)>>();
+        for (index, lump_name) in lump_indexes {
+            let contents = String::from_utf8_lossy(&reader.read_lump(index)?).to_string();
+            let lump_level = if lump_name == "DEHACKED" {
+                CompLevel::from_dehacked(&contents)
+            } else {
+                CompLevel::from_umapinfo(&contents)
+            };
+            level = level.max(lump_level);
+        }
+        Ok(Some(level))
+    }
+
+    /// Gets the level needed for the specials and things in a map.
+    pub fn from_map(map: &MapData) -> CompLevel {
+        let linedef_level = map
+            .linedefs
+            .iter()
+            .map(|x| CompLevel::from_linedef_special(x.special))
+            .max()
+            .unwrap_or(CompLevel::Vanilla);
+        let thing_level = map
+            .things
+            .iter()
+            .map(|x| {
+                let flags = x.get_doom_flags();
+                if x.thing_type == 888 || flags & THING_FLAG_FRIENDLY != 0 {
+                    // The helper dog and friendly monsters.
+                    CompLevel::Mbf
+                } else if x.thing_type == 5001
+                    || x.thing_type == 5002
+                    || flags & (THING_FLAG_NOT_DEATHMATCH | THING_FLAG_NOT_COOP) != 0
+                {
+                    // Point pushers and pullers, and the flags that remove things from
+                    // multiplayer modes.
+                    CompLevel::Boom
+                } else {
+                    CompLevel::Vanilla
+                }
+            })
+            .max()
+            .unwrap_or(CompLevel::Vanilla);
+        // Boom uses the bits above the vanilla sector types for generalized damage and secrets.
+        let sector_level = if map.sectors.iter().any(|x| x.special > 0x1F) {
+            CompLevel::Boom
+        } else {
+            CompLevel::Vanilla
+        };
+        linedef_level.max(thing_level).max(sector_level)
+    }
+
+    /// Gets the level that introduced a linedef special. Boom added its own specials after the
+    /// vanilla ones, and generalized specials from 0x2F80, which use bits to combine the options.
+    /// It also filled two of the gaps in the vanilla range, with 78 and 85.
+    pub fn from_linedef_special(special: u16) -> CompLevel {
+        match special {
+            78 | 85 => CompLevel::Boom,
+            0..=141 => CompLevel::Vanilla,
+            142..=269 => CompLevel::Boom,
+            271 | 272 => CompLevel::Mbf,
+            1024..=1026 => CompLevel::Mbf21,
+            0x2F80..=u16::MAX => CompLevel::Boom,
+            _ => CompLevel::Vanilla,
+        }
+    }
+
+    /// Gets the level needed for a DeHackEd patch, from its version and the BEX sections and
+    /// codepointers it uses.
+    pub fn from_dehacked(contents: &str) -> CompLevel {
+        let lowercase = contents.to_lowercase();
+        if lowercase.contains("doom version = 2021") || lowercase.contains("mbf21 bits") {
+            CompLevel::Mbf21
+        } else if CODEPOINTER_REGEX
+            .captures_iter(&lowercase)
+            .any(|x| MBF_CODEPOINTERS.contains(&&x[1]))
+        {
+            CompLevel::Mbf
+        } else if ["[codeptr]", "[strings]", "[pars]"]
+            .iter()
+            .any(|x| lowercase.contains(x))
+        {
+            CompLevel::Boom
+        } else {
+            CompLevel::Vanilla
+        }
+    }
+
+    /// UMAPINFO itself works at any level, but its boss actions can trigger any linedef special.
+    pub fn from_umapinfo(contents: &str) -> CompLevel {
+        BOSS_ACTION_REGEX
+            .captures_iter(contents)
+            .filter_map(|x| x[1].parse::<u16>().ok())
+            .map(CompLevel::from_linedef_special)
+            .max()
+            .unwrap_or(CompLevel::Vanilla)
+    }
+}
+
+impl std::fmt::Display for CompLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vanilla => write!(f, "Vanilla"),
+            Self::Boom => write!(f, "Boom"),
+            Self::Mbf => write!(f, "MBF"),
+            Self::Mbf21 => write!(f, "MBF21"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod from_wad {
+    use super::CompLevel;
+    use crate::wad::{MapData, WadReader};
+    use test_helpers::wad::{build_sectors, build_square_room, build_things, write_wad};
+
+    fn write_map_wad(path: &std::path::Path, extra_lumps: &[(&str, &[u8])], specials: &[u16]) {
+        let things = build_things(&[(1, 7), (5001, 7)]);
+        let (linedefs, sidedefs, vertexes) = build_square_room(256);
+        let sectors = build_sectors(specials);
+        let mut lumps = extra_lumps.to_vec();
+        lumps.extend([
+            ("MAP01", b"".as_slice()),
+            ("THINGS", &things),
+            ("LINEDEFS", &linedefs),
+            ("SIDEDEFS", &sidedefs),
+            ("VERTEXES", &vertexes),
+            ("SECTORS", &sectors),
+        ]);
+        write_wad(path, "PWAD", &lumps);
+    }
+
+    #[test]
+    fn should_use_the_highest_level_of_the_features() {
+        let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+        write_map_wad(
+            wad.path(),
+            &[(
+                "DEHACKED",
+                b"Patch File for DeHackEd v3.0\n[CODEPTR]\nFRAME 1 = Spawn\n",
+            )],
+            &[0],
+        );
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        // The point pusher is from Boom, but the A_Spawn codepointer is from MBF.
+        assert_eq!(
+            CompLevel::from_wad(&mut reader).unwrap(),
+            Some(CompLevel::Mbf)
+        );
+    }
+
+    #[test]
+    fn should_use_the_boss_actions_in_umapinfo() {
+        let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+        write_map_wad(
+            wad.path(),
+            &[(
+                "UMAPINFO",
+                b"MAP MAP01\n{\n  bossaction = Fatso, 1024, 666\n}\n",
+            )],
+            &[0],
+        );
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(
+            CompLevel::from_wad(&mut reader).unwrap(),
+            Some(CompLevel::Mbf21)
+        );
+    }
+
+    #[test]
+    fn should_use_the_dehacked_lump_in_a_wad_without_maps() {
+        let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+        write_wad(wad.path(), "PWAD", &[("D_RUNNIN", b"music")]);
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(
+            CompLevel::from_wad(&mut reader).unwrap(),
+            Some(CompLevel::Vanilla)
+        );
+
+        write_wad(
+            wad.path(),
+            "PWAD",
+            &[(
+                "DEHACKED",
+                b"Patch File for DeHackEd v3.0\n[CODEPTR]\nFRAME 1 = Spawn\n",
+            )],
+        );
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(
+            CompLevel::from_wad(&mut reader).unwrap(),
+            Some(CompLevel::Mbf)
+        );
+    }
+
+    #[test]
+    fn should_not_have_a_level_without_doom_format_maps() {
+        let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+        write_wad(
+            wad.path(),
+            "PWAD",
+            &[("MAP01", b""), ("TEXTMAP", b""), ("ENDMAP", b"")],
+        );
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(CompLevel::from_wad(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn should_ignore_the_thing_flags_when_the_reserved_flag_is_set() {
+        let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+        // An old editor that sets all the unused bits, including the friendly flag.
+        let things = build_things(&[(1, 0xFFEF)]);
+        let (linedefs, sidedefs, vertexes) = build_square_room(256);
+        let sectors = build_sectors(&[0]);
+        write_wad(
+            wad.path(),
+            "PWAD",
+            &[
+                ("MAP01", b""),
+                ("THINGS", &things),
+                ("LINEDEFS", &linedefs),
+                ("SIDEDEFS", &sidedefs),
+                ("VERTEXES", &vertexes),
+                ("SECTORS", &sectors),
+            ],
+        );
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(
+            CompLevel::from_wad(&mut reader).unwrap(),
+            Some(CompLevel::Vanilla)
+        );
+        // The skill levels and the ambush flag are vanilla, so they're kept.
+        let map = MapData::from_wad(&mut reader, "MAP01").unwrap();
+        assert_eq!(map.things[0].get_doom_flags(), 0x000F);
+    }
+}
+
+#[cfg(test)]
+mod from_complvl_lump {
+    use super::CompLevel;
+    use crate::wad::WadReader;
+    use test_helpers::wad::write_wad;
+
+    #[test]
+    fn should_read_the_level_from_the_lump() {
+        let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+        write_wad(wad.path(), "PWAD", &[("COMPLVL", b" Boom\r\n")]);
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(
+            CompLevel::from_complvl_lump(&mut reader).unwrap(),
+            Some(CompLevel::Boom)
+        );
+
+        write_wad(wad.path(), "PWAD", &[("MAP01", b"")]);
+        let mut reader = WadReader::open(wad.path()).unwrap();
+        assert_eq!(CompLevel::from_complvl_lump(&mut reader).unwrap(), None);
+    }
+}
+
+#[cfg(test)]
+mod from_linedef_special {
+    use super::CompLevel;
+
+    #[test]
+    fn should_get_the_level_that_introduced_the_special() {
+        assert_eq!(CompLevel::from_linedef_special(11), CompLevel::Vanilla);
+        assert_eq!(CompLevel::from_linedef_special(242), CompLevel::Boom);
+        assert_eq!(CompLevel::from_linedef_special(272), CompLevel::Mbf);
+        assert_eq!(CompLevel::from_linedef_special(1025), CompLevel::Mbf21);
+        assert_eq!(CompLevel::from_linedef_special(0x3001), CompLevel::Boom);
+    }
+
+    #[test]
+    fn should_use_boom_for_the_change_texture_and_effect_special() {
+        assert_eq!(CompLevel::from_linedef_special(78), CompLevel::Boom);
+    }
+
+    #[test]
+    fn should_use_boom_for_the_scroll_texture_right_special() {
+        assert_eq!(CompLevel::from_linedef_special(85), CompLevel::Boom);
+    }
+}
+
+#[cfg(test)]
+mod from_dehacked {
+    use super::CompLevel;
+
+    #[test]
+    fn should_get_the_level_from_the_version_and_the_extensions() {
+        let patch = "Patch File for DeHackEd v3.0\nDoom version = 21\nThing 1\nHit points = 50\n";
+        assert_eq!(CompLevel::from_dehacked(patch), CompLevel::Vanilla);
+        let patch = "Patch File for DeHackEd v3.0\n[STRINGS]\nGOTARMOR = Armour\n";
+        assert_eq!(CompLevel::from_dehacked(patch), CompLevel::Boom);
+        let patch = "Patch File for DeHackEd v3.0\nDoom version = 2021\nThing 1\nMBF21 Bits = 0\n";
+        assert_eq!(CompLevel::from_dehacked(patch), CompLevel::Mbf21);
+    }
+}
+
+#[cfg(test)]
+mod get_number {
+    use super::CompLevel;
+    use crate::iwad::Game;
+
+    #[test]
+    fn should_use_the_game_for_the_vanilla_level() {
+        assert_eq!(CompLevel::Vanilla.get_number(Some(Game::Doom2)), 2);
+        assert_eq!(CompLevel::Vanilla.get_number(Some(Game::UltimateDoom)), 3);
+        assert_eq!(CompLevel::Vanilla.get_number(Some(Game::Tnt)), 4);
+        assert_eq!(CompLevel::Vanilla.get_number(None), 2);
+        assert_eq!(CompLevel::Mbf.get_number(Some(Game::Tnt)), 11);
+    }
+}
@@ -0,0 +1,823 @@
+use crate::source_port::{Skill, SourcePort};
+use crate::wad::{MapInfo, Warp};
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Report, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+lazy_static! {
+    /// The progress lines written by the encoders, e.g. `frame=  120 fps= 60` for ffmpeg, or
+    /// `[12.5%] 120/960 frames` and `120 frames: 60.00 fps` for x264.
+    static ref ENCODER_FRAMES_REGEX: Regex =
+        Regex::new(r"frame=\s*([0-9]+)|\b([0-9]+)(?:/[0-9]+)? frames\b").unwrap();
+}
+
+/// The extension for demo files, which goes back to the `.lmp` lumps vanilla DOOM wrote them to.
+pub const DEMO_EXTENSION: &str = "lmp";
+const TICS_PER_SECOND: u64 = 35;
+/// The byte that follows the last tic. It can't be the first byte of a tic, since that's the
+/// forward movement, which never gets near -128.
+const DEMO_END_MARKER: u8 = 0x80;
+/// The block the Boom family stores its options in.
+const GAME_OPTION_SIZE: usize = 64;
+/// MBF21 has its own set of compatibility options, so its block is a different size.
+const MBF21_GAME_OPTION_SIZE: usize = 46;
+/// Boom 2.00 wrote a bigger block, most of which was never used.
+const BOOM_200_GAME_OPTION_SIZE: usize = 256;
+const VANILLA_MAX_PLAYERS: usize = 4;
+/// The Boom family has room for this many players in the header, though only 4 can play.
+const BOOM_MAX_PLAYERS: usize = 32;
+/// The signature of DSDA-Doom's header, which comes before the usual header.
+const DSDA_SIGNATURE: [u8; 6] = [0x1D, b'D', b'S', b'D', b'A', 0xE6];
+/// The signature of the extended header PrBoom+ writes for demos recorded with UMAPINFO.
+const EXTENDED_SIGNATURE: &[u8] = b"PR+UM";
+const EXTENDED_HEADER_VERSION: u8 = 255;
+/// The signature used by Boom and LxDoom. MBF and PrBoom have their own.
+const BOOM_SIGNATURE: [u8; 6] = [0x1D, b'B', b'o', b'o', b'm', 0xE6];
+/// The options at the start of the block, which every format in the Boom family shares. The gaps
+/// are for the options that are read into other fields.
+const BOOM_OPTIONS: [(usize, &str); 5] = [
+    (0, "monsters_remember"),
+    (1, "variable_friction"),
+    (2, "weapon_recoil"),
+    (3, "allow_pushers"),
+    (5, "player_bobbing"),
+];
+/// The options MBF added, which PrBoom kept.
+const MBF_OPTIONS: [(usize, &str); 8] = [
+    (14, "monster_infighting"),
+    (15, "dogs"),
+    (20, "monster_backing"),
+    (21, "monster_avoid_hazards"),
+    (22, "monster_friction"),
+    (23, "help_friends"),
+    (24, "dog_jumping"),
+    (25, "monkeys"),
+];
+/// MBF's compatibility options, which follow the other options.
+const MBF_COMP_OPTIONS_OFFSET: usize = 26;
+const MBF_COMP_OPTIONS: [&str; 17] = [
+    "comp_telefrag",
+    "comp_dropoff",
+    "comp_vile",
+    "comp_pain",
+    "comp_skull",
+    "comp_blazing",
+    "comp_doorlight",
+    "comp_model",
+    "comp_god",
+    "comp_falloff",
+    "comp_floors",
+    "comp_skymap",
+    "comp_pursuit",
+    "comp_doorstuck",
+    "comp_staylift",
+    "comp_zombie",
+    "comp_stairs",
+];
+
+/// The categories used by the Doom Speed Demo Archive, which determine the rules a run is played
+/// under.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DemoCategory {
+    UvMax,
+    UvSpeed,
+    NmSpeed,
+    Nm100,
+    Tyson,
+    Pacifist,
+    NoMo,
+    /// For anything that isn't a run in one of the other categories, like practice.
+    Other,
+}
+
+impl DemoCategory {
+    /// The code that goes in the file name, after the map.
+    pub fn get_code(&self) -> &'static str {
+        match self {
+            Self::UvMax => "m",
+            Self::UvSpeed => "s",
+            Self::NmSpeed => "n",
+            Self::Nm100 => "ns",
+            Self::Tyson => "t",
+            Self::Pacifist => "p",
+            Self::NoMo => "o",
+            Self::Other => "x",
+        }
+    }
+}
+
+impl FromStr for DemoCategory {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<DemoCategory, Self::Err> {
+        match input {
+            "UvMax" => Ok(DemoCategory::UvMax),
+            "UvSpeed" => Ok(DemoCategory::UvSpeed),
+            "NmSpeed" => Ok(DemoCategory::NmSpeed),
+            "Nm100" => Ok(DemoCategory::Nm100),
+            "Tyson" => Ok(DemoCategory::Tyson),
+            "Pacifist" => Ok(DemoCategory::Pacifist),
+            "NoMo" => Ok(DemoCategory::NoMo),
+            "Other" => Ok(DemoCategory::Other),
+            _ => Err(format!("{} is not a valid demo category", input)),
+        }
+    }
+}
+
+impl std::fmt::Display for DemoCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UvMax => write!(f, "UV-Max"),
+            Self::UvSpeed => write!(f, "UV-Speed"),
+            Self::NmSpeed => write!(f, "NM-Speed"),
+            Self::Nm100 => write!(f, "NM 100S"),
+            Self::Tyson => write!(f, "Tyson"),
+            Self::Pacifist => write!(f, "Pacifist"),
+            Self::NoMo => write!(f, "NoMo"),
+            Self::Other => write!(f, "Other"),
+        }
+    }
+}
+
+/// A demo that was recorded with the `play` command.
+///
+/// The file is kept in a directory named after the WAD, in the demos directory in the Doom home,
+/// and the entry is saved in the settings directory, with the file name as its ID.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DemoEntry {
+    pub id: String,
+    pub file_name: String,
+    pub wad_id: String,
+    /// The map the demo starts on. This isn't set for a demo that starts from the title screen,
+    /// which is usually a run of the whole WAD.
+    pub map: Option<String>,
+    pub category: DemoCategory,
+    /// The number of the attempt at this map and category.
+    pub attempt: u32,
+    pub source_port: SourcePort,
+    pub source_port_version: String,
+    pub profile: String,
+    pub recorded: DateTime<Utc>,
+}
+
+/// Gets the name for a demo, without the extension, following the DSDA convention: the WAD, the
+/// map, the category code, then the attempt number, e.g. `scythe207m-001` for the first UV-Max
+/// attempt at MAP07 of Scythe 2.
+///
+/// The map is the number for MAPxx, the episode and map for ExMy, or `all` for a demo that isn't
+/// started on a particular map. The DSDA uses the time rather than an attempt number, but the time
+/// isn't known until the demo has been recorded.
+pub fn get_demo_name(
+    wad_id: &str,
+    map: Option<&MapInfo>,
+    category: DemoCategory,
+    attempt: u32,
+) -> String {
+    format!("{}-{:03}", get_demo_prefix(wad_id, map, category), attempt)
+}
+
+/// Gets the part of the demo name that's shared by every attempt.
+pub fn get_demo_prefix(wad_id: &str, map: Option<&MapInfo>, category: DemoCategory) -> String {
+    let map = match map.map(|x| &x.warp) {
+        Some(Warp::Map(map)) => format!("{:02}", map),
+        Some(Warp::Episode(episode, map)) => format!("{}{}", episode, map),
+        Some(Warp::LumpName(name)) => name.to_lowercase(),
+        None => "all".to_string(),
+    };
+    format!("{}{}{}", wad_id.to_lowercase(), map, category.get_code())
+}
+
+/// Gets the number for the next attempt, which is one more than the highest attempt for the same
+/// demo name in the directory, so numbers aren't reused if a demo has been deleted.
+pub fn get_next_attempt(demos_path: &Path, prefix: &str) -> Result<u32, Report> {
+    let mut attempt = 0;
+    if demos_path.is_dir() {
+        for dir_entry in std::fs::read_dir(demos_path)? {
+            let path = dir_entry?.path();
+            let stem = path
+                .file_stem()
+                .map(|x| x.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let previous = stem
+                .strip_prefix(&format!("{}-", prefix))
+                .and_then(|x| x.parse::<u32>().ok());
+            if let Some(previous) = previous {
+                attempt = attempt.max(previous);
+            }
+        }
+    }
+    Ok(attempt + 1)
+}
+
+/// The family of executables a demo was recorded with, which decides the layout of its header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DemoFormat {
+    Vanilla,
+    Boom,
+    Mbf,
+    PrBoom,
+    Mbf21,
+}
+
+impl std::fmt::Display for DemoFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vanilla => write!(f, "Vanilla"),
+            Self::Boom => write!(f, "Boom"),
+            Self::Mbf => write!(f, "MBF"),
+            Self::PrBoom => write!(f, "PrBoom"),
+            Self::Mbf21 => write!(f, "MBF21"),
+        }
+    }
+}
+
+/// The information at the start of a demo, along with the number of tics that follow it.
+///
+/// Every format starts with the version, apart from the demos from before DOOM 1.4, which start
+/// with the skill. The Boom family follow the version with a signature and their own options.
+/// DSDA-Doom and PrBoom+ can also put their own header in front, which is skipped over, apart from
+/// the map name PrBoom+ stores for demos recorded with UMAPINFO.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DemoHeader {
+    pub format: DemoFormat,
+    /// This isn't set for the demos from before DOOM 1.4.
+    pub version: Option<u8>,
+    /// The complevel the demo has to be played back with.
+    pub complevel: u32,
+    pub skill: Skill,
+    pub episode: u8,
+    pub map: u8,
+    /// The lump name of the map, which is only stored for demos recorded with UMAPINFO.
+    pub map_lump_name: Option<String>,
+    /// 0 for cooperative, 1 for deathmatch and 2 for altdeath.
+    pub deathmatch: u8,
+    pub respawn: bool,
+    pub fast: bool,
+    pub no_monsters: bool,
+    /// The player whose view the demo is shown from.
+    pub console_player: u8,
+    /// The slots of the players in the game, starting from 0.
+    pub players: Vec<u8>,
+    /// The Boom family's gameplay and compatibility options, by name.
+    pub options: Vec<(String, u8)>,
+    /// Whether the turning is stored at full resolution, which takes an extra byte per tic.
+    pub longtics: bool,
+    pub tics: u32,
+}
+
+impl DemoHeader {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<DemoHeader, Report> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        DemoHeader::parse(&data).map_err(|e| eyre!("Failed to read {}: {}", path.display(), e))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<DemoHeader, Report> {
+        let mut offset = 0;
+        let mut end_marker_offset = None;
+        if data.starts_with(&DSDA_SIGNATURE) {
+            // The DSDA header has its own version, then the location of the end marker.
+            let header = read_bytes(data, &mut offset, DSDA_SIGNATURE.len() + 6)?;
+            let location = u32::from_le_bytes([header[7], header[8], header[9], header[10]]);
+            end_marker_offset = Some(location as usize);
+        }
+        let mut map_lump_name = None;
+        if data.get(offset) == Some(&EXTENDED_HEADER_VERSION) {
+            map_lump_name = read_extended_header(data, &mut offset)?;
+        }
+
+        let version = *data
+            .get(offset)
+            .ok_or_else(|| eyre!("The demo is too short to have a header"))?;
+        let mut header = match version {
+            0..=4 => read_vanilla_header(data, &mut offset, false)?,
+            104..=111 => read_vanilla_header(data, &mut offset, true)?,
+            200..=203 | 210..=214 | 221 => read_boom_header(data, &mut offset)?,
+            _ => return Err(eyre!("{} is not a supported demo version", version)),
+        };
+        header.map_lump_name = map_lump_name;
+        header.tics = count_tics(data, offset, &header, end_marker_offset)?;
+        Ok(header)
+    }
+
+    pub fn get_duration(&self) -> Duration {
+        Duration::from_millis(u64::from(self.tics) * 1000 / TICS_PER_SECOND)
+    }
+
+    /// Gets the number of the map the demo starts on. The header always has an episode, even for
+    /// the games that don't use them, so it's up to the caller to say which kind of game it is.
+    pub fn get_map_number(&self, has_episodes: bool) -> String {
+        if let Some(map_lump_name) = &self.map_lump_name {
+            return map_lump_name.clone();
+        }
+        if has_episodes {
+            format!("E{}M{}", self.episode, self.map)
+        } else {
+            format!("MAP{:02}", self.map)
+        }
+    }
+
+    /// Gets the version of the executable the demo was recorded with, e.g. 1.9 or PrBoom 2.2.
+    pub fn get_version_name(&self) -> String {
+        match self.version {
+            None => "1.2 or earlier".to_string(),
+            Some(version @ 104..=111) => format!("{}.{}", version / 100, version % 100),
+            Some(version @ 200..=202) => format!("Boom 2.0{}", version % 100),
+            Some(203) if self.format == DemoFormat::Boom => "LxDoom".to_string(),
+            Some(203) => "MBF".to_string(),
+            Some(210) => "PrBoom 2.1".to_string(),
+            Some(version @ 211..=214) => format!("PrBoom 2.{}", version - 209),
+            Some(_) => "MBF21".to_string(),
+        }
+    }
+}
+
+/// Reads the extended header PrBoom+ writes in front of a demo recorded with UMAPINFO. It lists
+/// the extensions that were used, and if UMAPINFO was one of them, the name of the map follows.
+fn read_extended_header(data: &[u8], offset: &mut usize) -> Result<Option<String>, Report> {
+    read_bytes(data, offset, 1)?;
+    if read_bytes(data, offset, EXTENDED_SIGNATURE.len())? != EXTENDED_SIGNATURE {
+        return Err(eyre!(
+            "The demo has an extended header that isn't recognised"
+        ));
+    }
+    // The version of the extended header, which has only ever been 1.
+    read_bytes(data, offset, 1)?;
+    let count = read_bytes(data, offset, 2)?;
+    let mut umapinfo = false;
+    for _ in 0..u16::from_le_bytes([count[0], count[1]]) {
+        let length = read_bytes(data, offset, 1)?[0] as usize;
+        if read_bytes(data, offset, length)? == b"UMAPINFO" {
+            umapinfo = true;
+        }
+    }
+    if !umapinfo {
+        return Ok(None);
+    }
+    let name = read_bytes(data, offset, 8)?;
+    Ok(Some(
+        String::from_utf8_lossy(name)
+            .trim_end_matches('\0')
+            .to_uppercase(),
+    ))
+}
+
+/// Reads the vanilla header. The demos from before 1.4 only have the skill, the map and the
+/// players.
+fn read_vanilla_header(
+    data: &[u8],
+    offset: &mut usize,
+    has_version: bool,
+) -> Result<DemoHeader, Report> {
+    let version = if has_version {
+        Some(read_bytes(data, offset, 1)?[0])
+    } else {
+        None
+    };
+    let skill = get_skill(read_bytes(data, offset, 1)?[0])?;
+    let map = read_bytes(data, offset, 2)?;
+    let (episode, map) = (map[0], map[1]);
+    let (deathmatch, respawn, fast, no_monsters, console_player) = if has_version {
+        let options = read_bytes(data, offset, 5)?;
+        (
+            options[0],
+            options[1] != 0,
+            options[2] != 0,
+            options[3] != 0,
+            options[4],
+        )
+    } else {
+        (0, false, false, false, 0)
+    };
+    let players = get_players(read_bytes(data, offset, VANILLA_MAX_PLAYERS)?);
+    // 1.9 was also used by The Ultimate DOOM and Final DOOM, which have their own complevels, but
+    // there's nothing in the header to tell them apart.
+    let complevel = match version {
+        None => 0,
+        Some(104..=108) => 1,
+        Some(_) => 2,
+    };
+    Ok(DemoHeader {
+        format: DemoFormat::Vanilla,
+        version,
+        complevel,
+        skill,
+        episode,
+        map,
+        map_lump_name: None,
+        deathmatch,
+        respawn,
+        fast,
+        no_monsters,
+        console_player,
+        players,
+        options: Vec::new(),
+        longtics: version == Some(111),
+        tics: 0,
+    })
+}
+
+/// Reads the header used by Boom and its descendants. The version is followed by the signature
+/// of the executable, a byte that was only used by Boom for its compatibility mode, the skill and
+/// the map, then a block of options.
+///
+/// LxDoom wrote version 203 with the Boom signature, and without the compatibility byte, which
+/// is how the ports tell it apart from MBF.
+fn read_boom_header(data: &[u8], offset: &mut usize) -> Result<DemoHeader, Report> {
+    let version = read_bytes(data, offset, 1)?[0];
+    let signature = read_bytes(data, offset, BOOM_SIGNATURE.len())?;
+    let is_boom_signature = signature == BOOM_SIGNATURE;
+    let compatibility = if version == 203 && is_boom_signature {
+        false
+    } else {
+        read_bytes(data, offset, 1)?[0] != 0
+    };
+    let skill = get_skill(read_bytes(data, offset, 1)?[0])?;
+    let fields = read_bytes(data, offset, 4)?;
+    let (episode, map, deathmatch, console_player) = (fields[0], fields[1], fields[2], fields[3]);
+    let option_size = match version {
+        200 => BOOM_200_GAME_OPTION_SIZE,
+        221 => MBF21_GAME_OPTION_SIZE,
+        _ => GAME_OPTION_SIZE,
+    };
+    let block = read_bytes(data, offset, option_size)?;
+    let players = get_players(read_bytes(data, offset, BOOM_MAX_PLAYERS)?);
+
+    let mut options = BOOM_OPTIONS
+        .iter()
+        .map(|(index, name)| (name.to_string(), block[*index]))
+        .collect::<Vec<(String, u8)>>();
+    if (203..=214).contains(&version) && !is_boom_signature {
+        options.extend(
+            MBF_OPTIONS
+                .iter()
+                .map(|(index, name)| (name.to_string(), block[*index])),
+        );
+        options.extend(
+            MBF_COMP_OPTIONS
+                .iter()
+                .enumerate()
+                .map(|(index, name)| (name.to_string(), block[MBF_COMP_OPTIONS_OFFSET + index])),
+        );
+    }
+    let (format, complevel) = match version {
+        200..=202 if compatibility => (DemoFormat::Boom, 7),
+        200 | 201 => (DemoFormat::Boom, 8),
+        202 => (DemoFormat::Boom, 9),
+        203 if is_boom_signature => (DemoFormat::Boom, 10),
+        203 => (DemoFormat::Mbf, 11),
+        221 => (DemoFormat::Mbf21, 21),
+        _ => (DemoFormat::PrBoom, u32::from(version) - 197),
+    };
+    Ok(DemoHeader {
+        format,
+        version: Some(version),
+        complevel,
+        skill,
+        episode,
+        map,
+        map_lump_name: None,
+        deathmatch,
+        respawn: block[6] != 0,
+        fast: block[7] != 0,
+        no_monsters: block[8] != 0,
+        console_player,
+        players,
+        options,
+        // PrBoom+ records with longtics from complevel 17, and so does MBF21.
+        longtics: matches!(version, 214 | 221),
+        tics: 0,
+    })
+}
+
+/// Counts the tics between the header and the end marker. Each tic has a command for each player.
+///
+/// If the end marker is missing, the demo was cut short, e.g. because the game crashed, and the
+/// tics that are there are counted.
+fn count_tics(
+    data: &[u8],
+    start: usize,
+    header: &DemoHeader,
+    end_marker_offset: Option<usize>,
+) -> Result<u32, Report> {
+    let command_size = if header.longtics { 5 } else { 4 };
+    let tic_size = command_size * header.players.len();
+    if tic_size == 0 {
+        return Err(eyre!("The demo doesn't have any players"));
+    }
+    let end = match end_marker_offset {
+        Some(end) if data.get(end) == Some(&DEMO_END_MARKER) && end >= start => end,
+        _ => data[start..]
+            .chunks(tic_size)
+            .position(|x| x[0] == DEMO_END_MARKER)
+            .map_or(data.len(), |x| start + x * tic_size),
+    };
+    Ok(((end - start) / tic_size) as u32)
+}
+
+fn read_bytes<'a>(data: &'a [u8], offset: &mut usize, count: usize) -> Result<&'a [u8], Report> {
+    let bytes = data
+        .get(*offset..*offset + count)
+        .ok_or_else(|| eyre!("The demo is too short to have a complete header"))?;
+    *offset += count;
+    Ok(bytes)
+}
+
+fn get_skill(value: u8) -> Result<Skill, Report> {
+    match value {
+        0 => Ok(Skill::TooYoungToDie),
+        1 => Ok(Skill::HeyNotTooRough),
+        2 => Ok(Skill::HurtMePlenty),
+        3 => Ok(Skill::UltraViolence),
+        4 => Ok(Skill::Nightmare),
+        _ => Err(eyre!("{} is not a valid skill", value)),
+    }
+}
+
+fn get_players(slots: &[u8]) -> Vec<u8> {
+    slots
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| **x != 0)
+        .map(|(slot, _)| slot as u8)
+        .collect()
+}
+
+/// Gets the number of tics a video encoder has got through, from a line of its progress output.
+/// The encoders count frames, so the frame rate of the video is needed to turn them into tics.
+pub fn get_encoded_tics(line: &str, fps: u32) -> Option<u32> {
+    let captures = ENCODER_FRAMES_REGEX.captures(line)?;
+    let frames = captures
+        .get(1)
+        .or_else(|| captures.get(2))?
+        .as_str()
+        .parse::<u64>()
+        .ok()?;
+    Some((frames * TICS_PER_SECOND / u64::from(fps.max(1))) as u32)
+}
+
+/// Formats the duration as m:ss.cc, the way the source ports show times.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}.{:02}",
+        seconds / 60,
+        seconds % 60,
+        duration.subsec_millis() / 10
+    )
+}
+
+#[cfg(test)]
+mod get_demo_name {
+    use super::{get_demo_name, DemoCategory};
+    use crate::wad::MapInfo;
+
+    #[test]
+    fn should_follow_the_dsda_naming_convention() {
+        let map = MapInfo::new("MAP07".to_string(), "Dead Simple".to_string()).unwrap();
+        assert_eq!(
+            get_demo_name("Scythe2", Some(&map), DemoCategory::UvMax, 1),
+            "scythe207m-001"
+        );
+        let map = MapInfo::new("E1M8".to_string(), "Phobos Anomaly".to_string()).unwrap();
+        assert_eq!(
+            get_demo_name("DOOM", Some(&map), DemoCategory::NmSpeed, 12),
+            "doom18n-012"
+        );
+        assert_eq!(
+            get_demo_name("DOOM2", None, DemoCategory::UvSpeed, 3),
+            "doom2alls-003"
+        );
+    }
+}
+
+#[cfg(test)]
+mod get_next_attempt {
+    use super::get_next_attempt;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn should_use_one_more_than_the_highest_attempt() {
+        let demos_dir = assert_fs::TempDir::new().unwrap();
+        assert_eq!(get_next_attempt(demos_dir.path(), "doom207m").unwrap(), 1);
+
+        demos_dir.child("doom207m-001.lmp").touch().unwrap();
+        demos_dir.child("doom207m-004.lmp").touch().unwrap();
+        demos_dir.child("doom207s-009.lmp").touch().unwrap();
+        demos_dir.child("doom2all-010.lmp").touch().unwrap();
+        assert_eq!(get_next_attempt(demos_dir.path(), "doom207m").unwrap(), 5);
+    }
+}
+
+#[cfg(test)]
+mod get_encoded_tics {
+    use super::get_encoded_tics;
+
+    #[test]
+    fn should_read_the_frame_count_from_ffmpeg_and_x264() {
+        assert_eq!(
+            get_encoded_tics("frame=  120 fps= 60 q=28.0 size=     256kB", 60),
+            Some(70)
+        );
+        assert_eq!(
+            get_encoded_tics("[12.5%] 120/960 frames, 60.00 fps, 1200 kb/s", 60),
+            Some(70)
+        );
+        assert_eq!(get_encoded_tics("35 frames: 35.00 fps", 35), Some(35));
+        assert_eq!(get_encoded_tics("x264 [info]: profile High", 60), None);
+    }
+}
+
+#[cfg(test)]
+mod demo_header {
+    mod parse {
+        use super::super::{DemoFormat, DemoHeader, DEMO_END_MARKER, DSDA_SIGNATURE};
+        use crate::source_port::Skill;
+
+        fn build_tics(count: usize, tic_size: usize) -> Vec<u8> {
+            let mut tics = vec![0x19; count * tic_size];
+            tics.push(DEMO_END_MARKER);
+            tics
+        }
+
+        fn build_prboom_header(version: u8, skill: u8, episode: u8, map: u8) -> Vec<u8> {
+            let mut header = vec![version, 0x1D, b'P', b'r', b'B', b'o', 0xE6, 0];
+            header.extend(&[skill, episode, map, 0, 0]);
+            let mut options = vec![0; 64];
+            options[0] = 1;
+            options[7] = 1;
+            options[26 + 3] = 1;
+            header.extend(options);
+            let mut players = vec![0; 32];
+            players[0] = 1;
+            header.extend(players);
+            header
+        }
+
+        #[test]
+        fn should_parse_a_vanilla_demo() {
+            let mut data = vec![109, 3, 0, 7, 0, 0, 0, 0, 0, 1, 0, 0, 0];
+            data.extend(build_tics(70, 4));
+            // The footer some ports add after the end marker shouldn't be counted.
+            data.extend(b"-iwad doom2.wad");
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.format, DemoFormat::Vanilla);
+            assert_eq!(header.version, Some(109));
+            assert_eq!(header.complevel, 2);
+            assert_eq!(header.skill, Skill::UltraViolence);
+            assert_eq!(header.get_map_number(false), "MAP07");
+            assert_eq!(header.players, vec![0]);
+            assert!(!header.longtics);
+            assert_eq!(header.tics, 70);
+            assert_eq!(header.get_duration().as_secs(), 2);
+            assert_eq!(header.get_version_name(), "1.9");
+        }
+
+        #[test]
+        fn should_parse_a_longtics_demo_with_several_players() {
+            let mut data = vec![111, 4, 2, 3, 1, 0, 1, 0, 1, 1, 1, 0, 1];
+            data.extend(build_tics(10, 15));
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.skill, Skill::Nightmare);
+            assert_eq!(header.get_map_number(true), "E2M3");
+            assert_eq!(header.deathmatch, 1);
+            assert!(header.fast);
+            assert_eq!(header.console_player, 1);
+            assert_eq!(header.players, vec![0, 1, 3]);
+            assert!(header.longtics);
+            assert_eq!(header.tics, 10);
+        }
+
+        #[test]
+        fn should_parse_a_demo_from_before_version_1_4() {
+            let mut data = vec![2, 1, 1, 1, 0, 0, 0];
+            data.extend(build_tics(35, 4));
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.version, None);
+            assert_eq!(header.complevel, 0);
+            assert_eq!(header.skill, Skill::HurtMePlenty);
+            assert_eq!(header.get_map_number(true), "E1M1");
+            assert_eq!(header.tics, 35);
+        }
+
+        #[test]
+        fn should_parse_a_prboom_demo_with_its_options() {
+            let mut data = build_prboom_header(214, 3, 1, 1);
+            data.extend(build_tics(100, 5));
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.format, DemoFormat::PrBoom);
+            assert_eq!(header.complevel, 17);
+            assert!(header.longtics);
+            assert_eq!(header.skill, Skill::UltraViolence);
+            assert!(header.fast);
+            assert!(header
+                .options
+                .contains(&("monsters_remember".to_string(), 1)));
+            assert!(header.options.contains(&("comp_pain".to_string(), 1)));
+            assert!(header.options.contains(&("comp_skull".to_string(), 0)));
+            assert_eq!(header.tics, 100);
+            assert_eq!(header.get_version_name(), "PrBoom 2.5");
+        }
+
+        #[test]
+        fn should_use_the_compatibility_byte_for_boom_demos() {
+            let mut data = build_prboom_header(202, 3, 1, 1);
+            data[1..7].copy_from_slice(&[0x1D, b'B', b'o', b'o', b'm', 0xE6]);
+            data[7] = 1;
+            data.extend(build_tics(1, 4));
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.format, DemoFormat::Boom);
+            assert_eq!(header.complevel, 7);
+            assert!(!header.options.iter().any(|(name, _)| name == "comp_pain"));
+        }
+
+        #[test]
+        fn should_parse_an_lxdoom_demo_without_the_compatibility_byte() {
+            let mut data = build_prboom_header(203, 3, 1, 7);
+            data[1..7].copy_from_slice(&[0x1D, b'B', b'o', b'o', b'm', 0xE6]);
+            data.remove(7);
+            data.extend(build_tics(35, 4));
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.format, DemoFormat::Boom);
+            assert_eq!(header.complevel, 10);
+            assert_eq!(header.skill, Skill::UltraViolence);
+            assert_eq!(header.get_map_number(false), "MAP07");
+            assert!(header.fast);
+            assert!(!header.longtics);
+            assert_eq!(header.tics, 35);
+        }
+
+        #[test]
+        fn should_parse_a_dsda_demo_with_umapinfo() {
+            let mut regular = vec![221, 0x1D, b'M', b'B', b'F', 0x02, 0xE6, 0];
+            regular.extend(&[3, 1, 1, 0, 0]);
+            regular.extend(vec![0; 46]);
+            let mut players = vec![0; 32];
+            players[0] = 1;
+            regular.extend(players);
+
+            let mut extended = vec![255];
+            extended.extend(b"PR+UM");
+            extended.extend(&[1, 1, 0, 8]);
+            extended.extend(b"UMAPINFO");
+            extended.extend(b"e1m10\0\0\0");
+
+            let tics = build_tics(350, 5);
+            let end_marker = DSDA_SIGNATURE.len() + 6 + extended.len() + regular.len() + 350 * 5;
+            let mut data = DSDA_SIGNATURE.to_vec();
+            data.push(1);
+            data.extend(&(end_marker as u32).to_le_bytes());
+            data.push(0);
+            data.extend(extended);
+            data.extend(regular);
+            data.extend(tics);
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.format, DemoFormat::Mbf21);
+            assert_eq!(header.complevel, 21);
+            assert_eq!(header.map_lump_name, Some("E1M10".to_string()));
+            assert_eq!(header.get_map_number(true), "E1M10");
+            assert!(header.longtics);
+            assert_eq!(header.tics, 350);
+            assert_eq!(header.get_duration().as_secs(), 10);
+        }
+
+        #[test]
+        fn should_count_the_tics_of_a_demo_without_an_end_marker() {
+            let mut data = vec![109, 3, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0];
+            data.extend(vec![0x19; 4 * 20 + 2]);
+
+            let header = DemoHeader::parse(&data).unwrap();
+            assert_eq!(header.tics, 20);
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_unsupported_version() {
+            let result = DemoHeader::parse(&[150, 3, 0, 1, 0]);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "150 is not a supported demo version"
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_incomplete_header() {
+            let result = DemoHeader::parse(&[214, 0x1D, b'P', b'r', b'B', b'o', 0xE6, 0, 3]);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "The demo is too short to have a complete header"
+            );
+        }
+    }
+}
@@ -11,30 +11,74 @@ fn main() -> Result<(), Report> {
     color_eyre::install()?;
 
     let mut game_args: HashMap<String, String> = HashMap::new();
-    let flags = vec!["-fullscreen", "-music", "-nofullscreen", "-nomusic"];
+    let flags = [
+        "-fullscreen",
+        "-levelstat",
+        "-music",
+        "-nofullscreen",
+        "-nomusic",
+        "-window",
+    ];
     println!("Running fake source port");
+    println!(
+        "Game called with arguments: {}",
+        std::env::args().skip(1).collect::<Vec<String>>().join(" ")
+    );
 
     // This is probably the most inelegant implementation ever devised for parsing arguments, but
     // it seems to work and it's just a dummy thing anyway.
     let mut warp_arg = String::new();
     let mut prev = "".to_string();
     for arg in std::env::args().skip(1) {
-        if prev == "-warp" {
+        let is_option = arg.starts_with('-') || arg.starts_with('+');
+        if prev == "-warp" && !is_option {
             // The warp argument is an annoying special case, where it sometimes requires 2 values.
-            // TODO: this actually needs to be fixed to look up the iwad.
             warp_arg.push_str(&format!("{} ", arg.to_owned()));
-            if warp_arg.len() >= 3 {
-                game_args.insert(prev.to_owned(), warp_arg.to_owned().trim().to_string());
-            }
-        } else if arg.starts_with('-') {
+            game_args.insert(prev.to_owned(), warp_arg.to_owned().trim().to_string());
+        } else if is_option {
             if flags.iter().any(|x| **x == arg) {
                 game_args.insert(arg.to_owned(), "true".to_string());
             } else {
                 prev = arg.to_owned();
             }
         } else {
-            game_args.insert(prev.to_owned(), arg.to_owned());
+            // Arguments like `-file` can have more than one value.
+            game_args
+                .entry(prev.to_owned())
+                .and_modify(|x| {
+                    x.push(' ');
+                    x.push_str(&arg);
+                })
+                .or_insert_with(|| arg.to_owned());
+        }
+    }
+    // A real source port writes the demo as the game is played, so an empty file stands in for it.
+    if let Some(demo_path) = game_args.get("-record") {
+        std::fs::write(demo_path, b"")?;
+    }
+    // Likewise for a video, along with the progress lines an encoder would write.
+    if let Some(video_path) = game_args.get("-viddump") {
+        for frame in (60..=120).step_by(60) {
+            print!("frame={:>5} fps= 60\r", frame);
         }
+        println!();
+        std::fs::write(video_path, b"")?;
+    }
+    // The map that was warped to is reported as finished, with all the monsters killed but not
+    // all the secrets found.
+    if game_args.contains_key("-levelstat") {
+        let map = match game_args
+            .get("-warp")
+            .map(|x| x.split(' ').collect::<Vec<&str>>())
+        {
+            Some(warp) if warp.len() == 2 => format!("E{}M{}", warp[0], warp[1]),
+            Some(warp) => format!("MAP{:0>2}", warp[0]),
+            None => "MAP01".to_string(),
+        };
+        std::fs::write(
+            "levelstat.txt",
+            format!("{} - 0:31.97 (0:31)  K: 14/14  I: 4/9  S: 2/5\n", map),
+        )?;
     }
     for (arg, value) in game_args.iter() {
         if value == "true" {
@@ -43,5 +87,10 @@ fn main() -> Result<(), Report> {
             println!("Game called with {}: {}", arg, value);
         }
     }
+    // The tests can make the port fail, to check what happens when a real one crashes.
+    if let Ok(code) = std::env::var("TDL_FAKE_SOURCE_PORT_EXIT_CODE") {
+        eprintln!("I_Error: R_TextureNumForName: SKY4 not found");
+        std::process::exit(code.parse()?);
+    }
     Ok(())
 }
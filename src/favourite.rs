@@ -0,0 +1,114 @@
+use crate::settings::get_app_settings_dir_path;
+use crate::storage::ObjectRepository;
+use chrono::{DateTime, Utc};
+use color_eyre::{Report, Result};
+use serde::{Deserialize, Serialize};
+
+/// A WAD or a map that's been added to the favourites.
+///
+/// A favourite WAD covers all of its maps, so a map only needs to be added on its own if the rest
+/// of the WAD isn't a favourite.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Favourite {
+    pub wad_id: String,
+    /// The map number, which isn't set for a favourite WAD.
+    pub map: Option<String>,
+    pub added: DateTime<Utc>,
+}
+
+impl Favourite {
+    pub fn new(wad_id: &str, map: Option<&str>) -> Favourite {
+        Favourite {
+            wad_id: wad_id.to_string(),
+            map: map.map(String::from),
+            added: Utc::now(),
+        }
+    }
+
+    /// The ID the favourite is saved with, which is the WAD ID, followed by the map number for a
+    /// map.
+    pub fn get_id(wad_id: &str, map: Option<&str>) -> String {
+        match map {
+            Some(map) => format!("{}-{}", wad_id, map),
+            None => wad_id.to_string(),
+        }
+    }
+
+    /// How the favourite is referred to in messages, e.g. "MAP07 in DOOM2".
+    pub fn get_description(wad_id: &str, map: Option<&str>) -> String {
+        match map {
+            Some(map) => format!("{} in {}", map, wad_id),
+            None => wad_id.to_string(),
+        }
+    }
+
+    /// Checks if a map is covered by the favourite, either because it's the map that was added, or
+    /// because the whole WAD was.
+    pub fn matches(&self, wad_id: &str, map: &str) -> bool {
+        self.wad_id == wad_id && self.map.as_ref().is_none_or(|x| x == map)
+    }
+}
+
+pub fn get_favourite_repository() -> Result<ObjectRepository, Report> {
+    let mut favourites_path = get_app_settings_dir_path()?;
+    favourites_path.push("favourites");
+    Ok(ObjectRepository::new(&favourites_path)?)
+}
+
+pub fn get_favourites() -> Result<Vec<Favourite>, Report> {
+    Ok(get_favourite_repository()?.get_all()?)
+}
+
+#[cfg(test)]
+mod get_id {
+    use super::Favourite;
+
+    #[test]
+    fn should_use_the_wad_id_for_a_wad() {
+        assert_eq!(Favourite::get_id("DOOM2", None), "DOOM2");
+    }
+
+    #[test]
+    fn should_add_the_map_number_for_a_map() {
+        assert_eq!(Favourite::get_id("DOOM2", Some("MAP07")), "DOOM2-MAP07");
+    }
+}
+
+#[cfg(test)]
+mod get_description {
+    use super::Favourite;
+
+    #[test]
+    fn should_use_the_wad_id_for_a_wad() {
+        assert_eq!(Favourite::get_description("DOOM2", None), "DOOM2");
+    }
+
+    #[test]
+    fn should_name_the_map_and_its_wad_for_a_map() {
+        assert_eq!(
+            Favourite::get_description("DOOM2", Some("MAP07")),
+            "MAP07 in DOOM2"
+        );
+    }
+}
+
+#[cfg(test)]
+mod matches {
+    use super::Favourite;
+
+    #[test]
+    fn should_match_every_map_in_a_favourite_wad() {
+        let favourite = Favourite::new("DOOM2", None);
+        assert!(favourite.matches("DOOM2", "MAP01"));
+        assert!(favourite.matches("DOOM2", "MAP07"));
+        assert!(!favourite.matches("TNT", "MAP01"));
+    }
+
+    #[test]
+    fn should_only_match_a_favourite_map() {
+        let favourite = Favourite::new("DOOM2", Some("MAP07"));
+        assert!(favourite.matches("DOOM2", "MAP07"));
+        assert!(!favourite.matches("DOOM2", "MAP01"));
+        assert!(!favourite.matches("TNT", "MAP07"));
+    }
+}
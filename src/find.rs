@@ -1,41 +1,116 @@
+use crate::demo::format_duration;
+use crate::favourite::get_favourites;
+use crate::progress::{get_wad_progress, MapProgress};
+use crate::session::{get_play_session_repository, PlaySession};
 use crate::settings::get_app_settings_dir_path;
 use crate::storage::ObjectRepository;
-use crate::wad::WadEntry;
-use color_eyre::{Report, Result};
+use crate::wad::{MapInfo, WadEntry};
+use chrono::{DateTime, Local, Utc};
+use color_eyre::{eyre::eyre, Help, Report, Result};
 #[cfg(target_family = "unix")]
 use skim::prelude::*;
+use std::collections::HashMap;
 #[cfg(target_family = "unix")]
-use std::io::Cursor;
+use std::io::IsTerminal;
 use std::path::Path;
 
+/// A line that can be picked in the finder, along with the text for the preview pane, which is
+/// shown while the line is highlighted.
+struct FinderItem {
+    line: String,
+    preview: Option<String>,
+}
+
+#[cfg(target_family = "unix")]
+impl SkimItem for FinderItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.line)
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        match &self.preview {
+            Some(preview) => ItemPreview::Text(preview.clone()),
+            None => ItemPreview::Global,
+        }
+    }
+}
+
+/// Lets the user pick a map with the fuzzy finder, returning the WAD and the map number. There
+/// won't be a selection if the finder was cancelled.
+pub fn select_map_to_play(favourites_only: bool) -> Result<Option<(String, String)>, Report> {
+    let items = get_map_items(favourites_only)?;
+    Ok(run_finder("Please select a map to play", items)?.map(get_map_selection_from_search_result))
+}
+
+/// Lets the user pick one of the items with the fuzzy finder, returning its index. There won't be
+/// a selection if the finder was cancelled.
+pub fn select_item(prompt: &str, items: &[String]) -> Result<Option<usize>, Report> {
+    let finder_items = items
+        .iter()
+        .map(|x| FinderItem {
+            line: x.clone(),
+            preview: None,
+        })
+        .collect();
+    let selected = run_finder(prompt, finder_items)?;
+    Ok(selected.and_then(|selected| items.iter().position(|x| x == selected.trim_end())))
+}
+
+/// Skim panics if there's no terminal to draw on, like when TDL is run from a script.
+///
+/// The preview pane is only shown if the items have something to preview. The previews come from
+/// the items themselves, so there's no preview command to run.
 #[cfg(target_family = "unix")]
-pub fn select_map_to_play() -> Result<(String, String), Report> {
-    let search_string = get_search_string("\n")?;
+fn run_finder(prompt: &str, items: Vec<FinderItem>) -> Result<Option<String>, Report> {
+    if !std::io::stdin().is_terminal() {
+        return Err(
+            eyre!("The finder can only be used from a terminal").suggestion(
+                "Use the `play` command with the `--megawad` option to choose what to play",
+            ),
+        );
+    }
+    let prompt = format!("{}\n", prompt);
+    let has_preview = items.iter().any(|x| x.preview.is_some());
     let options = SkimOptionsBuilder::default()
         .height(Some("70%"))
         .multi(false)
-        .prompt(Some("Please select a map to play\n"))
+        .prompt(Some(&prompt))
+        .preview(if has_preview { Some("") } else { None })
+        .preview_window(Some("right:40%:wrap"))
         .build()
         .unwrap();
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(Cursor::new(search_string));
-    let selected = Skim::run_with(&options, Some(items))
-        .map(|out| out.selected_items)
-        .unwrap();
-    let result = String::from(selected.get(0).unwrap().output());
-    Ok(get_map_selection_from_search_result(result))
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+    for item in items {
+        let _ = tx.send(Arc::new(item));
+    }
+    drop(tx);
+    let selected = Skim::run_with(&options, Some(rx))
+        .filter(|out| !out.is_abort)
+        .and_then(|out| out.selected_items.first().map(|x| x.output().to_string()));
+    Ok(selected)
 }
 
+/// fzf can only preview the output of a command, so there's no preview pane on Windows.
+///
+/// The lines are given to fzf directly, rather than echoed by a shell, because the titles and
+/// authors can have quotes and other characters the shell would interpret.
 #[cfg(target_family = "windows")]
-pub fn select_map_to_play() -> Result<(String, String), Report> {
-    let search_string = get_search_string("`n")?;
-    let output = duct::cmd!("powershell.exe", format!("echo \"{}\"", search_string))
-        .pipe(duct::cmd!("fzf"))
+fn run_finder(prompt: &str, items: Vec<FinderItem>) -> Result<Option<String>, Report> {
+    let lines = items
+        .iter()
+        .map(|x| format!("{}\n", x.line))
+        .collect::<String>();
+    // fzf exits with an error and doesn't write anything when it's cancelled.
+    let output = duct::cmd!("fzf", "--prompt", format!("{}> ", prompt))
+        .stdin_bytes(lines)
+        .unchecked()
         .read()?;
-    Ok(get_map_selection_from_search_result(output))
+    Ok(Some(output).filter(|x| !x.is_empty()))
 }
 
-fn get_search_string(line_separator: &str) -> Result<String, Report> {
+/// Builds an item for each map that can be selected. The favourite maps, and the maps in favourite
+/// WADs, are listed first, or on their own if `favourites_only` is set.
+fn get_map_items(favourites_only: bool) -> Result<Vec<FinderItem>, Report> {
     let mut wads_path = get_app_settings_dir_path()?;
     wads_path.push("wads");
 
@@ -48,16 +123,156 @@ fn get_search_string(line_separator: &str) -> Result<String, Report> {
         wad_entries.push(wad);
     }
 
-    let mut search_entries = String::new();
-    for entry in wad_entries {
-        for map in entry.maps {
-            search_entries.push_str(&format!(
-                "{} {} {}{}",
-                entry.name, map.number, map.name, line_separator
+    let favourites = get_favourites()?;
+    if favourites_only && favourites.is_empty() {
+        return Err(eyre!("You don't have any favourites yet")
+            .suggestion("Use the `fav add` command to add a WAD or a map"));
+    }
+    let last_played = get_last_played_times()?;
+    let mut favourite_items = Vec::new();
+    let mut other_items = Vec::new();
+    for entry in &wad_entries {
+        let progress = get_wad_progress(&entry.id)?;
+        for map in &entry.maps {
+            let is_favourite = favourites.iter().any(|x| x.matches(&entry.id, &map.number));
+            if favourites_only && !is_favourite {
+                continue;
+            }
+            let map_progress = progress.get_map_progress(&map.number);
+            let last_played = last_played
+                .get(&(entry.id.clone(), map.number.clone()))
+                .copied();
+            let item = FinderItem {
+                line: get_search_line(entry, map, &map_progress, is_favourite),
+                preview: Some(get_preview(entry, map, &map_progress, last_played)),
+            };
+            if is_favourite {
+                favourite_items.push(item);
+            } else {
+                other_items.push(item);
+            }
+        }
+    }
+    favourite_items.extend(other_items);
+    Ok(favourite_items)
+}
+
+/// The WAD name and map number go at the start of the line, since the selection is parsed from
+/// them. The title and author of the WAD follow the map name, so a map can be searched for along
+/// with the WAD it's in, e.g. "Plutonia Caughtyard", or by who made it.
+fn get_search_line(
+    entry: &WadEntry,
+    map: &MapInfo,
+    progress: &[&MapProgress],
+    is_favourite: bool,
+) -> String {
+    let stats = map.stats.as_ref().map_or_else(String::new, |x| {
+        format!(" ({} monsters, {} secrets)", x.hard_monsters, x.secrets)
+    });
+    format!(
+        "{} {} {} - {} by {}{}{}{}",
+        entry.name,
+        map.number,
+        map.name,
+        entry.title,
+        entry.author,
+        stats,
+        get_finished_description(progress),
+        if is_favourite { " [Favourite]" } else { "" }
+    )
+}
+
+/// The details for the preview pane: the WAD the map is in, the statistics for the map, the best
+/// result on each skill it's been finished on, and when it was last played.
+fn get_preview(
+    entry: &WadEntry,
+    map: &MapInfo,
+    progress: &[&MapProgress],
+    last_played: Option<DateTime<Utc>>,
+) -> String {
+    let mut lines = vec![
+        entry.title.clone(),
+        format!("by {}", entry.author),
+        format!("Released {}", entry.release_date),
+        String::new(),
+        format!("{} {}", map.number, map.name),
+    ];
+    match &map.stats {
+        Some(stats) => {
+            lines.push(format!(
+                "Monsters: {} ({} on easy, {} on medium)",
+                stats.hard_monsters, stats.easy_monsters, stats.medium_monsters
             ));
+            lines.push(format!("Items: {}", stats.items));
+            lines.push(format!("Secrets: {}", stats.secrets));
+        }
+        None => lines.push("There are no statistics for this map".to_string()),
+    }
+    lines.push(String::new());
+    if progress.is_empty() {
+        lines.push("Not finished yet".to_string());
+    }
+    for result in progress {
+        let mut line = format!(
+            "Finished on {} in {}",
+            result.skill,
+            format_duration(result.best_time)
+        );
+        if result.all_kills {
+            line.push_str(", 100% kills");
+        }
+        if result.all_secrets {
+            line.push_str(", 100% secrets");
         }
+        let times = if result.completions == 1 {
+            "time"
+        } else {
+            "times"
+        };
+        line.push_str(&format!(" ({} {})", result.completions, times));
+        lines.push(line);
     }
-    Ok(search_entries)
+    lines.push(match last_played {
+        Some(last_played) => format!(
+            "Last played {}",
+            last_played.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        None => "Never played".to_string(),
+    });
+    lines.join("\n")
+}
+
+/// Works out when each map was last played, keyed by the WAD ID and the map number. Only the map
+/// a game was started on counts, since that's all a play session records.
+fn get_last_played_times() -> Result<HashMap<(String, String), DateTime<Utc>>, Report> {
+    let mut times: HashMap<(String, String), DateTime<Utc>> = HashMap::new();
+    for session in get_play_session_repository()?.get_all::<PlaySession>()? {
+        if let Some(map) = session.map {
+            let time = times
+                .entry((session.wad_id, map))
+                .or_insert(session.started);
+            *time = (*time).max(session.started);
+        }
+    }
+    Ok(times)
+}
+
+/// Marks a map that's been finished with the best time on any skill, and whether all the
+/// monsters have been killed and all the secrets found, so the finished maps can be searched for.
+pub fn get_finished_description(results: &[&MapProgress]) -> String {
+    let best_time = match results.iter().map(|x| x.best_time).min() {
+        Some(best_time) => best_time,
+        None => return String::new(),
+    };
+    let mut description = format!(" [Finished in {}", format_duration(best_time));
+    if results.iter().any(|x| x.all_kills) {
+        description.push_str(", 100% kills");
+    }
+    if results.iter().any(|x| x.all_secrets) {
+        description.push_str(", 100% secrets");
+    }
+    description.push(']');
+    description
 }
 
 fn get_map_selection_from_search_result(result: String) -> (String, String) {
@@ -70,3 +285,109 @@ fn get_map_selection_from_search_result(result: String) -> (String, String) {
         .to_owned();
     (selected_wad, split[1].clone())
 }
+
+#[cfg(test)]
+mod get_search_line {
+    use super::get_search_line;
+    use crate::wad::{MapInfo, WadEntry};
+
+    fn get_wad_entry() -> WadEntry {
+        WadEntry::new(
+            "PLUTONIA".to_string(),
+            "PLUTONIA.WAD".to_string(),
+            "The Plutonia Experiment".to_string(),
+            "1996-06-17".to_string(),
+            "Dario Casali and Milo Casali".to_string(),
+            vec![MapInfo::new("MAP01".to_string(), "Congo".to_string()).unwrap()],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn should_start_with_the_wad_name_and_map_number() {
+        let entry = get_wad_entry();
+        let line = get_search_line(&entry, &entry.maps[0], &[], false);
+        assert_eq!(
+            line,
+            "PLUTONIA.WAD MAP01 Congo - The Plutonia Experiment by Dario Casali and Milo Casali"
+        );
+    }
+
+    #[test]
+    fn should_mark_a_favourite_map() {
+        let entry = get_wad_entry();
+        let line = get_search_line(&entry, &entry.maps[0], &[], true);
+        assert!(line.ends_with(" [Favourite]"));
+    }
+}
+
+#[cfg(test)]
+mod get_preview {
+    use super::get_preview;
+    use crate::progress::MapProgress;
+    use crate::source_port::Skill;
+    use crate::wad::{MapInfo, MapStats, WadEntry};
+    use chrono::{Local, TimeZone, Utc};
+    use std::time::Duration;
+
+    fn get_wad_entry() -> WadEntry {
+        let mut map = MapInfo::new("MAP01".to_string(), "Congo".to_string()).unwrap();
+        map.stats = Some(MapStats {
+            easy_monsters: 48,
+            medium_monsters: 55,
+            hard_monsters: 62,
+            items: 19,
+            secrets: 2,
+            linedefs: 1024,
+            sectors: 210,
+        });
+        WadEntry::new(
+            "PLUTONIA".to_string(),
+            "PLUTONIA.WAD".to_string(),
+            "The Plutonia Experiment".to_string(),
+            "1996-06-17".to_string(),
+            "Dario Casali and Milo Casali".to_string(),
+            vec![map],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn should_show_the_wad_and_map_details_for_a_map_that_has_not_been_played() {
+        let entry = get_wad_entry();
+        let preview = get_preview(&entry, &entry.maps[0], &[], None);
+        assert_eq!(
+            preview,
+            "The Plutonia Experiment\n\
+            by Dario Casali and Milo Casali\n\
+            Released 1996-06-17\n\
+            \n\
+            MAP01 Congo\n\
+            Monsters: 62 (48 on easy, 55 on medium)\n\
+            Items: 19\n\
+            Secrets: 2\n\
+            \n\
+            Not finished yet\n\
+            Never played"
+        );
+    }
+
+    #[test]
+    fn should_show_the_results_and_when_the_map_was_last_played() {
+        let entry = get_wad_entry();
+        let progress = MapProgress {
+            map: "MAP01".to_string(),
+            skill: Skill::UltraViolence,
+            best_time: Duration::from_millis(83_450),
+            all_kills: true,
+            all_secrets: false,
+            completions: 3,
+        };
+        let last_played = Local.ymd(2021, 3, 1).and_hms(20, 15, 0).with_timezone(&Utc);
+        let preview = get_preview(&entry, &entry.maps[0], &[&progress], Some(last_played));
+        assert!(preview.ends_with(
+            "Finished on Ultra-Violence in 1:23.45, 100% kills (3 times)\n\
+            Last played 2021-03-01 20:15"
+        ));
+    }
+}
@@ -0,0 +1,527 @@
+use crate::complevel::CompLevel;
+use crate::source_port::get_current_tdl_version;
+use color_eyre::{Report, Result};
+use lazy_static::lazy_static;
+use log::{debug, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const IDGAMES_API_URL: &str = "https://www.doomworld.com/idgames/api/api.php";
+const IDGAMES_MIRROR_URL: &str = "https://youfailit.net/pub/idgames";
+lazy_static! {
+    /// The idgames text file template lays out its fields as `Name : Value`, with a variable amount
+    /// of padding between the name and the colon.
+    static ref TEXT_FILE_FIELD_REGEX: Regex =
+        Regex::new(r"(?i)^\s*(title|authors?|release date|date finished|advanced engine needed)\s*:\s*(.*?)\s*$").unwrap();
+    /// Authors often give the complevel the WAD was tested with, e.g. `-complevel 9` or `cl21`,
+    /// anywhere in the text file.
+    static ref COMPLEVEL_REGEX: Regex =
+        Regex::new(r"(?i)(?:\bcomplevel|\bcl)\s*:?\s*([0-9]{1,2})\b").unwrap();
+}
+
+#[derive(Debug, Error)]
+pub enum IdgamesError {
+    #[error("The idgames archive has no file with ID {0}")]
+    FileNotFound(u32),
+    #[error("The idgames API returned an error: {0}")]
+    Api(String),
+    #[error("The response from the idgames API for ID {0} is missing the {1} field")]
+    MissingField(u32, String),
+    #[error("The {0} archive does not contain a WAD file")]
+    NoWadInArchive(String),
+    #[error("Failed to retrieve response from the idgames API")]
+    ApiRequest(#[from] reqwest::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+}
+
+/// A file in the idgames archive, as described by the `get` action of the idgames API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IdgamesFile {
+    pub id: u32,
+    pub title: String,
+    pub author: String,
+    /// The date the file was uploaded, in the YYYY-MM-DD format.
+    pub date: String,
+    /// The directory of the file in the archive, e.g. `levels/doom2/Ports/megawads/`.
+    pub dir: String,
+    /// The name of the archive file, e.g. `scythe2.zip`.
+    pub filename: String,
+    /// The contents of the text file that was uploaded with the file.
+    pub textfile: String,
+}
+
+pub struct IdgamesClient {
+    pub api_base_url: String,
+    /// Files are downloaded from a mirror of the archive, rather than from the API.
+    pub mirror_base_url: String,
+}
+
+impl IdgamesClient {
+    pub fn new() -> IdgamesClient {
+        IdgamesClient {
+            api_base_url: String::from(IDGAMES_API_URL),
+            mirror_base_url: String::from(IDGAMES_MIRROR_URL),
+        }
+    }
+
+    pub fn get_file(&self, id: u32) -> Result<IdgamesFile, IdgamesError> {
+        info!("Getting information for idgames file {}", id);
+        let url = format!("{}?action=get&id={}&out=json", self.api_base_url, id);
+        let response = reqwest::blocking::Client::new()
+            .get(url)
+            .header(
+                reqwest::header::USER_AGENT,
+                format!("tdl {}", get_current_tdl_version()),
+            )
+            .send()?
+            .error_for_status()?;
+        let response_json = response.json::<Value>()?;
+        get_file_from_response(id, &response_json)
+    }
+
+    /// Downloads the archive for the file from the mirror and extracts it.
+    ///
+    /// Returns the paths of the extracted files.
+    pub fn download_file(
+        &self,
+        file: &IdgamesFile,
+        destination_dir_path: &Path,
+    ) -> Result<Vec<PathBuf>, IdgamesError> {
+        let url = format!("{}/{}{}", self.mirror_base_url, file.dir, file.filename);
+        info!("Downloading {}...", url);
+        let mut response = reqwest::blocking::Client::new()
+            .get(url)
+            .header(
+                reqwest::header::USER_AGENT,
+                format!("tdl {}", get_current_tdl_version()),
+            )
+            .send()?
+            .error_for_status()?;
+        let archive_path = destination_dir_path.join(&file.filename);
+        let mut archive_file = std::fs::File::create(&archive_path)?;
+        response.copy_to(&mut archive_file)?;
+
+        info!(
+            "Extracting {} to {}...",
+            archive_path.display(),
+            destination_dir_path.display()
+        );
+        // The paths are built from the same enclosed names `extract` writes the files to, so an
+        // entry that would be written outside the destination is rejected before anything is.
+        let mut zip_archive = zip::ZipArchive::new(std::fs::File::open(&archive_path)?)?;
+        let mut extracted_paths = Vec::new();
+        for i in 0..zip_archive.len() {
+            let file = zip_archive.by_index(i)?;
+            let enclosed_name = file
+                .enclosed_name()
+                .ok_or(zip::result::ZipError::InvalidArchive("Invalid file path"))?;
+            extracted_paths.push(destination_dir_path.join(enclosed_name));
+        }
+        zip_archive.extract(destination_dir_path)?;
+        std::fs::remove_file(&archive_path)?;
+        Ok(extracted_paths)
+    }
+}
+
+/// Gets the WAD from the files extracted from an idgames archive.
+///
+/// Most archives will only contain a single WAD, but some have additional WADs, e.g. a separate
+/// WAD for the music, or a fix for a particular source port. In this case the WAD with the same
+/// name as the archive is used. Mods for the ZDoom family are often a PK3 instead of a WAD.
+pub fn get_wad_from_archive_files(
+    file: &IdgamesFile,
+    paths: &[PathBuf],
+) -> Result<PathBuf, IdgamesError> {
+    let wads = paths
+        .iter()
+        .filter(|path| {
+            path.extension()
+                .map(|x| x.eq_ignore_ascii_case("wad") || x.eq_ignore_ascii_case("pk3"))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<&PathBuf>>();
+    let archive_stem = Path::new(&file.filename)
+        .file_stem()
+        .map(|x| x.to_ascii_lowercase());
+    let wad = wads
+        .iter()
+        .find(|path| path.file_stem().map(|x| x.to_ascii_lowercase()) == archive_stem)
+        .or_else(|| wads.first())
+        .ok_or_else(|| IdgamesError::NoWadInArchive(file.filename.clone()))?;
+    debug!("Using {} from the {} archive", wad.display(), file.filename);
+    Ok(wad.to_path_buf())
+}
+
+/// The information from the text file that accompanies every upload to the idgames archive.
+///
+/// The uploads are supposed to follow a template, but in practice there's a lot of variation, so
+/// any of the fields could be missing.
+#[derive(Clone, Debug, Default)]
+pub struct IdgamesTextFile {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub release_date: Option<String>,
+    /// The compatibility level, from a complevel number or the advanced engine field.
+    pub complevel: Option<CompLevel>,
+}
+
+impl IdgamesTextFile {
+    /// Reads the text file at the given path.
+    ///
+    /// Many of these files were written in DOS editors and aren't valid UTF-8, so any invalid
+    /// characters are replaced rather than producing an error.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<IdgamesTextFile, Report> {
+        let bytes = std::fs::read(path)?;
+        Ok(IdgamesTextFile::parse(&String::from_utf8_lossy(&bytes)))
+    }
+
+    pub fn parse(contents: &str) -> IdgamesTextFile {
+        let mut text_file = IdgamesTextFile::default();
+        let mut engine = None;
+        for line in contents.lines() {
+            if let Some(captures) = TEXT_FILE_FIELD_REGEX.captures(line) {
+                let value = captures[2].to_string();
+                if value.is_empty() {
+                    continue;
+                }
+                let field = match captures[1].to_lowercase().as_str() {
+                    "title" => &mut text_file.title,
+                    "author" | "authors" => &mut text_file.author,
+                    "advanced engine needed" => &mut engine,
+                    _ => &mut text_file.release_date,
+                };
+                // Some text files repeat the template for each map, so only the first value for
+                // each field is used.
+                if field.is_none() {
+                    *field = Some(value);
+                }
+            }
+        }
+        // A complevel number is more specific than the engine, which is often just the name of a
+        // source port.
+        text_file.complevel = COMPLEVEL_REGEX
+            .captures(contents)
+            .and_then(|x| x[1].parse::<u32>().ok())
+            .and_then(CompLevel::from_number)
+            .or_else(|| engine.and_then(|x| CompLevel::from_engine(&x)));
+        text_file
+    }
+}
+
+fn get_file_from_response(id: u32, response: &Value) -> Result<IdgamesFile, IdgamesError> {
+    if let Some(message) = response["error"]["message"].as_str() {
+        return Err(IdgamesError::Api(message.to_string()));
+    }
+    let content = &response["content"];
+    if content.is_null() {
+        return Err(IdgamesError::FileNotFound(id));
+    }
+    let get_field = |name: &str| -> Result<String, IdgamesError> {
+        content[name]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| IdgamesError::MissingField(id, name.to_string()))
+    };
+    Ok(IdgamesFile {
+        id,
+        title: get_field("title")?,
+        author: get_field("author")?,
+        date: get_field("date")?,
+        dir: get_field("dir")?,
+        filename: get_field("filename")?,
+        textfile: get_field("textfile")?,
+    })
+}
+
+#[cfg(test)]
+mod get_file_from_response {
+    use super::{get_file_from_response, IdgamesError};
+    use serde_json::Value;
+    use std::path::Path;
+
+    fn read_response(name: &str) -> Value {
+        let response_path = Path::new("resources/test_data/idgames_responses").join(name);
+        let response = std::fs::read_to_string(response_path).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn should_return_the_file_information() {
+        let response = read_response("scythe2.json");
+        let file = get_file_from_response(13867, &response).unwrap();
+        assert_eq!(file.id, 13867);
+        assert_eq!(file.title, "Scythe 2");
+        assert_eq!(file.author, "Erik Alm");
+        assert_eq!(file.date, "2005-10-19");
+        assert_eq!(file.dir, "levels/doom2/Ports/megawads/");
+        assert_eq!(file.filename, "scythe2.zip");
+        assert!(file
+            .textfile
+            .starts_with("Title                   : Scythe 2"));
+    }
+
+    #[test]
+    fn should_return_an_error_for_a_file_that_does_not_exist() {
+        let response = read_response("not_found.json");
+        let result = get_file_from_response(999999, &response);
+        assert!(matches!(result, Err(IdgamesError::FileNotFound(999999))));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The idgames archive has no file with ID 999999"
+        );
+    }
+
+    #[test]
+    fn should_return_an_error_for_an_api_error() {
+        let response = read_response("error.json");
+        let result = get_file_from_response(1, &response);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The idgames API returned an error: The id parameter was not specified."
+        );
+    }
+}
+
+#[cfg(test)]
+mod idgames_client {
+    use super::{IdgamesClient, IdgamesError, IDGAMES_API_URL, IDGAMES_MIRROR_URL};
+    use std::io::Write;
+    use test_helpers::http::serve;
+
+    fn build_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn new_should_set_the_base_urls() {
+        let client = IdgamesClient::new();
+        assert_eq!(client.api_base_url, IDGAMES_API_URL);
+        assert_eq!(client.mirror_base_url, IDGAMES_MIRROR_URL);
+    }
+
+    #[test]
+    fn should_get_a_file_and_download_its_archive() {
+        let response = std::fs::read("resources/test_data/idgames_responses/scythe2.json").unwrap();
+        let archive = build_archive(&[("scythe2.wad", b"PWAD"), ("scythe2.txt", b"text")]);
+        let (base_url, handle) = serve(
+            vec![
+                (
+                    "/api.php?action=get&id=13867&out=json".to_string(),
+                    response,
+                ),
+                (
+                    "/levels/doom2/Ports/megawads/scythe2.zip".to_string(),
+                    archive,
+                ),
+            ],
+            2,
+        );
+        let client = IdgamesClient {
+            api_base_url: format!("{}/api.php", base_url),
+            mirror_base_url: base_url,
+        };
+        let destination_dir = assert_fs::TempDir::new().unwrap();
+
+        let file = client.get_file(13867).unwrap();
+        let paths = client.download_file(&file, destination_dir.path()).unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(file.title, "Scythe 2");
+        assert_eq!(
+            paths,
+            vec![
+                destination_dir.path().join("scythe2.wad"),
+                destination_dir.path().join("scythe2.txt")
+            ]
+        );
+        assert_eq!(std::fs::read(&paths[0]).unwrap(), b"PWAD");
+        assert!(!destination_dir.path().join("scythe2.zip").exists());
+    }
+
+    #[test]
+    fn should_reject_an_archive_with_a_file_outside_the_destination() {
+        let response = std::fs::read("resources/test_data/idgames_responses/scythe2.json").unwrap();
+        let archive = build_archive(&[("../scythe2.wad", b"PWAD")]);
+        let (base_url, handle) = serve(
+            vec![
+                (
+                    "/api.php?action=get&id=13867&out=json".to_string(),
+                    response,
+                ),
+                (
+                    "/levels/doom2/Ports/megawads/scythe2.zip".to_string(),
+                    archive,
+                ),
+            ],
+            2,
+        );
+        let client = IdgamesClient {
+            api_base_url: format!("{}/api.php", base_url),
+            mirror_base_url: base_url,
+        };
+        let parent_dir = assert_fs::TempDir::new().unwrap();
+        let destination_dir = parent_dir.path().join("downloads");
+        std::fs::create_dir(&destination_dir).unwrap();
+
+        let file = client.get_file(13867).unwrap();
+        let result = client.download_file(&file, &destination_dir);
+        handle.join().unwrap();
+
+        assert!(matches!(
+            result.unwrap_err(),
+            IdgamesError::Zip(zip::result::ZipError::InvalidArchive("Invalid file path"))
+        ));
+        assert!(!parent_dir.path().join("scythe2.wad").exists());
+    }
+}
+
+#[cfg(test)]
+mod get_wad_from_archive_files {
+    use super::{get_wad_from_archive_files, IdgamesFile};
+    use std::path::PathBuf;
+
+    fn get_file(filename: &str) -> IdgamesFile {
+        IdgamesFile {
+            id: 1,
+            title: "Test".to_string(),
+            author: "Test".to_string(),
+            date: "2021-01-01".to_string(),
+            dir: "levels/doom2/".to_string(),
+            filename: filename.to_string(),
+            textfile: String::new(),
+        }
+    }
+
+    #[test]
+    fn should_prefer_the_wad_with_the_same_name_as_the_archive() {
+        let paths = vec![
+            PathBuf::from("/tmp/music.wad"),
+            PathBuf::from("/tmp/MEGA.WAD"),
+            PathBuf::from("/tmp/mega.txt"),
+        ];
+        let wad = get_wad_from_archive_files(&get_file("mega.zip"), &paths).unwrap();
+        assert_eq!(wad, PathBuf::from("/tmp/MEGA.WAD"));
+    }
+
+    #[test]
+    fn should_use_the_first_wad_if_none_match_the_archive_name() {
+        let paths = vec![PathBuf::from("/tmp/a.txt"), PathBuf::from("/tmp/b.wad")];
+        let wad = get_wad_from_archive_files(&get_file("mega.zip"), &paths).unwrap();
+        assert_eq!(wad, PathBuf::from("/tmp/b.wad"));
+    }
+
+    #[test]
+    fn should_use_a_pk3_package() {
+        let paths = vec![
+            PathBuf::from("/tmp/mega.txt"),
+            PathBuf::from("/tmp/mega.pk3"),
+        ];
+        let wad = get_wad_from_archive_files(&get_file("mega.zip"), &paths).unwrap();
+        assert_eq!(wad, PathBuf::from("/tmp/mega.pk3"));
+    }
+
+    #[test]
+    fn should_return_an_error_if_there_is_no_wad() {
+        let paths = vec![PathBuf::from("/tmp/a.txt")];
+        let result = get_wad_from_archive_files(&get_file("mega.zip"), &paths);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The mega.zip archive does not contain a WAD file"
+        );
+    }
+}
+
+#[cfg(test)]
+mod idgames_text_file {
+    mod parse {
+        use super::super::IdgamesTextFile;
+        use crate::complevel::CompLevel;
+
+        #[test]
+        fn should_parse_fields_from_the_template() {
+            let contents = "\
+===========================================================================
+Archive Maintainer      : Please put this in /levels/doom2/megawads
+Advanced engine needed  : Limit-removing
+Primary purpose         : Single play
+===========================================================================
+Title                   : Scythe 2
+Filename                : scythe2.wad
+Release date            : 19/10/2005
+Author                  : Erik Alm
+Email Address           : erik@example.com
+";
+            let text_file = IdgamesTextFile::parse(contents);
+            assert_eq!(text_file.title, Some("Scythe 2".to_string()));
+            assert_eq!(text_file.author, Some("Erik Alm".to_string()));
+            assert_eq!(text_file.release_date, Some("19/10/2005".to_string()));
+        }
+
+        #[test]
+        fn should_use_the_first_value_for_repeated_fields() {
+            let contents = "\
+Title                   : Community Chest
+Authors                 : Various
+Title                   : Map01
+Date Finished           : 2001-02-03
+";
+            let text_file = IdgamesTextFile::parse(contents);
+            assert_eq!(text_file.title, Some("Community Chest".to_string()));
+            assert_eq!(text_file.author, Some("Various".to_string()));
+            assert_eq!(text_file.release_date, Some("2001-02-03".to_string()));
+        }
+
+        #[test]
+        fn should_ignore_fields_with_no_value() {
+            let contents = "\
+Title                   :
+Author                  : Someone
+";
+            let text_file = IdgamesTextFile::parse(contents);
+            assert!(text_file.title.is_none());
+            assert_eq!(text_file.author, Some("Someone".to_string()));
+            assert!(text_file.release_date.is_none());
+        }
+
+        #[test]
+        fn should_get_the_complevel_from_the_advanced_engine_field() {
+            let contents = "\
+Advanced engine needed  : Boom-compatible port
+Title                   : Boom Map
+";
+            let text_file = IdgamesTextFile::parse(contents);
+            assert_eq!(text_file.complevel, Some(CompLevel::Boom));
+
+            let contents = "Advanced engine needed  : GZDoom\n";
+            let text_file = IdgamesTextFile::parse(contents);
+            assert!(text_file.complevel.is_none());
+        }
+
+        #[test]
+        fn should_prefer_a_complevel_number_to_the_advanced_engine_field() {
+            let contents = "\
+Advanced engine needed  : DSDA-Doom
+Title                   : MBF21 Map
+Build Time              : A week
+Tested with             : dsda-doom -complevel 21
+";
+            let text_file = IdgamesTextFile::parse(contents);
+            assert_eq!(text_file.complevel, Some(CompLevel::Mbf21));
+        }
+    }
+}
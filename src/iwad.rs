@@ -0,0 +1,514 @@
+use crate::wad::{WadMetadata, UNKNOWN_VALUE};
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use lazy_static::lazy_static;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+lazy_static! {
+    static ref DOOM_MAP_INFO: HashMap<&'static str, &'static str> = {
+        maplit::hashmap! {
+            "E1M1" => "Hanger",
+            "E1M2" => "Nuclear Plant",
+            "E1M3" => "Toxin Refinery",
+            "E1M4" => "Command Control",
+            "E1M5" => "Phobos Lab",
+            "E1M6" => "Central Processing",
+            "E1M7" => "Computer Station",
+            "E1M8" => "Phobos Anomaly",
+            "E1M9" => "Military Base",
+            "E2M1" => "Deimos Anomaly",
+            "E2M2" => "Containment Area",
+            "E2M3" => "Refinery",
+            "E2M4" => "Deimos Lab",
+            "E2M5" => "Command Center",
+            "E2M6" => "Halls of the Damned",
+            "E2M7" => "Spawning Vats",
+            "E2M8" => "Tower of Babel",
+            "E2M9" => "Fortress of Mystery",
+            "E3M1" => "Hell Keep",
+            "E3M2" => "Slough of Despair",
+            "E3M3" => "Pandemonium",
+            "E3M4" => "House of Pain",
+            "E3M5" => "Unholy Cathedral",
+            "E3M6" => "Mt. Erebus",
+            "E3M7" => "Limbo",
+            "E3M8" => "Dis",
+            "E3M9" => "Warrens",
+            "E4M1" => "Hell Beneath",
+            "E4M2" => "Perfect Hatred",
+            "E4M3" => "Sever the Wicked",
+            "E4M4" => "Unruly Evil",
+            "E4M5" => "They Will Repent",
+            "E4M6" => "Against Thee Wickedly",
+            "E4M7" => "And Hell Followed",
+            "E4M8" => "Unto the Cruel",
+            "E4M9" => "Fear",
+        }
+    };
+    static ref DOOM2_MAP_INFO: HashMap<&'static str, &'static str> = {
+        maplit::hashmap! {
+            "MAP01" => "Entryway",
+            "MAP02" => "Underhalls",
+            "MAP03" => "The Gantlet",
+            "MAP04" => "The Focus",
+            "MAP05" => "The Waste Tunnels",
+            "MAP06" => "The Crusher",
+            "MAP07" => "Dead Simple",
+            "MAP08" => "Tricks and Traps",
+            "MAP09" => "The Pit",
+            "MAP10" => "Refueling Base",
+            "MAP11" => "'O' of Destruction!",
+            "MAP12" => "The Factory",
+            "MAP13" => "Downtown",
+            "MAP14" => "The Inmost Dens",
+            "MAP15" => "Industrial Zone",
+            "MAP16" => "Suburbs",
+            "MAP17" => "Tenements",
+            "MAP18" => "The Courtyard",
+            "MAP19" => "The Citadel",
+            "MAP20" => "Gotcha!",
+            "MAP21" => "Nirvana",
+            "MAP22" => "The Catacombs",
+            "MAP23" => "Barrels o' Fun",
+            "MAP24" => "The Chasm",
+            "MAP25" => "Bloodfalls",
+            "MAP26" => "The Abandoned Mines",
+            "MAP27" => "Monster Condo",
+            "MAP28" => "The Spirit World",
+            "MAP29" => "The Living End",
+            "MAP30" => "Icon of Sin",
+            "MAP31" => "Wolfenstein",
+            "MAP32" => "Grosse",
+            "MAP33" => "Betray",
+        }
+    };
+    static ref PLUTONIA_MAP_INFO: HashMap<&'static str, &'static str> = {
+        maplit::hashmap! {
+            "MAP01" => "Congo",
+            "MAP02" => "Well of Souls",
+            "MAP03" => "Aztec",
+            "MAP04" => "Caged",
+            "MAP05" => "Ghost Town",
+            "MAP06" => "Baron's Lair",
+            "MAP07" => "Caughtyard",
+            "MAP08" => "Realm",
+            "MAP09" => "Abattoire",
+            "MAP10" => "Onslaught",
+            "MAP11" => "Hunted",
+            "MAP12" => "Speed",
+            "MAP13" => "The Crypt",
+            "MAP14" => "Genesis",
+            "MAP15" => "The Twilight",
+            "MAP16" => "The Omen",
+            "MAP17" => "Compound",
+            "MAP18" => "Neurosphere",
+            "MAP19" => "NME",
+            "MAP20" => "The Death Domain",
+            "MAP21" => "Slayer",
+            "MAP22" => "Impossible Mission",
+            "MAP23" => "Tombstone",
+            "MAP24" => "The Final Frontier",
+            "MAP25" => "The Temple of Darkness",
+            "MAP26" => "Bunker",
+            "MAP27" => "Anti-Christ",
+            "MAP28" => "The Sewers",
+            "MAP29" => "Odyssey of Noises",
+            "MAP30" => "The Gateway of Hell",
+            "MAP31" => "Cyberden",
+            "MAP32" => "Go 2 It",
+        }
+    };
+    static ref TNT_MAP_INFO: HashMap<&'static str, &'static str> = {
+        maplit::hashmap! {
+            "MAP01" => "System Control",
+            "MAP02" => "Human BBQ",
+            "MAP03" => "Power Control",
+            "MAP04" => "Wormhole",
+            "MAP05" => "Hanger",
+            "MAP06" => "Open Season",
+            "MAP07" => "Prison",
+            "MAP08" => "Metal",
+            "MAP09" => "Stronghold",
+            "MAP10" => "Redemption",
+            "MAP11" => "Storage Facility",
+            "MAP12" => "Crater",
+            "MAP13" => "Nukage",
+            "MAP14" => "Steel Works",
+            "MAP15" => "Dead Zone",
+            "MAP16" => "Deepest Reaches",
+            "MAP17" => "Processing Area",
+            "MAP18" => "Mill",
+            "MAP19" => "Shipping/Respawning",
+            "MAP20" => "Central Processing",
+            "MAP21" => "Administration Center",
+            "MAP22" => "Habitat",
+            "MAP23" => "Lunar Mining Project",
+            "MAP24" => "Quarry",
+            "MAP25" => "Baron's Den",
+            "MAP26" => "Ballistyx",
+            "MAP27" => "Mount Pain",
+            "MAP28" => "Heck",
+            "MAP29" => "River Styx",
+            "MAP30" => "Last Call",
+            "MAP31" => "Pharaoh",
+            "MAP32" => "Caribbean",
+        }
+    };
+}
+
+/// The games that are distributed as IWADs.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Game {
+    DoomShareware,
+    Doom,
+    UltimateDoom,
+    Doom2,
+    Plutonia,
+    Tnt,
+    Freedoom1,
+    Freedoom2,
+    FreeDm,
+    ChexQuest,
+    Heretic,
+    Hexen,
+}
+
+impl Game {
+    /// The ID for the game's entry. Every release of a game has the same ID, because the source
+    /// ports only look for one file name for each game.
+    pub fn get_id(&self) -> String {
+        match self {
+            Game::DoomShareware => "DOOM1".to_string(),
+            Game::Doom => "DOOM".to_string(),
+            Game::UltimateDoom => "DOOM".to_string(),
+            Game::Doom2 => "DOOM2".to_string(),
+            Game::Plutonia => "PLUTONIA".to_string(),
+            Game::Tnt => "TNT".to_string(),
+            Game::Freedoom1 => "FREEDOOM1".to_string(),
+            Game::Freedoom2 => "FREEDOOM2".to_string(),
+            Game::FreeDm => "FREEDM".to_string(),
+            Game::ChexQuest => "CHEX".to_string(),
+            Game::Heretic => "HERETIC".to_string(),
+            Game::Hexen => "HEXEN".to_string(),
+        }
+    }
+
+    /// The file name the IWAD is imported with. Some source ports work out which game is being
+    /// played from the name of the IWAD, so a renamed file is given its usual name back.
+    pub fn get_file_name(&self) -> String {
+        format!("{}.WAD", self.get_id())
+    }
+
+    pub fn get_title(&self) -> String {
+        match self {
+            Game::DoomShareware => "DOOM (Shareware)".to_string(),
+            Game::Doom => "DOOM".to_string(),
+            Game::UltimateDoom => "The Ultimate DOOM".to_string(),
+            Game::Doom2 => "Doom II: Hell on Earth".to_string(),
+            Game::Plutonia => "The Plutonia Experiment".to_string(),
+            Game::Tnt => "TNT: Evilution".to_string(),
+            Game::Freedoom1 => "Freedoom: Phase 1".to_string(),
+            Game::Freedoom2 => "Freedoom: Phase 2".to_string(),
+            Game::FreeDm => "FreeDM".to_string(),
+            Game::ChexQuest => "Chex Quest".to_string(),
+            Game::Heretic => "Heretic".to_string(),
+            Game::Hexen => "Hexen: Beyond Heretic".to_string(),
+        }
+    }
+
+    /// The release date of the game. Freedoom and FreeDM are updated regularly, so they don't have
+    /// a single date.
+    pub fn get_release_date(&self) -> String {
+        match self {
+            Game::DoomShareware => "1993-12-10".to_string(),
+            Game::Doom => "1993-12-10".to_string(),
+            Game::UltimateDoom => "1995-04-30".to_string(),
+            Game::Doom2 => "1994-09-30".to_string(),
+            Game::Plutonia => "1996-06-17".to_string(),
+            Game::Tnt => "1996-06-17".to_string(),
+            Game::Freedoom1 => UNKNOWN_VALUE.to_string(),
+            Game::Freedoom2 => UNKNOWN_VALUE.to_string(),
+            Game::FreeDm => UNKNOWN_VALUE.to_string(),
+            Game::ChexQuest => "1996".to_string(),
+            Game::Heretic => "1994-12-23".to_string(),
+            Game::Hexen => "1995-10-30".to_string(),
+        }
+    }
+
+    pub fn get_author(&self) -> String {
+        match self {
+            Game::DoomShareware => "id Software".to_string(),
+            Game::Doom => "id Software".to_string(),
+            Game::UltimateDoom => "id Software".to_string(),
+            Game::Doom2 => "id Software".to_string(),
+            Game::Plutonia => "Dario Casali & Milo Casali".to_string(),
+            Game::Tnt => "TeamTNT".to_string(),
+            Game::Freedoom1 => "The Freedoom Project".to_string(),
+            Game::Freedoom2 => "The Freedoom Project".to_string(),
+            Game::FreeDm => "The Freedoom Project".to_string(),
+            Game::ChexQuest => "Digital Café".to_string(),
+            Game::Heretic => "Raven Software".to_string(),
+            Game::Hexen => "Raven Software".to_string(),
+        }
+    }
+
+    /// The names of the maps in the original games. Their IWADs don't contain them, because
+    /// they're part of the executable, which shows them on the automap.
+    pub fn get_map_names(&self) -> Option<&'static HashMap<&'static str, &'static str>> {
+        match self {
+            Game::DoomShareware | Game::Doom | Game::UltimateDoom => Some(&DOOM_MAP_INFO),
+            Game::Doom2 => Some(&DOOM2_MAP_INFO),
+            Game::Plutonia => Some(&PLUTONIA_MAP_INFO),
+            Game::Tnt => Some(&TNT_MAP_INFO),
+            _ => None,
+        }
+    }
+}
+
+/// A particular release of a game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IwadRelease {
+    pub game: Game,
+    /// The version isn't known for a release identified by its lumps.
+    pub version: Option<String>,
+}
+
+/// The MD5 hashes of the releases that can be identified exactly. MD5 is enough to tell these
+/// apart, since it's only being used to recognise files, not to guard against tampering.
+///
+/// This isn't every release. Freedoom and FreeDM have a new hash with every version, so those are
+/// identified by their lumps instead, as is anything patched after these.
+const KNOWN_RELEASES: [(&str, Game, &str); 23] = [
+    (
+        "f0cefca49926d00903cf57551d901abe",
+        Game::DoomShareware,
+        "1.9",
+    ),
+    ("1cd63c5ddff1bf8ce844237f580e9cf3", Game::Doom, "1.9"),
+    (
+        "c4fe9fd920207691a9f493668e0a2083",
+        Game::UltimateDoom,
+        "1.9",
+    ),
+    (
+        "fb35c4a5a9fd49ec29ab6e900572c524",
+        Game::UltimateDoom,
+        "BFG Edition",
+    ),
+    ("25e1459ca71d321525f84628f45ca8cd", Game::Doom2, "1.9"),
+    (
+        "c3bea40570c23e511a7ed3ebcd9865f7",
+        Game::Doom2,
+        "BFG Edition",
+    ),
+    ("75c8cf89566741fa9d22447604053bd7", Game::Plutonia, "1.9"),
+    (
+        "3493be7e1e2588bc9c8b31eab2587a04",
+        Game::Plutonia,
+        "Anthology",
+    ),
+    ("4e158d9953c79ccf97bd0663244cc6b6", Game::Tnt, "1.9"),
+    ("1d39e405bf6ee3df69a8d2646c8d5c49", Game::Tnt, "Anthology"),
+    ("25485721882b050afa96a56e5758dd52", Game::ChexQuest, "1.0"),
+    ("66d686b1ed6d35ff103f15dbd30e0341", Game::Heretic, "1.3"),
+    ("abb033caf81e26f12a2103e1fa25453f", Game::Hexen, "1.1"),
+    (
+        "8517c4e8f0eef90b82852667d345eb86",
+        Game::UltimateDoom,
+        "Unity 1.0",
+    ),
+    (
+        "4461d4511386518e784c647e3128e7bc",
+        Game::UltimateDoom,
+        "Unity 1.3",
+    ),
+    (
+        "3b37188f6337f15718b617c16e6e7a9c",
+        Game::UltimateDoom,
+        "KEX",
+    ),
+    ("8ab6d0527a29efdc1ef200e5687b5cae", Game::Doom2, "Unity 1.0"),
+    ("9aa3cbf65b961d0bdac98ec403b832e1", Game::Doom2, "Unity 1.3"),
+    ("64a4c88a871da67492aaa2020a068cd8", Game::Doom2, "KEX"),
+    ("ae76c20366ff685d3bb9fab11b148b84", Game::Plutonia, "Unity"),
+    ("e47cf6d82a0ccedf8c1c16a284bb5937", Game::Plutonia, "KEX"),
+    ("f5528f6fd55cf9629141d79eda169630", Game::Tnt, "Unity"),
+    ("8974e3117ed4a1839c752d5e11ab1b7b", Game::Tnt, "KEX"),
+];
+
+/// Works out which game an IWAD is for, using its contents rather than its file name, so it
+/// doesn't matter if the file has been renamed or is in lowercase.
+///
+/// If the hash doesn't match a known release, the game is identified by lumps that are unique to
+/// it, but the version won't be known.
+pub fn identify_iwad(path: &Path, metadata: &WadMetadata) -> Result<IwadRelease, Report> {
+    if metadata.header.wad_type != "IWAD" {
+        return Err(eyre!("{} is not an IWAD", path.display())
+            .suggestion("Use the `wad import` command to import a PWAD"));
+    }
+    let hash = get_md5_hash(path)?;
+    get_known_release(&hash)
+        .or_else(|| get_game_from_lumps(metadata))
+        .ok_or_else(|| {
+            eyre!("{} is not a recognised IWAD", path.display()).suggestion(
+                "Supported IWADs are DOOM, DOOM II, Final DOOM, Freedoom, FreeDM, Chex Quest, \
+                Heretic and Hexen",
+            )
+        })
+}
+
+pub fn get_md5_hash(path: &Path) -> Result<String, Report> {
+    let mut file = File::open(path)?;
+    let mut hasher = Md5::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn get_known_release(hash: &str) -> Option<IwadRelease> {
+    KNOWN_RELEASES
+        .iter()
+        .find(|(known_hash, _, _)| *known_hash == hash)
+        .map(|(_, game, version)| IwadRelease {
+            game: *game,
+            version: Some(version.to_string()),
+        })
+}
+
+/// Identifies the game using the same lumps the ZDoom family of source ports looks for.
+///
+/// The order matters: the Freedoom IWADs and Chex Quest contain the same map markers as the games
+/// they replace, and Heretic uses the same episode markers as DOOM.
+fn get_game_from_lumps(metadata: &WadMetadata) -> Option<IwadRelease> {
+    let has_lump = |name: &str| metadata.get_lump_index(name).is_some();
+    let game = if has_lump("FREEDM") {
+        Game::FreeDm
+    } else if has_lump("FREEDOOM") && has_lump("E1M1") {
+        Game::Freedoom1
+    } else if has_lump("FREEDOOM") {
+        Game::Freedoom2
+    } else if has_lump("W94_1") {
+        Game::ChexQuest
+    } else if has_lump("WINNOWR") {
+        Game::Hexen
+    } else if has_lump("MUS_E1M1") {
+        Game::Heretic
+    } else if has_lump("CAMO1") {
+        Game::Plutonia
+    } else if has_lump("REDTNT2") {
+        Game::Tnt
+    } else if has_lump("MAP01") {
+        Game::Doom2
+    } else if has_lump("E4M1") {
+        Game::UltimateDoom
+    } else if has_lump("E2M1") {
+        Game::Doom
+    } else if has_lump("E1M1") {
+        Game::DoomShareware
+    } else {
+        return None;
+    };
+    Some(IwadRelease {
+        game,
+        version: None,
+    })
+}
+
+#[cfg(test)]
+mod identify_iwad {
+    use super::{identify_iwad, Game, IwadRelease};
+    use crate::wad::WadMetadata;
+    use test_helpers::wad::write_wad;
+
+    fn identify(wad_type: &str, lumps: &[(&str, &[u8])]) -> color_eyre::Result<IwadRelease> {
+        let wad = assert_fs::NamedTempFile::new("iwad.wad").unwrap();
+        write_wad(wad.path(), wad_type, lumps);
+        let metadata = WadMetadata::from_path(wad.path()).unwrap();
+        identify_iwad(wad.path(), &metadata)
+    }
+
+    #[test]
+    fn should_identify_the_game_from_its_lumps() {
+        let games = [
+            (vec!["MAP01", "FREEDM"], Game::FreeDm),
+            (vec!["E1M1", "FREEDOOM"], Game::Freedoom1),
+            (vec!["MAP01", "FREEDOOM"], Game::Freedoom2),
+            (vec!["E1M1", "E4M1", "W94_1"], Game::ChexQuest),
+            (vec!["MAP01", "WINNOWR"], Game::Hexen),
+            (vec!["E1M1", "E2M1", "MUS_E1M1"], Game::Heretic),
+            (vec!["MAP01", "CAMO1"], Game::Plutonia),
+            (vec!["MAP01", "REDTNT2"], Game::Tnt),
+            (vec!["MAP01"], Game::Doom2),
+            (vec!["E1M1", "E2M1", "E4M1"], Game::UltimateDoom),
+            (vec!["E1M1", "E2M1"], Game::Doom),
+            (vec!["E1M1"], Game::DoomShareware),
+        ];
+        for (lump_names, game) in games {
+            let lumps = lump_names
+                .iter()
+                .map(|x| (*x, b"" as &[u8]))
+                .collect::<Vec<_>>();
+            let release = identify("IWAD", &lumps).unwrap();
+            assert_eq!(release.game, game);
+            assert_eq!(release.version, None);
+        }
+    }
+
+    #[test]
+    fn should_reject_an_unrecognised_iwad() {
+        let result = identify("IWAD", &[("STARTUP", b"")]);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .ends_with("is not a recognised IWAD"));
+    }
+
+    #[test]
+    fn should_reject_a_pwad() {
+        let result = identify("PWAD", &[("MAP01", b"")]);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().ends_with("is not an IWAD"));
+    }
+}
+
+#[cfg(test)]
+mod get_known_release {
+    use super::{get_known_release, Game, IwadRelease};
+
+    #[test]
+    fn should_identify_a_release_from_its_hash() {
+        assert_eq!(
+            get_known_release("c3bea40570c23e511a7ed3ebcd9865f7"),
+            Some(IwadRelease {
+                game: Game::Doom2,
+                version: Some("BFG Edition".to_string())
+            })
+        );
+        assert_eq!(
+            get_known_release("64a4c88a871da67492aaa2020a068cd8"),
+            Some(IwadRelease {
+                game: Game::Doom2,
+                version: Some("KEX".to_string())
+            })
+        );
+        assert_eq!(get_known_release("d41d8cd98f00b204e9800998ecf8427e"), None);
+    }
+}
+
+#[cfg(test)]
+mod get_md5_hash {
+    use super::get_md5_hash;
+
+    #[test]
+    fn should_hash_the_contents_of_the_file() {
+        let file = assert_fs::NamedTempFile::new("hash.txt").unwrap();
+        std::fs::write(file.path(), b"").unwrap();
+        assert_eq!(
+            get_md5_hash(file.path()).unwrap(),
+            "d41d8cd98f00b204e9800998ecf8427e"
+        );
+    }
+}
@@ -1,18 +1,37 @@
+#[cfg(target_family = "unix")]
+mod browser;
+mod command_line;
 mod commands;
+mod complevel;
+mod demo;
+mod favourite;
 mod find;
+mod idgames;
+mod iwad;
+mod package;
 mod profile;
+mod progress;
+mod session;
 mod settings;
 mod source_port;
 mod storage;
 mod wad;
 
+use crate::commands::browse::run_browse_cmd;
+use crate::commands::demo::run_demo_cmd;
+use crate::commands::favourite::run_favourite_cmd;
+use crate::commands::history::run_history_cmd;
+use crate::commands::interactive::run_interactive_cmd;
 use crate::commands::iwad::run_iwad_cmd;
-use crate::commands::play::run_play_cmd;
+use crate::commands::play::{get_session_to_continue, run_play_cmd};
 use crate::commands::profile::run_profile_cmd;
+use crate::commands::session_log::run_log_cmd;
 use crate::commands::source_port::run_source_port_cmd;
 use crate::commands::wad::run_wad_cmd;
 use crate::commands::Command;
 use crate::find::select_map_to_play;
+use crate::idgames::IdgamesClient;
+use crate::session::{discard_play_session, end_play_session, run_session, PlaySessionFilter};
 use crate::settings::get_app_settings_dir_path;
 use crate::source_port::GithubReleaseRepository;
 use crate::storage::AppSettingsRepository;
@@ -39,6 +58,7 @@ fn main() -> Result<(), Report> {
     app_settings_path.push("app_settings.json");
     let app_settings_repository = AppSettingsRepository::new(app_settings_path)?;
     let github_release_repository = GithubReleaseRepository::new();
+    let idgames_client = IdgamesClient::new();
 
     let args = CmdArgs::from_args();
     let result = match args.cmd {
@@ -46,28 +66,71 @@ fn main() -> Result<(), Report> {
             megawad,
             map,
             profile,
+            record,
+            category,
+            continue_last,
+            next,
+            favourites,
+            detach,
         }) => {
-            if let Some(wad_to_play) = megawad {
-                run_play_cmd(wad_to_play, map, profile, app_settings_repository)
+            let (wad_to_play, map_to_play, profile) = if continue_last || next {
+                let (wad_to_play, map_to_play, last_profile) = get_session_to_continue(next)?;
+                (wad_to_play, map_to_play, profile.or(Some(last_profile)))
+            } else if let Some(wad_to_play) = megawad {
+                (wad_to_play, map, profile)
             } else {
-                let selected = select_map_to_play()?;
-                let wad_to_play = selected.0;
-                let map_to_play = Some(selected.1);
-                info!(
-                    "Selected {}: {}",
-                    &wad_to_play,
-                    map_to_play.as_ref().unwrap()
-                );
-                run_play_cmd(wad_to_play, map_to_play, profile, app_settings_repository)
-            }
+                match select_map_to_play(favourites)? {
+                    Some(selected) => {
+                        info!("Selected {}: {}", selected.0, selected.1);
+                        (selected.0, Some(selected.1), profile)
+                    }
+                    None => {
+                        info!("No map was selected");
+                        return Ok(());
+                    }
+                }
+            };
+            run_play_cmd(
+                wad_to_play,
+                map_to_play,
+                profile,
+                record,
+                category,
+                detach,
+                &app_settings_repository,
+            )
         }
         Some(Command::Profile { cmd }) => run_profile_cmd(cmd, app_settings_repository),
         Some(Command::SourcePort { cmd }) => {
             run_source_port_cmd(cmd, &app_settings_repository, &github_release_repository)
         }
-        Some(Command::Wad { cmd }) => run_wad_cmd(cmd),
+        Some(Command::Wad { cmd }) => run_wad_cmd(cmd, &idgames_client),
         Some(Command::Iwad { cmd }) => run_iwad_cmd(cmd),
-        None => panic!("Eventually go into interactive mode"),
+        Some(Command::Fav { cmd }) => run_favourite_cmd(cmd),
+        Some(Command::Demo { cmd }) => run_demo_cmd(cmd, app_settings_repository),
+        Some(Command::Browse) => run_browse_cmd(&app_settings_repository),
+        Some(Command::Log { list }) => run_log_cmd(list),
+        Some(Command::History {
+            megawad,
+            source_port,
+            from,
+            to,
+        }) => run_history_cmd(PlaySessionFilter {
+            wad_id: megawad,
+            source_port,
+            from,
+            to,
+        }),
+        Some(Command::Session {
+            log,
+            play_session,
+            source_port,
+            args,
+        }) => match run_session(&source_port, &args, &log, false) {
+            Ok(status) => end_play_session(&play_session, status),
+            Err(error) => discard_play_session(&play_session, &log).and(Err(error)),
+        },
+        None => run_interactive_cmd(&app_settings_repository),
     };
     result
 }
@@ -0,0 +1,305 @@
+use crate::wad::{MapDefinitions, WadReader, MAP_DEFINITION_LUMP_NAMES};
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// The directory in a package that holds the maps. Each WAD in here contains a single map, which
+/// is named after the file rather than the marker lump inside it.
+const MAPS_DIRECTORY: &str = "maps/";
+
+/// The kinds of file that can be imported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackageType {
+    Wad,
+    Pk3,
+}
+
+impl PackageType {
+    /// Works out the kind of file from its first few bytes, rather than its extension, since it's
+    /// quite common for PK3 files to be renamed to ZIP and vice versa.
+    ///
+    /// Anything that isn't recognised as a package is treated as a WAD, so if it's not valid, the
+    /// error comes from parsing the WAD header.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<PackageType, Report> {
+        let path = path.as_ref();
+        let mut magic = Vec::new();
+        File::open(path)?.take(4).read_to_end(&mut magic)?;
+        match magic.as_slice() {
+            b"PK\x03\x04" => Ok(PackageType::Pk3),
+            [b'7', b'z', 0xBC, 0xAF] => Err(eyre!(
+                "{} is a 7-Zip package, which isn't supported",
+                path.display()
+            )
+            .suggestion("Extract the package and compress it again as a zip file")),
+            _ => Ok(PackageType::Wad),
+        }
+    }
+}
+
+/// A file in a package.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageFile {
+    /// The path of the file within the package, e.g. `maps/MAP01.wad`.
+    pub path: String,
+    pub size: u64,
+    pub compressed_size: u64,
+}
+
+/// A WAD that's embedded in a package.
+pub struct PackageWad {
+    /// The path of the WAD within the package.
+    pub path: String,
+    /// For a WAD in the maps directory, this is the name of the map it contains.
+    pub map_name: Option<String>,
+    pub reader: WadReader<Cursor<Vec<u8>>>,
+}
+
+/// A mod that's distributed as a zip file, which the ZDoom family of source ports calls a PK3.
+///
+/// The directories in the zip take the place of the markers in a WAD, e.g. the maps go in a `maps`
+/// directory, and the lump names are the file names without their extensions.
+pub struct Pk3Package<R: Read + Seek = File> {
+    archive: ZipArchive<R>,
+}
+
+impl Pk3Package<File> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Pk3Package<File>, Report> {
+        Pk3Package::new(File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> Pk3Package<R> {
+    pub fn new(source: R) -> Result<Pk3Package<R>, Report> {
+        Ok(Pk3Package {
+            archive: ZipArchive::new(source)?,
+        })
+    }
+
+    /// Lists the files in the package, in the order they're stored. Directories aren't included.
+    pub fn list_files(&mut self) -> Result<Vec<PackageFile>, Report> {
+        let mut files = Vec::new();
+        for index in 0..self.archive.len() {
+            let file = self.archive.by_index(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            files.push(PackageFile {
+                path: file.name().to_string(),
+                size: file.size(),
+                compressed_size: file.compressed_size(),
+            });
+        }
+        Ok(files)
+    }
+
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, Report> {
+        let mut file = self.archive.by_name(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the map information from the MAPINFO-style files in the root of the package, e.g.
+    /// `MAPINFO.txt` or `ZMAPINFO`.
+    ///
+    /// This doesn't include the lumps in any of the embedded WADs. Those can be read from the WADs
+    /// returned by `get_wads`. The IWAD is the one the package is played with, if it's known.
+    pub fn get_map_definitions(&mut self, iwad: Option<&str>) -> Result<MapDefinitions, Report> {
+        let mut lumps = Vec::new();
+        for file in self.list_files()? {
+            if file.path.contains('/') {
+                continue;
+            }
+            // Only the files that define maps are read, since the root can also hold large files,
+            // like the text file or the music.
+            let lump_name = get_lump_name(&file.path);
+            if !MAP_DEFINITION_LUMP_NAMES.contains(&lump_name.as_str()) {
+                continue;
+            }
+            lumps.push((lump_name, self.read_file(&file.path)?));
+        }
+        Ok(MapDefinitions::from_lumps(&lumps, iwad))
+    }
+
+    /// Opens the WADs in the package. These are the ones in the root, which the source port loads
+    /// as if they'd been supplied on the command line, and the ones in the maps directory.
+    ///
+    /// The WADs are read into memory, since the zip format doesn't allow seeking within a file.
+    pub fn get_wads(&mut self) -> Result<Vec<PackageWad>, Report> {
+        let mut wads = Vec::new();
+        for file in self.list_files()? {
+            let lowercase_path = file.path.to_lowercase();
+            if !lowercase_path.ends_with(".wad") {
+                continue;
+            }
+            let map_name = match lowercase_path.strip_prefix(MAPS_DIRECTORY) {
+                Some(map_path) if !map_path.contains('/') => Some(get_lump_name(&file.path)),
+                Some(_) => continue,
+                None if file.path.contains('/') => continue,
+                None => None,
+            };
+            let data = self.read_file(&file.path)?;
+            let reader = WadReader::new(&file.path, Cursor::new(data))?;
+            wads.push(PackageWad {
+                path: file.path,
+                map_name,
+                reader,
+            });
+        }
+        Ok(wads)
+    }
+}
+
+/// Gets the lump name for a file in a package, which is its uppercase file name, without the
+/// directory or the extension, e.g. `maps/map01.wad` is MAP01.
+fn get_lump_name(path: &str) -> String {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let stem = file_name.split('.').next().unwrap_or(file_name);
+    stem.to_uppercase()
+}
+
+#[cfg(test)]
+mod packagetype {
+    mod from_path {
+        use super::super::PackageType;
+        use test_helpers::wad::{write_pk3, write_wad};
+
+        #[test]
+        fn should_identify_wads_and_pk3s_from_their_contents() {
+            let wad = assert_fs::NamedTempFile::new("test.pk3").unwrap();
+            write_wad(wad.path(), "PWAD", &[("MAP01", b"")]);
+            assert_eq!(
+                PackageType::from_path(wad.path()).unwrap(),
+                PackageType::Wad
+            );
+
+            let pk3 = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_pk3(pk3.path(), &[("MAPINFO.txt", b"")]);
+            assert_eq!(
+                PackageType::from_path(pk3.path()).unwrap(),
+                PackageType::Pk3
+            );
+        }
+
+        #[test]
+        fn should_reject_a_7zip_package() {
+            let pk7 = assert_fs::NamedTempFile::new("test.pk7").unwrap();
+            std::fs::write(pk7.path(), b"7z\xBC\xAF\x27\x1C").unwrap();
+            let result = PackageType::from_path(pk7.path());
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "{} is a 7-Zip package, which isn't supported",
+                    pk7.path().display()
+                )
+            );
+        }
+
+        #[test]
+        fn should_treat_any_other_file_as_a_wad() {
+            let text = assert_fs::NamedTempFile::new("test.txt").unwrap();
+            std::fs::write(text.path(), b"Ti").unwrap();
+            assert_eq!(
+                PackageType::from_path(text.path()).unwrap(),
+                PackageType::Wad
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod pk3package {
+    mod list_files {
+        use super::super::{PackageFile, Pk3Package};
+        use std::io::Cursor;
+        use test_helpers::wad::build_pk3;
+
+        #[test]
+        fn should_list_the_files_but_not_the_directories() {
+            let pk3 = build_pk3(&[("MAPINFO.txt", b"map"), ("maps/MAP01.wad", b"PWAD")]);
+            let mut package = Pk3Package::new(Cursor::new(pk3)).unwrap();
+            let files = package.list_files().unwrap();
+            assert_eq!(
+                files,
+                vec![
+                    PackageFile {
+                        path: "MAPINFO.txt".to_string(),
+                        size: 3,
+                        compressed_size: 3,
+                    },
+                    PackageFile {
+                        path: "maps/MAP01.wad".to_string(),
+                        size: 4,
+                        compressed_size: 4,
+                    },
+                ]
+            );
+        }
+    }
+
+    mod get_map_definitions {
+        use super::super::Pk3Package;
+        use std::io::Cursor;
+        use test_helpers::wad::build_pk3;
+
+        #[test]
+        fn should_read_the_definitions_in_the_root_of_the_package() {
+            let pk3 = build_pk3(&[
+                (
+                    "zmapinfo.txt",
+                    b"map MAP01 \"Gateway\" { next = \"MAP02\" }",
+                ),
+                ("UMAPINFO", b"MAP MAP01 { levelname = \"Portal\" }"),
+                (
+                    "filter/doom.doom2/MAPINFO.txt",
+                    b"map MAP02 \"Filtered\" { }",
+                ),
+                ("README.txt", b"map MAP03 \"Not a map\""),
+            ]);
+            let mut package = Pk3Package::new(Cursor::new(pk3)).unwrap();
+            let definitions = package.get_map_definitions(None).unwrap();
+            assert_eq!(definitions.maps.len(), 1);
+            let map = definitions.get("MAP01").unwrap();
+            assert_eq!(map.name, Some("Portal".to_string()));
+            assert_eq!(map.next, Some("MAP02".to_string()));
+        }
+    }
+
+    mod get_wads {
+        use super::super::Pk3Package;
+        use std::io::Cursor;
+        use test_helpers::wad::{build_pk3, build_wad};
+
+        #[test]
+        fn should_open_the_wads_in_the_root_and_the_maps_directory() {
+            let map = build_wad("PWAD", &[("MAP01", b""), ("THINGS", b"")]);
+            let music = build_wad("PWAD", &[("D_RUNNIN", b"music")]);
+            let pk3 = build_pk3(&[
+                ("maps/start.wad", &map),
+                ("maps/old/MAP02.wad", &map),
+                ("music.WAD", &music),
+                ("sounds/extra.wad", &music),
+                ("textures.txt", b"texture"),
+            ]);
+            let mut package = Pk3Package::new(Cursor::new(pk3)).unwrap();
+            let wads = package.get_wads().unwrap();
+            assert_eq!(wads.len(), 2);
+            assert_eq!(wads[0].path, "maps/start.wad");
+            assert_eq!(wads[0].map_name, Some("START".to_string()));
+            assert_eq!(wads[0].reader.metadata.get_map_lump_names(), vec!["MAP01"]);
+            assert_eq!(wads[1].path, "music.WAD");
+            assert_eq!(wads[1].map_name, None);
+            assert_eq!(wads[1].reader.metadata.directory.len(), 1);
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_invalid_embedded_wad() {
+            let pk3 = build_pk3(&[("maps/MAP01.wad", b"not a wad")]);
+            let mut package = Pk3Package::new(Cursor::new(pk3)).unwrap();
+            assert!(package.get_wads().is_err());
+        }
+    }
+}
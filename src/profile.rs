@@ -12,6 +12,72 @@ pub struct Profile {
     pub fullscreen: bool,
     pub music: bool,
     pub default: bool,
+    /// The options for rendering demos to video with this profile.
+    #[serde(default)]
+    pub render: RenderSettings,
+}
+
+/// The encoder options PrBoom+ and DSDA-Doom use when they render a demo with `-viddump`.
+///
+/// The ports pipe the frames and the sound to the commands as they play the demo, then run the mux
+/// command to put the two together, so any encoder can be used. Anything that isn't set is left to
+/// the port's own defaults, which use x264, oggenc2 and mkvmerge.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RenderSettings {
+    /// The frame rate of the video. The ports default to 60.
+    pub fps: Option<u32>,
+    /// The command the frames are piped to, e.g. `x264 -o output.mp4 ... -`.
+    pub video_command: Option<String>,
+    /// The command the sound is piped to, e.g. `oggenc2 -r -R %s -q 5 - -o output.ogg`.
+    pub sound_command: Option<String>,
+    /// The command that combines the video and sound into the output file.
+    pub mux_command: Option<String>,
+}
+
+impl RenderSettings {
+    pub fn get_fps(&self) -> u32 {
+        self.fps.unwrap_or(60)
+    }
+
+    /// Gets the settings in the form they take in the port's config file.
+    pub fn get_config(&self) -> String {
+        let mut config = String::new();
+        if let Some(fps) = self.fps {
+            config.push_str(&format!("cap_fps {}\n", fps));
+        }
+        let commands = [
+            ("cap_videocommand", &self.video_command),
+            ("cap_soundcommand", &self.sound_command),
+            ("cap_muxcommand", &self.mux_command),
+        ];
+        for (name, command) in commands.iter() {
+            if let Some(command) = command {
+                config.push_str(&format!("{} \"{}\"\n", name, command));
+            }
+        }
+        config
+    }
+
+    /// Puts the settings into the contents of the port's own config file, replacing any values it
+    /// already has for them. Everything else is kept, so the video looks the same as the game
+    /// does when it's played.
+    pub fn merge_config(&self, port_config: &str) -> String {
+        let render_config = self.get_config();
+        let names = render_config
+            .lines()
+            .filter_map(|x| x.split_whitespace().next())
+            .collect::<Vec<&str>>();
+        let mut config = String::new();
+        for line in port_config.lines() {
+            let name = line.split_whitespace().next().unwrap_or_default();
+            if !names.contains(&name) {
+                config.push_str(line);
+                config.push('\n');
+            }
+        }
+        config.push_str(&render_config);
+        config
+    }
 }
 
 impl Profile {
@@ -37,6 +103,7 @@ impl Profile {
             fullscreen,
             music,
             default,
+            render: RenderSettings::default(),
         })
     }
 }
@@ -104,3 +171,47 @@ mod new {
         );
     }
 }
+
+#[cfg(test)]
+mod render_settings {
+    mod get_config {
+        use super::super::RenderSettings;
+
+        #[test]
+        fn should_only_include_the_settings_that_are_set() {
+            let settings = RenderSettings {
+                fps: Some(35),
+                mux_command: Some("mkvmerge -o %f output.mp4 output.ogg".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                settings.get_config(),
+                "cap_fps 35\ncap_muxcommand \"mkvmerge -o %f output.mp4 output.ogg\"\n"
+            );
+            assert_eq!(RenderSettings::default().get_config(), "");
+        }
+    }
+
+    mod merge_config {
+        use super::super::RenderSettings;
+
+        #[test]
+        fn should_replace_the_settings_and_keep_the_rest_of_the_config() {
+            let settings = RenderSettings {
+                fps: Some(35),
+                ..Default::default()
+            };
+            let port_config = "# misc settings\n\
+                               default_compatibility_level 21\n\
+                               cap_fps 60\n\
+                               cap_muxcommand \"mux\"\n";
+            assert_eq!(
+                settings.merge_config(port_config),
+                "# misc settings\n\
+                 default_compatibility_level 21\n\
+                 cap_muxcommand \"mux\"\n\
+                 cap_fps 35\n"
+            );
+        }
+    }
+}
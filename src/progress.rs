@@ -0,0 +1,289 @@
+use crate::settings::get_app_settings_dir_path;
+use crate::source_port::Skill;
+use crate::storage::ObjectRepository;
+use color_eyre::{Report, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+lazy_static! {
+    /// A line in the levelstat file, e.g. `MAP01 - 0:31.97 (0:31)  K: 14/14  I: 4/9  S: 2/5`. The
+    /// time in brackets is the total time for the game so far, which isn't needed.
+    static ref LEVELSTAT_REGEX: Regex = Regex::new(
+        r"^(\S+)\s+-\s+(?:([0-9]+):)?([0-9]+):([0-9]+)\.([0-9]+)\s+\([^)]*\)\s+K:\s*([0-9]+)/([0-9]+)\s+I:\s*([0-9]+)/([0-9]+)\s+S:\s*([0-9]+)/([0-9]+)"
+    )
+    .unwrap();
+}
+
+/// The name of the file the source ports write the level statistics to, in their working
+/// directory.
+pub const LEVELSTAT_FILE_NAME: &str = "levelstat.txt";
+
+/// The statistics for a map that was finished, as the source port wrote them with `-levelstat`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelStat {
+    pub map: String,
+    pub time: Duration,
+    pub kills: u32,
+    pub total_kills: u32,
+    pub items: u32,
+    pub total_items: u32,
+    pub secrets: u32,
+    pub total_secrets: u32,
+}
+
+impl LevelStat {
+    /// Parses the levelstat file, which has a line for each map that was finished, in the order
+    /// they were finished. Any other lines are skipped.
+    pub fn parse(text: &str) -> Vec<LevelStat> {
+        text.lines()
+            .filter_map(|line| {
+                let captures = LEVELSTAT_REGEX.captures(line.trim())?;
+                let number = |i: usize| -> Option<u32> {
+                    captures.get(i).map_or(Some(0), |x| x.as_str().parse().ok())
+                };
+                let hundredths = captures.get(5)?.as_str();
+                // The fraction of a second is usually in hundredths, but it's safer not to
+                // assume so.
+                let fraction = format!("0.{}", hundredths).parse::<f64>().ok()?;
+                let seconds = number(2)? * 3600 + number(3)? * 60 + number(4)?;
+                Some(LevelStat {
+                    map: captures.get(1)?.as_str().to_uppercase(),
+                    time: Duration::from_secs(seconds as u64) + Duration::from_secs_f64(fraction),
+                    kills: number(6)?,
+                    total_kills: number(7)?,
+                    items: number(8)?,
+                    total_items: number(9)?,
+                    secrets: number(10)?,
+                    total_secrets: number(11)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn from_path(path: &Path) -> Result<Vec<LevelStat>, Report> {
+        Ok(LevelStat::parse(&std::fs::read_to_string(path)?))
+    }
+
+    /// The kill count can go over the total when monsters are resurrected or spawned, so anything
+    /// at or over the total counts as all of them.
+    pub fn has_all_kills(&self) -> bool {
+        self.kills >= self.total_kills
+    }
+
+    pub fn has_all_secrets(&self) -> bool {
+        self.secrets >= self.total_secrets
+    }
+}
+
+/// The best results for a map on one skill.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapProgress {
+    pub map: String,
+    pub skill: Skill,
+    pub best_time: Duration,
+    /// Whether the map has been finished with all the monsters killed. This doesn't have to have
+    /// been on the same run as the best time.
+    pub all_kills: bool,
+    /// Whether the map has been finished with all the secrets found.
+    pub all_secrets: bool,
+    /// The number of times the map has been finished on this skill.
+    pub completions: u32,
+}
+
+/// The maps that have been finished in a WAD, which is saved in the settings directory with the
+/// ID of the WAD.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WadProgress {
+    pub wad_id: String,
+    pub maps: Vec<MapProgress>,
+}
+
+impl WadProgress {
+    pub fn new(wad_id: &str) -> WadProgress {
+        WadProgress {
+            wad_id: wad_id.to_string(),
+            maps: Vec::new(),
+        }
+    }
+
+    /// Adds the result of finishing a map, keeping the best of it and any previous results for
+    /// the map on the same skill.
+    pub fn add_result(&mut self, stat: &LevelStat, skill: &Skill) {
+        match self
+            .maps
+            .iter_mut()
+            .find(|x| x.map == stat.map && x.skill == *skill)
+        {
+            Some(progress) => {
+                progress.best_time = progress.best_time.min(stat.time);
+                progress.all_kills |= stat.has_all_kills();
+                progress.all_secrets |= stat.has_all_secrets();
+                progress.completions += 1;
+            }
+            None => self.maps.push(MapProgress {
+                map: stat.map.clone(),
+                skill: skill.clone(),
+                best_time: stat.time,
+                all_kills: stat.has_all_kills(),
+                all_secrets: stat.has_all_secrets(),
+                completions: 1,
+            }),
+        }
+    }
+
+    /// Gets the results for a map, on every skill it's been finished on.
+    pub fn get_map_progress(&self, map: &str) -> Vec<&MapProgress> {
+        self.maps.iter().filter(|x| x.map == map).collect()
+    }
+
+    pub fn is_finished(&self, map: &str) -> bool {
+        self.maps.iter().any(|x| x.map == map)
+    }
+}
+
+pub fn get_progress_repository() -> Result<ObjectRepository, Report> {
+    let mut progress_path = get_app_settings_dir_path()?;
+    progress_path.push("progress");
+    Ok(ObjectRepository::new(&progress_path)?)
+}
+
+/// Gets the progress for a WAD. There won't be anything saved for a WAD none of the maps have
+/// been finished in yet.
+pub fn get_wad_progress(wad_id: &str) -> Result<WadProgress, Report> {
+    let repository = get_progress_repository()?;
+    if !repository.exists(wad_id) {
+        return Ok(WadProgress::new(wad_id));
+    }
+    Ok(repository.get(wad_id)?)
+}
+
+/// Reads the levelstat file the source port wrote and adds the maps that were finished to the
+/// progress for the WAD.
+pub fn record_levelstats(
+    wad_id: &str,
+    skill: &Skill,
+    levelstat_path: &Path,
+) -> Result<Vec<LevelStat>, Report> {
+    let stats = LevelStat::from_path(levelstat_path)?;
+    if stats.is_empty() {
+        return Ok(stats);
+    }
+    let mut progress = get_wad_progress(wad_id)?;
+    for stat in &stats {
+        progress.add_result(stat, skill);
+    }
+    let repository = get_progress_repository()?;
+    if repository.exists(wad_id) {
+        repository.update(wad_id, &progress)?;
+    } else {
+        repository.save(wad_id, &progress)?;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod levelstat {
+    mod parse {
+        use super::super::LevelStat;
+        use std::time::Duration;
+
+        #[test]
+        fn should_parse_the_stats_for_each_map() {
+            let text = "MAP01 - 0:31.97 (0:31)  K: 14/14  I: 4/9  S: 2/5\n\
+                MAP02 - 1:05.40 (1:37)  K: 20/25  I: 9/9  S: 1/1\n";
+            let stats = LevelStat::parse(text);
+            assert_eq!(stats.len(), 2);
+            assert_eq!(
+                stats[0],
+                LevelStat {
+                    map: "MAP01".to_string(),
+                    time: Duration::from_millis(31970),
+                    kills: 14,
+                    total_kills: 14,
+                    items: 4,
+                    total_items: 9,
+                    secrets: 2,
+                    total_secrets: 5,
+                }
+            );
+            assert_eq!(stats[1].map, "MAP02");
+            assert_eq!(stats[1].time, Duration::from_millis(65400));
+        }
+
+        #[test]
+        fn should_parse_a_time_with_hours() {
+            let stats = LevelStat::parse("E1M1 - 1:02:03.50 (1:02:03)  K: 0/4  I: 0/37  S: 0/2");
+            assert_eq!(stats[0].map, "E1M1");
+            assert_eq!(stats[0].time, Duration::from_millis(3723500));
+        }
+
+        #[test]
+        fn should_skip_lines_that_are_not_stats() {
+            let stats = LevelStat::parse("\nsomething else\nMAP01 - 0:31.97 (0:31)  K: 1/1");
+            assert!(stats.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod wadprogress {
+    mod add_result {
+        use super::super::{LevelStat, WadProgress};
+        use crate::source_port::Skill;
+        use std::time::Duration;
+
+        fn get_stat(seconds: u64, kills: u32, secrets: u32) -> LevelStat {
+            LevelStat {
+                map: "MAP01".to_string(),
+                time: Duration::from_secs(seconds),
+                kills,
+                total_kills: 10,
+                items: 0,
+                total_items: 0,
+                secrets,
+                total_secrets: 2,
+            }
+        }
+
+        #[test]
+        fn should_add_a_map_that_has_not_been_finished_before() {
+            let mut progress = WadProgress::new("DOOM2");
+            progress.add_result(&get_stat(30, 10, 1), &Skill::UltraViolence);
+            assert!(progress.is_finished("MAP01"));
+            assert!(!progress.is_finished("MAP02"));
+            let map = progress.get_map_progress("MAP01")[0];
+            assert_eq!(map.best_time, Duration::from_secs(30));
+            assert!(map.all_kills);
+            assert!(!map.all_secrets);
+            assert_eq!(map.completions, 1);
+        }
+
+        #[test]
+        fn should_keep_the_best_results_for_the_same_skill() {
+            let mut progress = WadProgress::new("DOOM2");
+            progress.add_result(&get_stat(30, 10, 1), &Skill::UltraViolence);
+            progress.add_result(&get_stat(45, 5, 2), &Skill::UltraViolence);
+            progress.add_result(&get_stat(25, 5, 0), &Skill::UltraViolence);
+            let maps = progress.get_map_progress("MAP01");
+            assert_eq!(maps.len(), 1);
+            assert_eq!(maps[0].best_time, Duration::from_secs(25));
+            assert!(maps[0].all_kills);
+            assert!(maps[0].all_secrets);
+            assert_eq!(maps[0].completions, 3);
+        }
+
+        #[test]
+        fn should_keep_the_results_for_each_skill_separately() {
+            let mut progress = WadProgress::new("DOOM2");
+            progress.add_result(&get_stat(30, 10, 2), &Skill::UltraViolence);
+            progress.add_result(&get_stat(60, 5, 0), &Skill::Nightmare);
+            let maps = progress.get_map_progress("MAP01");
+            assert_eq!(maps.len(), 2);
+            assert_eq!(maps[1].skill, Skill::Nightmare);
+            assert!(!maps[1].all_kills);
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use crate::demo::format_duration;
+use crate::profile::Profile;
+use crate::progress::record_levelstats;
+use crate::settings::get_app_settings_dir_path;
+use crate::source_port::{InstalledSourcePort, Skill, SourcePort};
+use crate::storage::ObjectRepository;
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use color_eyre::{eyre::eyre, Help, Report, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+
+/// Describes the exit status of a game that hasn't exited, which is either still running in the
+/// background, or its session was stopped before the game exited.
+pub const NO_EXIT_STATUS_DESCRIPTION: &str = "None (still running, or the session was stopped)";
+const LOG_EXTENSION: &str = "log";
+const EXIT_STATUS_PREFIX: &str = "Exit status: ";
+/// The lines at the start of the log, with the command line and start time, that come before the
+/// output.
+const LOG_HEADER_LINES: usize = 4;
+/// The number of lines from the end of the log that are shown when a source port fails. A crash
+/// usually only leaves a few lines behind, but the port may have written something useful before
+/// that.
+const CRASH_OUTPUT_LINES: usize = 20;
+
+/// A record of a game launched with the `play` command: what was played, what it was played with,
+/// and for how long.
+///
+/// It's saved when the game is launched and updated when the game exits. The ID is the name of the
+/// session log, without the extension.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaySession {
+    pub id: String,
+    pub wad_id: String,
+    /// The map the game was started on. This isn't set if the game was started from the title
+    /// screen.
+    pub map: Option<String>,
+    pub profile: String,
+    pub source_port: SourcePort,
+    pub source_port_version: String,
+    /// The skill from the profile. This wasn't recorded by older versions.
+    #[serde(default)]
+    pub skill: Option<Skill>,
+    /// Where the source port writes the statistics for the maps that are finished, if it can.
+    #[serde(default)]
+    pub levelstat_path: Option<PathBuf>,
+    pub started: DateTime<Utc>,
+    pub ended: Option<DateTime<Utc>>,
+    /// This won't be set if the source port was killed by a signal.
+    pub exit_code: Option<i32>,
+}
+
+impl PlaySession {
+    pub fn new(
+        log_path: &Path,
+        wad_id: &str,
+        map: Option<String>,
+        profile: &Profile,
+        source_port: &InstalledSourcePort,
+        levelstat_path: Option<PathBuf>,
+    ) -> PlaySession {
+        PlaySession {
+            id: get_session_id(log_path),
+            wad_id: wad_id.to_string(),
+            map,
+            profile: profile.name.clone(),
+            source_port: source_port.name,
+            source_port_version: source_port.version.clone(),
+            skill: Some(profile.skill.clone()),
+            levelstat_path,
+            started: Utc::now(),
+            ended: None,
+            exit_code: None,
+        }
+    }
+
+    /// Gets how long the game was played for, if it's exited.
+    pub fn get_duration(&self) -> Option<Duration> {
+        self.ended.map(|ended| ended - self.started)
+    }
+}
+
+/// The filters for the sessions shown in the history. Dates are in local time, since that's the
+/// day the player would think of a session as being played on.
+#[derive(Debug, Default)]
+pub struct PlaySessionFilter {
+    pub wad_id: Option<String>,
+    pub source_port: Option<SourcePort>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl PlaySessionFilter {
+    /// Checks if a session matches all the filters that have been set. The date range includes
+    /// both the `from` and `to` dates.
+    pub fn matches(&self, session: &PlaySession) -> bool {
+        let date = session.started.with_timezone(&Local).naive_local().date();
+        self.wad_id
+            .as_ref()
+            .is_none_or(|x| x.eq_ignore_ascii_case(&session.wad_id))
+            && self.source_port.is_none_or(|x| x == session.source_port)
+            && self.from.is_none_or(|x| date >= x)
+            && self.to.is_none_or(|x| date <= x)
+    }
+}
+
+pub fn get_play_session_repository() -> Result<ObjectRepository, Report> {
+    let mut sessions_path = get_app_settings_dir_path()?;
+    sessions_path.push("sessions");
+    Ok(ObjectRepository::new(&sessions_path)?)
+}
+
+/// Records the end time and exit code of a session once the game has exited, along with the
+/// maps that were finished, if the source port wrote them to its levelstat file.
+pub fn end_play_session(id: &str, status: ExitStatus) -> Result<(), Report> {
+    let repository = get_play_session_repository()?;
+    let mut session: PlaySession = repository.get(id)?;
+    session.ended = Some(Utc::now());
+    session.exit_code = status.code();
+    repository.update(id, &session)?;
+
+    if let (Some(path), Some(skill)) = (&session.levelstat_path, &session.skill) {
+        if path.is_file() {
+            for stat in record_levelstats(&session.wad_id, skill, path)? {
+                info!(
+                    "Finished {} in {} with {}/{} kills and {}/{} secrets",
+                    stat.map,
+                    format_duration(stat.time),
+                    stat.kills,
+                    stat.total_kills,
+                    stat.secrets,
+                    stat.total_secrets
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the session for a game that couldn't be launched, along with its log, so it isn't left
+/// in the history as a game that never exited.
+pub fn discard_play_session(id: &str, log_path: &Path) -> Result<(), Report> {
+    get_play_session_repository()?.delete(id)?;
+    if log_path.exists() {
+        std::fs::remove_file(log_path)?;
+    }
+    Ok(())
+}
+
+/// Gets the path for a new session log. The logs are named after the time and the WAD being
+/// played, so they sort in the order the sessions were started.
+pub fn get_session_log_path(wad_id: &str) -> Result<PathBuf, Report> {
+    let logs_path = get_logs_path()?;
+    std::fs::create_dir_all(&logs_path)?;
+    Ok(logs_path.join(format!(
+        "{}-{}.{}",
+        Utc::now().format("%Y%m%d-%H%M%S-%3f"),
+        wad_id.to_lowercase(),
+        LOG_EXTENSION
+    )))
+}
+
+/// Gets all the session logs, oldest first.
+pub fn get_session_logs() -> Result<Vec<PathBuf>, Report> {
+    let logs_path = get_logs_path()?;
+    if !logs_path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut logs = Vec::new();
+    for dir_entry in std::fs::read_dir(logs_path)? {
+        let path = dir_entry?.path();
+        if path.extension().is_some_and(|x| x == LOG_EXTENSION) {
+            logs.push(path);
+        }
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+/// Reads the exit status from the end of a session log, if it's been written.
+pub fn read_exit_status(log_path: &Path) -> Result<Option<String>, Report> {
+    let log = std::fs::read_to_string(log_path)?;
+    Ok(log
+        .lines()
+        .rev()
+        .find_map(|x| x.strip_prefix(EXIT_STATUS_PREFIX))
+        .map(String::from))
+}
+
+/// Runs the source port from its own directory and waits for it to exit.
+///
+/// Everything the port writes to stdout and stderr goes to the session log as it's written, along
+/// with the command line and, at the end, the exit status. If `echo` is set, the output is also
+/// written to the terminal.
+pub fn run_session(
+    source_port_path: &Path,
+    args: &[String],
+    log_path: &Path,
+    echo: bool,
+) -> Result<ExitStatus, Report> {
+    let mut log = File::create(log_path)?;
+    writeln!(log, "Source port: {}", source_port_path.display())?;
+    writeln!(log, "Arguments: {}", args.join(" "))?;
+    writeln!(log, "Started: {}", Utc::now().to_rfc3339())?;
+    writeln!(log)?;
+
+    let mut source_port_dir = source_port_path.to_path_buf();
+    source_port_dir.pop();
+    let reader = duct::cmd(source_port_path, args)
+        .dir(source_port_dir)
+        .stderr_to_stdout()
+        .unchecked()
+        .reader()?;
+    let mut output = BufReader::new(&reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if output.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if echo {
+            std::io::stdout().write_all(&line)?;
+        }
+        log.write_all(&line)?;
+    }
+    let status = reader
+        .try_wait()?
+        .ok_or_else(|| eyre!("The source port closed its output without exiting"))?
+        .status;
+    writeln!(log)?;
+    writeln!(
+        log,
+        "{}{}",
+        EXIT_STATUS_PREFIX,
+        describe_exit_status(status)
+    )?;
+    Ok(status)
+}
+
+/// Runs the session from another TDL process, so the terminal can be used while the game is
+/// running. The other process writes the log, including the exit status once the game exits, and
+/// records the end of the play session.
+pub fn spawn_detached_session(
+    source_port_path: &Path,
+    args: &[String],
+    log_path: &Path,
+    play_session_id: &str,
+) -> Result<(), Report> {
+    std::process::Command::new(std::env::current_exe()?)
+        .arg("session")
+        .arg("--log")
+        .arg(log_path)
+        .arg("--play-session")
+        .arg(play_session_id)
+        .arg(source_port_path)
+        .arg("--")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Reports how the session ended. If the source port failed, the last of its output is shown,
+/// since that's usually where the reason for a crash is.
+pub fn check_exit_status(
+    source_port: SourcePort,
+    status: ExitStatus,
+    log_path: &Path,
+) -> Result<(), Report> {
+    if status.success() {
+        info!("The session was logged to {}", log_path.display());
+        return Ok(());
+    }
+    let log = std::fs::read_to_string(log_path)?;
+    let output = log
+        .lines()
+        .skip(LOG_HEADER_LINES)
+        .filter(|x| !x.trim().is_empty() && !x.starts_with(EXIT_STATUS_PREFIX))
+        .collect::<Vec<&str>>();
+    show_last_output(source_port, &output);
+    Err(eyre!(
+        "{} exited with status {}",
+        source_port,
+        describe_exit_status(status)
+    )
+    .suggestion(format!(
+        "The full output is in the session log at {}",
+        log_path.display()
+    )))
+}
+
+/// Shows the last lines of output from a source port that failed.
+pub fn show_last_output(source_port: SourcePort, output: &[&str]) {
+    let output = &output[output.len().saturating_sub(CRASH_OUTPUT_LINES)..];
+    if !output.is_empty() {
+        warn!("The last output from {} was:", source_port);
+        for line in output {
+            warn!("{}", line);
+        }
+    }
+}
+
+/// Gives the exit code, or if the process was killed by a signal, which signal it was.
+pub fn describe_exit_status(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => code.to_string(),
+        None => status.to_string(),
+    }
+}
+
+fn get_session_id(log_path: &Path) -> String {
+    log_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn get_logs_path() -> Result<PathBuf, Report> {
+    let mut logs_path = get_app_settings_dir_path()?;
+    logs_path.push("logs");
+    Ok(logs_path)
+}
+
+#[cfg(test)]
+mod read_exit_status {
+    use super::read_exit_status;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn should_read_the_exit_status_from_the_end_of_the_log() {
+        let log = assert_fs::NamedTempFile::new("session.log").unwrap();
+        log.write_str("Source port: dsda-doom\nArguments: -iwad DOOM2.WAD\nStarted: now\n\nExit status: 1 is not this line\n\nExit status: 0\n")
+            .unwrap();
+        assert_eq!(read_exit_status(log.path()).unwrap(), Some("0".to_string()));
+    }
+
+    #[test]
+    fn should_return_none_for_a_session_that_has_not_finished() {
+        let log = assert_fs::NamedTempFile::new("session.log").unwrap();
+        log.write_str("Source port: dsda-doom\nArguments: -iwad DOOM2.WAD\nStarted: now\n\n")
+            .unwrap();
+        assert_eq!(read_exit_status(log.path()).unwrap(), None);
+    }
+}
+
+#[cfg(test)]
+mod play_session_filter {
+    mod matches {
+        use super::super::{PlaySession, PlaySessionFilter};
+        use crate::source_port::SourcePort;
+        use chrono::{Local, NaiveDate, TimeZone, Utc};
+
+        fn get_session(wad_id: &str, source_port: SourcePort, day: u32) -> PlaySession {
+            PlaySession {
+                id: "20210301-200000-000-doom2".to_string(),
+                wad_id: wad_id.to_string(),
+                map: None,
+                profile: "default".to_string(),
+                source_port,
+                source_port_version: "0.24.3".to_string(),
+                skill: None,
+                levelstat_path: None,
+                started: Local
+                    .ymd(2021, 3, day)
+                    .and_hms(20, 0, 0)
+                    .with_timezone(&Utc),
+                ended: None,
+                exit_code: None,
+            }
+        }
+
+        #[test]
+        fn should_match_every_session_without_any_filters() {
+            let filter = PlaySessionFilter::default();
+            assert!(filter.matches(&get_session("DOOM2", SourcePort::Dsda, 1)));
+        }
+
+        #[test]
+        fn should_match_the_wad_regardless_of_case() {
+            let filter = PlaySessionFilter {
+                wad_id: Some("doom2".to_string()),
+                ..Default::default()
+            };
+            assert!(filter.matches(&get_session("DOOM2", SourcePort::Dsda, 1)));
+            assert!(!filter.matches(&get_session("TNT", SourcePort::Dsda, 1)));
+        }
+
+        #[test]
+        fn should_match_the_source_port() {
+            let filter = PlaySessionFilter {
+                source_port: Some(SourcePort::Woof),
+                ..Default::default()
+            };
+            assert!(filter.matches(&get_session("DOOM2", SourcePort::Woof, 1)));
+            assert!(!filter.matches(&get_session("DOOM2", SourcePort::Dsda, 1)));
+        }
+
+        #[test]
+        fn should_match_dates_in_the_range_including_the_first_and_last_day() {
+            let filter = PlaySessionFilter {
+                from: Some(NaiveDate::from_ymd(2021, 3, 2)),
+                to: Some(NaiveDate::from_ymd(2021, 3, 4)),
+                ..Default::default()
+            };
+            assert!(!filter.matches(&get_session("DOOM2", SourcePort::Dsda, 1)));
+            assert!(filter.matches(&get_session("DOOM2", SourcePort::Dsda, 2)));
+            assert!(filter.matches(&get_session("DOOM2", SourcePort::Dsda, 4)));
+            assert!(!filter.matches(&get_session("DOOM2", SourcePort::Dsda, 5)));
+        }
+    }
+}
@@ -11,6 +11,8 @@ pub struct UserSettings {
     pub iwads_path: PathBuf,
     pub wads_path: PathBuf,
     pub source_ports_path: PathBuf,
+    /// Demos are kept in a directory for each WAD.
+    pub demos_path: PathBuf,
 }
 
 impl UserSettings {
@@ -29,10 +31,15 @@ impl UserSettings {
         if !sp_pb.exists() {
             std::fs::create_dir_all(sp_pb.as_path())?;
         }
+        let demos_pb = doom_home_path.join("demos");
+        if !demos_pb.exists() {
+            std::fs::create_dir_all(demos_pb.as_path())?;
+        }
         Ok(UserSettings {
             iwads_path: iwad_pb,
             wads_path: wad_pb,
             source_ports_path: sp_pb,
+            demos_path: demos_pb,
         })
     }
 }
@@ -95,16 +102,15 @@ pub fn get_app_settings_dir_path() -> Result<PathBuf, Report> {
 ///
 /// There will be 3 different mechanisms for getting the user settings:
 /// * If the `TDL_DOOM_HOME_PATH` is set, the various paths available in the user settings will be
-/// set automatically, relative to that directory.
+///   set automatically, relative to that directory.
 /// * Look for environment variables for each of the settings.
 /// * Read them from a file located at `TDL_SETTINGS_PATH/user_settings.json`
 pub fn get_user_settings() -> Result<UserSettings, Report> {
-    let settings;
-    if std::env::var("TDL_DOOM_HOME_PATH").is_ok() {
-        settings = UserSettings::set_from_doom_home()?;
+    let settings = if std::env::var("TDL_DOOM_HOME_PATH").is_ok() {
+        UserSettings::set_from_doom_home()?
     } else {
         panic!("Alternative method for retrieving user settings is not yet supported");
-    }
+    };
     Ok(settings)
 }
 
@@ -135,11 +141,13 @@ mod user_settings {
             let iwads_dir = doom_home.child("iwads");
             let wads_dir = doom_home.child("wads");
             let source_ports_dir = doom_home.child("source-ports");
+            let demos_dir = doom_home.child("demos");
             set_var("TDL_DOOM_HOME_PATH", doom_home.path().to_str().unwrap());
             let _ = UserSettings::set_from_doom_home().unwrap();
             iwads_dir.assert(predicate::path::is_dir());
             wads_dir.assert(predicate::path::is_dir());
             source_ports_dir.assert(predicate::path::is_dir());
+            demos_dir.assert(predicate::path::is_dir());
         }
     }
 }
@@ -216,5 +224,9 @@ mod get_user_settings {
             user_settings.source_ports_path.as_path(),
             source_ports_dir.path()
         );
+        assert_eq!(
+            user_settings.demos_path.as_path(),
+            doom_home_dir.child("demos").path()
+        );
     }
 }
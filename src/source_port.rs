@@ -1,4 +1,6 @@
+use crate::demo::{DemoFormat, DemoHeader};
 use crate::storage::{ObjectRepository, StorageError};
+use crate::wad::MapFormat;
 use chrono::DateTime;
 #[cfg(not(test))]
 use chrono::Utc;
@@ -62,9 +64,9 @@ lazy_static! {
     };
 }
 
-///
-/// Public members
-///
+//
+// Public members
+//
 
 /// I originally had all these entries suffixed with 'Error', but clippy advises against doing
 /// this: https://rust-lang.github.io/rust-clippy/master/index.html#enum_variant_names
@@ -100,7 +102,7 @@ pub enum SourcePortError {
     Fs(#[from] fs_extra::error::Error),
 }
 
-#[derive(Clone, Debug, StructOpt, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, StructOpt, Serialize, Deserialize)]
 pub enum Skill {
     TooYoungToDie,
     HeyNotTooRough,
@@ -124,6 +126,18 @@ impl FromStr for Skill {
     }
 }
 
+impl std::fmt::Display for Skill {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooYoungToDie => write!(f, "I'm too young to die"),
+            Self::HeyNotTooRough => write!(f, "Hey, not too rough"),
+            Self::HurtMePlenty => write!(f, "Hurt me plenty"),
+            Self::UltraViolence => write!(f, "Ultra-Violence"),
+            Self::Nightmare => write!(f, "Nightmare!"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, EnumIter, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum SourcePort {
     Chocolate,
@@ -232,6 +246,126 @@ impl SourcePort {
             Self::Zandronum => false,
         }
     }
+
+    /// Determines whether the source port can load PK3 packages with `-file`. The others can only
+    /// load WADs.
+    pub fn supports_pk3(&self) -> bool {
+        match self {
+            Self::Chocolate => false,
+            Self::Crispy => false,
+            Self::DoomRetro => false,
+            Self::Dsda => false,
+            Self::EternityEngine => true,
+            Self::GzDoom => true,
+            Self::LzDoom => true,
+            Self::Odamex => false,
+            Self::PrBoomPlus => false,
+            Self::Rude => false,
+            Self::Woof => false,
+            Self::Zandronum => true,
+        }
+    }
+
+    /// Determines whether the source port can record demos with `-record`. Doom Retro can play
+    /// demos back, but it can't record them.
+    pub fn supports_demo_recording(&self) -> bool {
+        match self {
+            Self::Chocolate => true,
+            Self::Crispy => true,
+            Self::DoomRetro => false,
+            Self::Dsda => true,
+            Self::EternityEngine => true,
+            Self::GzDoom => true,
+            Self::LzDoom => true,
+            Self::Odamex => true,
+            Self::PrBoomPlus => true,
+            Self::Rude => true,
+            Self::Woof => true,
+            Self::Zandronum => true,
+        }
+    }
+
+    /// Determines whether the source port can play maps in the given format.
+    ///
+    /// Every port can play Doom format maps. The ZDoom family supports the Hexen format and any
+    /// UDMF namespace it doesn't recognise is assumed to be one of its own, since that's where most
+    /// of them come from. Eternity and DSDA-Doom each have their own namespace.
+    pub fn supports_map_format(&self, format: &MapFormat) -> bool {
+        match format {
+            MapFormat::Doom => true,
+            MapFormat::Hexen => matches!(
+                self,
+                Self::Dsda | Self::EternityEngine | Self::GzDoom | Self::LzDoom | Self::Zandronum
+            ),
+            MapFormat::Udmf(namespace) => match namespace.as_str() {
+                "eternity" => matches!(self, Self::EternityEngine),
+                "dsda" => matches!(self, Self::Dsda | Self::GzDoom),
+                _ => matches!(self, Self::GzDoom | Self::LzDoom | Self::Zandronum),
+            },
+        }
+    }
+
+    /// Determines whether the source port can render a demo to a video with `-viddump`. This came
+    /// from PrBoom+, so it's only PrBoom+ and DSDA-Doom that have it.
+    pub fn supports_video_dump(&self) -> bool {
+        matches!(self, Self::Dsda | Self::PrBoomPlus)
+    }
+
+    /// Finds the config file for one of the ports that can render a demo, if it's been run before
+    /// and written one.
+    ///
+    /// On Windows, the config is next to the executable. Elsewhere it's in a directory in the
+    /// home directory, although the newer versions of DSDA-Doom use the data directory instead.
+    pub fn get_config_path(&self, source_port_dir: &Path) -> Option<PathBuf> {
+        let name = match self {
+            Self::Dsda => "dsda-doom",
+            Self::PrBoomPlus => "prboom-plus",
+            _ => return None,
+        };
+        let file_name = format!("{}.cfg", name);
+        let mut paths = vec![source_port_dir.join(&file_name)];
+        if let Some(home_dir) = dirs::home_dir() {
+            paths.push(home_dir.join(format!(".{}", name)).join(&file_name));
+        }
+        if let Some(data_dir) = dirs::data_dir() {
+            paths.push(data_dir.join(name).join(&file_name));
+        }
+        paths.into_iter().find(|x| x.is_file())
+    }
+
+    /// Determines whether the source port can write the statistics for each map that's finished
+    /// with `-levelstat`. This is another one from PrBoom+.
+    pub fn supports_levelstat(&self) -> bool {
+        matches!(self, Self::Dsda | Self::PrBoomPlus)
+    }
+
+    /// Determines whether the source port can play back a demo.
+    ///
+    /// A demo only stays in sync with the engine it was recorded with, so this comes down to which
+    /// versions each port emulates. PrBoom+ and DSDA-Doom can play every version from vanilla up
+    /// to their own, but PrBoom+ stops short of MBF21. The vanilla ports can only play 1.9 demos,
+    /// and Woof! only has the complevels for 1.9, Boom 2.02, MBF and MBF21. The ZDoom family and
+    /// Eternity have their own demo formats.
+    pub fn supports_demo(&self, header: &DemoHeader) -> bool {
+        let supports_format = match header.format {
+            DemoFormat::Vanilla => match self {
+                Self::Dsda | Self::PrBoomPlus => true,
+                Self::Chocolate | Self::Crispy | Self::DoomRetro | Self::Woof => {
+                    matches!(header.version, Some(109..=111))
+                }
+                _ => false,
+            },
+            DemoFormat::Boom | DemoFormat::Mbf | DemoFormat::PrBoom => match self {
+                Self::Dsda | Self::PrBoomPlus => true,
+                Self::Woof => header.complevel == 9 || header.complevel == 11,
+                _ => false,
+            },
+            DemoFormat::Mbf21 => matches!(self, Self::Dsda | Self::Woof),
+        };
+        // Only the ports that support UMAPINFO write the extended header.
+        supports_format
+            && (header.map_lump_name.is_none() || matches!(self, Self::Dsda | Self::PrBoomPlus))
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -283,6 +417,8 @@ pub trait ReleaseRepository {
         &self,
         source_port: SourcePort,
     ) -> Result<SourcePortRelease, SourcePortError>;
+    // This will be used when installing a specific version of a source port is supported.
+    #[allow(dead_code)]
     fn get_releases(
         &self,
         source_port: SourcePort,
@@ -326,7 +462,7 @@ impl ReleaseRepository for GithubReleaseRepository {
 
     fn get_releases(
         &self,
-        source_port: SourcePort,
+        _source_port: SourcePort,
     ) -> Result<Vec<SourcePortRelease>, SourcePortError> {
         Ok(Vec::new())
     }
@@ -457,7 +593,7 @@ fn get_latest_source_port_release_from_response(
 
     let mut release_assets = Vec::new();
     let assets = response["assets"].as_array().unwrap();
-    for platform in vec!["windows", "linux", "macos"].iter() {
+    for platform in ["windows", "linux", "macos"].iter() {
         if let Some(asset_regex) =
             RELEASE_ASSET_MAP.get(format!("{}/{}/{}", owner, repository, *platform).as_str())
         {
@@ -497,8 +633,8 @@ fn move_source_port_files_out_of_archive_directory(
 ) -> Result<(), SourcePortError> {
     let mut options = fs_extra::dir::CopyOptions::new();
     options.content_only = true;
-    let archive_root_dir = std::fs::read_dir(&destination_dir_path)?.next().unwrap()?;
-    fs_extra::dir::copy(archive_root_dir.path(), &destination_dir_path, &options)?;
+    let archive_root_dir = std::fs::read_dir(destination_dir_path)?.next().unwrap()?;
+    fs_extra::dir::copy(archive_root_dir.path(), destination_dir_path, &options)?;
     fs_extra::dir::remove(archive_root_dir.path())?;
     Ok(())
 }
@@ -547,7 +683,7 @@ fn download_release_archive(url: &str, dest_path: &Path) -> Result<(), SourcePor
 
             let mut src = BufReader::new(response);
             let mut downloaded = 0;
-            let mut dest = std::fs::File::create(&dest_path)?;
+            let mut dest = std::fs::File::create(dest_path)?;
             loop {
                 let n = {
                     let buf = src.fill_buf()?;
@@ -586,7 +722,7 @@ fn extract_archive(
     Ok(())
 }
 
-fn get_current_tdl_version() -> String {
+pub fn get_current_tdl_version() -> String {
     format!(
         "{}.{}.{}",
         pkg_version::pkg_version_major!(),
@@ -614,7 +750,7 @@ pub mod test {
     impl ReleaseRepository for FakeReleaseRepository {
         fn get_releases(
             &self,
-            source_port: SourcePort,
+            _source_port: SourcePort,
         ) -> Result<Vec<SourcePortRelease>, SourcePortError> {
             Ok(Vec::new())
         }
@@ -1252,3 +1388,138 @@ mod github_release_repository {
         }
     }
 }
+
+#[cfg(test)]
+mod sourceport {
+    mod supports_map_format {
+        use super::super::SourcePort;
+        use crate::wad::MapFormat;
+        use strum::IntoEnumIterator;
+
+        #[test]
+        fn should_support_doom_format_maps_for_every_port() {
+            assert!(SourcePort::iter().all(|x| x.supports_map_format(&MapFormat::Doom)));
+        }
+
+        #[test]
+        fn should_support_hexen_format_maps_for_the_advanced_ports() {
+            assert!(SourcePort::GzDoom.supports_map_format(&MapFormat::Hexen));
+            assert!(SourcePort::EternityEngine.supports_map_format(&MapFormat::Hexen));
+            assert!(SourcePort::Dsda.supports_map_format(&MapFormat::Hexen));
+            assert!(!SourcePort::Chocolate.supports_map_format(&MapFormat::Hexen));
+            assert!(!SourcePort::PrBoomPlus.supports_map_format(&MapFormat::Hexen));
+        }
+
+        #[test]
+        fn should_support_udmf_maps_based_on_the_namespace() {
+            let zdoom = MapFormat::Udmf("zdoom".to_string());
+            let eternity = MapFormat::Udmf("eternity".to_string());
+            let dsda = MapFormat::Udmf("dsda".to_string());
+            assert!(SourcePort::GzDoom.supports_map_format(&zdoom));
+            assert!(SourcePort::Zandronum.supports_map_format(&zdoom));
+            assert!(!SourcePort::EternityEngine.supports_map_format(&zdoom));
+            assert!(SourcePort::EternityEngine.supports_map_format(&eternity));
+            assert!(!SourcePort::GzDoom.supports_map_format(&eternity));
+            assert!(SourcePort::Dsda.supports_map_format(&dsda));
+            assert!(!SourcePort::Woof.supports_map_format(&dsda));
+        }
+    }
+
+    mod get_config_path {
+        use super::super::SourcePort;
+        use assert_fs::prelude::*;
+
+        #[test]
+        fn should_find_the_config_next_to_the_executable() {
+            let source_port_dir = assert_fs::TempDir::new().unwrap();
+            source_port_dir
+                .child("dsda-doom.cfg")
+                .write_str("cap_fps 60\n")
+                .unwrap();
+            assert_eq!(
+                SourcePort::Dsda.get_config_path(source_port_dir.path()),
+                Some(source_port_dir.child("dsda-doom.cfg").path().to_path_buf())
+            );
+            assert_eq!(
+                SourcePort::GzDoom.get_config_path(source_port_dir.path()),
+                None
+            );
+        }
+    }
+
+    mod supports_demo {
+        use super::super::SourcePort;
+        use crate::demo::{DemoFormat, DemoHeader};
+        use crate::source_port::Skill;
+
+        fn get_header(format: DemoFormat, version: u8, complevel: u32) -> DemoHeader {
+            DemoHeader {
+                format,
+                version: Some(version),
+                complevel,
+                skill: Skill::UltraViolence,
+                episode: 1,
+                map: 1,
+                map_lump_name: None,
+                deathmatch: 0,
+                respawn: false,
+                fast: false,
+                no_monsters: false,
+                console_player: 0,
+                players: vec![0],
+                options: Vec::new(),
+                longtics: false,
+                tics: 35,
+            }
+        }
+
+        #[test]
+        fn should_support_vanilla_demos_for_the_vanilla_and_boom_ports() {
+            let header = get_header(DemoFormat::Vanilla, 109, 2);
+            assert!(SourcePort::Chocolate.supports_demo(&header));
+            assert!(SourcePort::Woof.supports_demo(&header));
+            assert!(SourcePort::PrBoomPlus.supports_demo(&header));
+            assert!(!SourcePort::GzDoom.supports_demo(&header));
+
+            let header = get_header(DemoFormat::Vanilla, 106, 1);
+            assert!(!SourcePort::Chocolate.supports_demo(&header));
+            assert!(SourcePort::Dsda.supports_demo(&header));
+        }
+
+        #[test]
+        fn should_support_boom_demos_for_the_complevels_each_port_has() {
+            let header = get_header(DemoFormat::PrBoom, 214, 17);
+            assert!(SourcePort::PrBoomPlus.supports_demo(&header));
+            assert!(!SourcePort::Woof.supports_demo(&header));
+            assert!(!SourcePort::Crispy.supports_demo(&header));
+
+            let header = get_header(DemoFormat::Mbf, 203, 11);
+            assert!(SourcePort::Woof.supports_demo(&header));
+        }
+
+        #[test]
+        fn should_support_mbf21_demos_for_the_ports_that_have_it() {
+            let header = get_header(DemoFormat::Mbf21, 221, 21);
+            assert!(SourcePort::Dsda.supports_demo(&header));
+            assert!(SourcePort::Woof.supports_demo(&header));
+            assert!(!SourcePort::PrBoomPlus.supports_demo(&header));
+        }
+
+        #[test]
+        fn should_support_umapinfo_demos_for_the_ports_that_write_them() {
+            let mut header = get_header(DemoFormat::PrBoom, 214, 17);
+            header.map_lump_name = Some("E1M10".to_string());
+            assert!(SourcePort::PrBoomPlus.supports_demo(&header));
+            assert!(!SourcePort::Woof.supports_demo(&header));
+        }
+
+        #[test]
+        fn should_only_support_umapinfo_demos_in_a_format_the_port_has() {
+            let mut header = get_header(DemoFormat::Mbf21, 221, 21);
+            header.map_lump_name = Some("E1M10".to_string());
+            assert!(SourcePort::Dsda.supports_demo(&header));
+            assert!(!SourcePort::PrBoomPlus.supports_demo(&header));
+            assert!(!SourcePort::Woof.supports_demo(&header));
+        }
+    }
+}
@@ -79,6 +79,24 @@ impl ObjectRepository {
         Ok(serde_json::from_str(&serialized)?)
     }
 
+    /// Gets every object that's been saved in the `object_path` directory.
+    ///
+    /// # Errors
+    ///
+    /// The same as `get`, for any of the objects.
+    pub fn get_all<T: DeserializeOwned>(&self) -> Result<Vec<T>, StorageError> {
+        let mut objects = Vec::new();
+        for dir_entry in std::fs::read_dir(&self.object_path)? {
+            let path = dir_entry?.path();
+            if path.extension().is_none_or(|x| x != "json") {
+                continue;
+            }
+            let id = path.file_stem().unwrap().to_str().unwrap();
+            objects.push(self.get(id)?);
+        }
+        Ok(objects)
+    }
+
     /// Saves any struct to JSON, provided it implements Serialize.
     ///
     /// The object will be serialized to JSON and saved at `object_path/<id>.json`.
@@ -110,6 +128,37 @@ impl ObjectRepository {
         Ok(())
     }
 
+    /// Replaces an object that's already been saved.
+    ///
+    /// This is for objects that change after they've been saved, like a play session, which is
+    /// saved when the game is launched and updated when it exits.
+    ///
+    /// # Errors
+    ///
+    /// Result will be an error if there is no object with the specified ID.
+    ///
+    /// Any other errors would be from file IO or the JSON library.
+    pub fn update<T: Serialize>(&self, id: &str, object: &T) -> Result<(), StorageError> {
+        let update_pb = Path::new(&self.object_path).join(format!("{}.json", id));
+        if !update_pb.is_file() {
+            return Err(StorageError::ObjectIdError(format!(
+                "No repository entry for ID {}",
+                id
+            )));
+        }
+        let serialized = serde_json::to_string(&object)?;
+        debug!("Updating entry for {}", id);
+        std::fs::write(update_pb.as_path(), serialized)?;
+        Ok(())
+    }
+
+    /// Checks if an object has been saved with the specified ID.
+    pub fn exists(&self, id: &str) -> bool {
+        Path::new(&self.object_path)
+            .join(format!("{}.json", id))
+            .is_file()
+    }
+
     /// Deletes a saved object.
     ///
     /// This exists for use with the Github release cache to remove stale cache entries.
@@ -259,7 +308,7 @@ mod object_repository {
             wad_dir.create_dir_all().unwrap();
 
             let sut = ObjectRepository::new(&wad_dir).unwrap();
-            let result = sut.save(&"".to_string(), &entry);
+            let result = sut.save("", &entry);
             assert!(result.is_err());
             assert_eq!(
                 result.unwrap_err().to_string(),
@@ -403,6 +452,88 @@ mod object_repository {
             assert!(result.is_ok());
         }
     }
+
+    mod get_all {
+        use super::super::ObjectRepository;
+        use assert_fs::prelude::*;
+
+        #[test]
+        fn should_retrieve_every_saved_object() {
+            let tmp_dir = assert_fs::TempDir::new().unwrap();
+            let wad_dir = tmp_dir.child("wads");
+            wad_dir.create_dir_all().unwrap();
+
+            let sut = ObjectRepository::new(&wad_dir).unwrap();
+            sut.save("DOOM2", &"DOOM2 entry").unwrap();
+            sut.save("TNT", &"TNT entry").unwrap();
+
+            let mut result: Vec<String> = sut.get_all().unwrap();
+            result.sort();
+            assert_eq!(result, vec!["DOOM2 entry", "TNT entry"]);
+        }
+
+        #[test]
+        fn should_return_an_empty_list_when_nothing_has_been_saved() {
+            let tmp_dir = assert_fs::TempDir::new().unwrap();
+            let wad_dir = tmp_dir.child("wads");
+
+            let sut = ObjectRepository::new(&wad_dir).unwrap();
+            let result: Vec<String> = sut.get_all().unwrap();
+            assert!(result.is_empty());
+        }
+    }
+
+    mod update {
+        use super::super::ObjectRepository;
+        use assert_fs::prelude::*;
+
+        #[test]
+        fn should_replace_a_saved_object() {
+            let tmp_dir = assert_fs::TempDir::new().unwrap();
+            let wad_dir = tmp_dir.child("wads");
+            wad_dir.create_dir_all().unwrap();
+
+            let sut = ObjectRepository::new(&wad_dir).unwrap();
+            sut.save("DOOM2", &"entry").unwrap();
+            sut.update("DOOM2", &"updated entry").unwrap();
+
+            let result: String = sut.get("DOOM2").unwrap();
+            assert_eq!(result, "updated entry");
+        }
+
+        #[test]
+        fn should_return_error_for_non_existent_entry() {
+            let tmp_dir = assert_fs::TempDir::new().unwrap();
+            let wad_dir = tmp_dir.child("wads");
+            wad_dir.create_dir_all().unwrap();
+
+            let sut = ObjectRepository::new(&wad_dir).unwrap();
+            let result = sut.update("DOOM2", &"entry");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "No repository entry for ID DOOM2"
+            );
+        }
+    }
+
+    mod exists {
+        use super::super::ObjectRepository;
+        use assert_fs::prelude::*;
+
+        #[test]
+        fn should_check_for_a_saved_object() {
+            let tmp_dir = assert_fs::TempDir::new().unwrap();
+            let wad_dir = tmp_dir.child("wads");
+            wad_dir.create_dir_all().unwrap();
+
+            let sut = ObjectRepository::new(&wad_dir).unwrap();
+            sut.save("DOOM2", &"entry").unwrap();
+
+            assert!(sut.exists("DOOM2"));
+            assert!(!sut.exists("TNT"));
+        }
+    }
 }
 
 #[cfg(test)]
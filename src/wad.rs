@@ -1,14 +1,21 @@
+use crate::complevel::CompLevel;
+use crate::iwad::Game;
 use byteorder::{LittleEndian, ReadBytesExt};
 use color_eyre::{eyre::ensure, eyre::eyre, Help, Report, Result};
 use lazy_static::lazy_static;
+use log::warn;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{Cursor, SeekFrom};
+use std::io::{Cursor, SeekFrom, Take};
+use std::ops::Range;
 use std::path::Path;
 
+/// Used for any information that couldn't be determined when importing a WAD.
+pub const UNKNOWN_VALUE: &str = "Unknown";
 const DIRECTORY_ENTRY_SIZE: u32 = 16;
 lazy_static! {
     /// These regexes are used in a loop when IWADs or PWADs are being imported, and compiling the
@@ -16,201 +23,3111 @@ lazy_static! {
     /// import. For that reason, they are compiled once.
     ///
     /// It's reasonable to perform an unwrap here because I know the regex is valid.
-    static ref DOOM2_FORMAT_REGEX: Regex = Regex::new("^MAP0{1}[1-9]|MAP[0-9]{2}$").unwrap();
-    static ref DOOM_FORMAT_REGEX: Regex = Regex::new("^E[1-9]{1}M[1-9]{1}$").unwrap();
+    static ref DOOM2_FORMAT_REGEX: Regex = Regex::new("^MAP([0-9]{2,})$").unwrap();
+    static ref DOOM_FORMAT_REGEX: Regex = Regex::new("^E([0-9]+)M([0-9]+)$").unwrap();
+    static ref LUMP_NAME_REGEX: Regex = Regex::new(r"^[A-Z0-9_\-\[\]\\]{1,8}$").unwrap();
+    static ref DEHACKED_MAP_NAME_KEY_REGEX: Regex =
+        Regex::new("^(HUSTR|PHUSTR|THUSTR)_(?:([0-9]+)|(E[1-9]M[1-9]))$").unwrap();
+    static ref DEHACKED_MAP_NAME_PREFIX_REGEX: Regex =
+        Regex::new(r"(?i)^(?:level\s+[0-9]+|E[1-9]M[1-9])\s*:\s*").unwrap();
+    static ref DEHACKED_STOCK_MAP_NAME_REGEX: Regex =
+        Regex::new(r"(?i)^(?:level\s+([0-9]+)|(E[1-9]M[1-9]))\s*:\s*(.*)$").unwrap();
+    static ref DEHACKED_TEXT_REGEX: Regex =
+        Regex::new(r"(?i)^Text\s+([0-9]+)\s+([0-9]+)$").unwrap();
+    static ref UDMF_NAMESPACE_REGEX: Regex =
+        Regex::new(r#"(?i)namespace\s*=\s*"([^"]*)"\s*;"#).unwrap();
 }
 
+/// The lumps that can define map information, in order of precedence. When a WAD has more than
+/// one of these, the information from the earlier lump wins, but gaps are filled in from the
+/// later ones.
+pub const MAP_DEFINITION_LUMP_NAMES: [&str; 5] =
+    ["UMAPINFO", "ZMAPINFO", "MAPINFO", "EMAPINFO", "DEHACKED"];
+
+/// The lumps that can follow a map marker in the binary map formats. This includes the Hexen
+/// BEHAVIOR and SCRIPTS lumps, along with the lumps used by Doom 64.
+const MAP_DATA_LUMP_NAMES: [&str; 15] = [
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP", "BEHAVIOR", "SCRIPTS", "LEAFS", "LIGHTS", "MACROS",
+];
+
+/// The sizes of the records in the binary map lumps. The Hexen format adds a thing ID, a height
+/// and an action special to things, and replaces the tag on lines with arguments for the special.
+const THING_SIZE: usize = 10;
+const HEXEN_THING_SIZE: usize = 20;
+const LINEDEF_SIZE: usize = 14;
+const HEXEN_LINEDEF_SIZE: usize = 16;
+const SIDEDEF_SIZE: usize = 30;
+const VERTEX_SIZE: usize = 4;
+const SECTOR_SIZE: usize = 26;
+
+/// The flags on a thing that determine when it appears. The Boom and MBF flags are ignored if the
+/// reserved flag is set, because some old editors set all the unused bits.
+const THING_FLAG_EASY: u16 = 0x0001;
+const THING_FLAG_MEDIUM: u16 = 0x0002;
+const THING_FLAG_HARD: u16 = 0x0004;
+const THING_FLAG_AMBUSH: u16 = 0x0008;
+const THING_FLAG_MULTIPLAYER: u16 = 0x0010;
+pub const THING_FLAG_NOT_DEATHMATCH: u16 = 0x0020;
+pub const THING_FLAG_NOT_COOP: u16 = 0x0040;
+pub const THING_FLAG_FRIENDLY: u16 = 0x0080;
+const THING_FLAG_RESERVED: u16 = 0x0100;
+
+/// The Hexen format has explicit flags for the game modes a thing appears in, rather than flags
+/// for the modes it doesn't. The friendly flag is the one used by ZDoom. UDMF things are given
+/// these flags too, since its boolean properties map directly onto them.
+const HEXEN_THING_FLAG_AMBUSH: u16 = 0x0008;
+const HEXEN_THING_FLAG_DORMANT: u16 = 0x0010;
+const HEXEN_THING_FLAG_SINGLE: u16 = 0x0100;
+const HEXEN_THING_FLAG_COOP: u16 = 0x0200;
+const HEXEN_THING_FLAG_DEATHMATCH: u16 = 0x0400;
+const HEXEN_THING_FLAG_FRIENDLY: u16 = 0x2000;
+
+/// The flags on a line, in the order of the bits, with the names used for them in UDMF.
+const LINEDEF_FLAG_NAMES: [&str; 9] = [
+    "blocking",
+    "blockmonsters",
+    "twosided",
+    "dontpegtop",
+    "dontpegbottom",
+    "secret",
+    "blocksound",
+    "dontdraw",
+    "mapped",
+];
+
+/// The namespace must be the first thing in a TEXTMAP lump, so only the start of the lump needs
+/// to be read to find it.
+const UDMF_NAMESPACE_SEARCH_SIZE: u64 = 1024;
+
+/// The sector specials that mark a secret. Boom's generalised sector types use a bit for the
+/// secret, which is what allows it to be combined with damage or lighting effects.
+const SECTOR_SPECIAL_SECRET: u16 = 9;
+const SECTOR_SPECIAL_BOOM_SECRET_FLAG: u16 = 0x0080;
+const SECTOR_SPECIAL_ZDOOM_SECRET_FLAG: u16 = 0x0400;
+
+/// The thing types for the DOOM and DOOM2 monsters that count towards the kill percentage. The
+/// boss brain for the Icon of Sin and the Lost Soul aren't included, because they don't count.
+const MONSTER_THING_TYPES: [u16; 18] = [
+    3004, 9, 65, 3001, 3002, 58, 3005, 69, 3003, 68, 71, 66, 67, 64, 16, 7, 84, 72,
+];
+
+/// The thing types for the items that count towards the item percentage. Not every pickup counts,
+/// e.g. ammo and the radiation suit don't.
+const ITEM_THING_TYPES: [u16; 9] = [2014, 2015, 2013, 83, 2022, 2023, 2024, 2026, 2045];
+
+/// The keywords that begin a top level definition in the MAPINFO dialects. In the older ZDoom and
+/// Hexen formats the properties of a map aren't enclosed in braces, so this is how we know where
+/// the properties end.
+const MAPINFO_TOP_LEVEL_KEYWORDS: [&str; 21] = [
+    "map",
+    "defaultmap",
+    "adddefaultmap",
+    "gamedefaults",
+    "episode",
+    "clearepisodes",
+    "cluster",
+    "clusterdef",
+    "skill",
+    "clearskills",
+    "gameinfo",
+    "intermission",
+    "automap",
+    "automap_overlay",
+    "doomednums",
+    "spawnnums",
+    "conversationids",
+    "damagetype",
+    "include",
+    "cutscene",
+    "lock",
+];
+
 pub struct WadHeader {
     pub wad_type: String,
     pub directory_entries: u32,
     pub directory_offset: u32,
 }
 
-pub struct WadDirectoryEntry {
-    pub lump_offset: u32,
-    pub lump_size: u32,
-    pub lump_name: String,
-}
+pub struct WadDirectoryEntry {
+    pub lump_offset: u32,
+    pub lump_size: u32,
+    pub lump_name: String,
+}
+
+pub struct WadMetadata {
+    pub header: WadHeader,
+    pub directory: Vec<WadDirectoryEntry>,
+}
+
+/// Provides access to the lump data in a WAD.
+///
+/// The source is usually a file, but it can be anything that can be read and seeked, which allows
+/// for reading WADs that have been extracted from a package into memory.
+pub struct WadReader<R: Read + Seek = File> {
+    pub metadata: WadMetadata,
+    source: R,
+}
+
+/// Structure that describes a map in the game.
+///
+/// The number is the name of the map's marker lump. This is usually MAPxx, for DOOM2, or ExMx for
+/// DOOM, e.g. MAP03 or E1M3, but megawads go beyond those, with maps like MAP100 or E5M1, and
+/// UMAPINFO allows a map to have any name, like START. The map will also have a name. Newer WADs
+/// contain MAPINFO lumps in the WAD, but the original IWADs did not contain these.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MapInfo {
+    /// The map number, which is the name of its marker lump.
+    pub number: String,
+    /// The name of the map.
+    pub name: String,
+    /// How the map is started from the command line.
+    #[serde(deserialize_with = "deserialize_warp")]
+    pub warp: Warp,
+    /// The par time, in seconds, if the WAD defines one.
+    #[serde(default)]
+    pub par_time: Option<u32>,
+    /// The lump name of the map that follows this one, if the WAD defines it.
+    #[serde(default)]
+    pub next: Option<String>,
+    /// The lump name of the map the secret exit leads to, if the WAD defines it.
+    #[serde(default)]
+    pub next_secret: Option<String>,
+    /// The format of the map, which determines the source ports that can play it.
+    #[serde(default)]
+    pub format: MapFormat,
+    /// The statistics for the map. These won't be available if the map is incomplete.
+    #[serde(default)]
+    pub stats: Option<MapStats>,
+}
+
+/// The arguments used to start a map from the command line.
+///
+/// The `-warp` argument only takes numbers, which the source port turns back into MAPxx or ExMy,
+/// so a map with any other kind of name, like START, has to be loaded using its lump name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Warp {
+    /// `-warp xx`, for MAPxx. This includes MAP100 and above.
+    Map(u32),
+    /// `-warp x y`, for ExMy. This includes maps like E5M1 and E1M10.
+    Episode(u32, u32),
+    /// The map can only be loaded using its lump name.
+    LumpName(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WadEntry {
+    pub id: String,
+    pub name: String,
+    pub title: String,
+    pub release_date: String,
+    pub author: String,
+    pub maps: Vec<MapInfo>,
+    #[serde(default)]
+    pub episodes: Vec<EpisodeDefinition>,
+    /// The game an IWAD is for. This isn't set for PWADs.
+    #[serde(default)]
+    pub game: Option<Game>,
+    /// The release of the game, e.g. 1.9 or BFG Edition, if it could be identified.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The ID of the IWAD a PWAD is played with. This isn't set for IWADs.
+    #[serde(default)]
+    pub iwad: Option<String>,
+    /// Other files that are loaded along with a PWAD, in the order they're loaded.
+    #[serde(default)]
+    pub files: Vec<SupplementaryFile>,
+    /// The compatibility level a PWAD is made for, which is only worked out for WADs with Doom
+    /// format maps.
+    #[serde(default)]
+    pub complevel: Option<CompLevel>,
+}
+
+/// Whether a supplementary file is loaded before or after the PWAD it goes with.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoadOrder {
+    /// For resources the PWAD depends on, like a texture pack, which the PWAD can override.
+    BeforeWad,
+    /// For anything that overrides the PWAD, like a music pack or a patch.
+    AfterWad,
+}
+
+/// A file that's needed to play a PWAD, like a DeHackEd patch, a music pack or a texture pack.
+///
+/// These are kept in a directory named after the PWAD's ID, alongside the PWAD itself, so files
+/// with the same name for different PWADs don't overwrite each other.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SupplementaryFile {
+    pub name: String,
+    pub load_order: LoadOrder,
+}
+
+/// A map, as described by one of the map information lumps, e.g. UMAPINFO or MAPINFO.
+///
+/// Everything other than the lump name is optional, because none of the formats require any
+/// particular property to be set. The par time is in seconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapDefinition {
+    pub lump_name: String,
+    pub name: Option<String>,
+    pub par_time: Option<u32>,
+    pub next: Option<String>,
+    pub next_secret: Option<String>,
+}
+
+/// An episode, as described by one of the map information lumps.
+///
+/// The map is the lump name of the first map in the episode and the key is the shortcut key used
+/// on the episode selection menu.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeDefinition {
+    pub map: String,
+    pub name: String,
+    pub key: Option<String>,
+}
+
+/// The map and episode information found in a WAD.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapDefinitions {
+    pub maps: Vec<MapDefinition>,
+    pub episodes: Vec<EpisodeDefinition>,
+}
+
+/// The format a map is stored in.
+///
+/// The Doom format is the original binary format. The Hexen format is also binary, but it has a
+/// BEHAVIOR lump for its scripts and extends the things and lines to support action specials. UDMF
+/// is a text format, stored in a TEXTMAP lump, and the namespace determines which source ports
+/// can play it, e.g. `zdoom` or `eternity`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MapFormat {
+    #[default]
+    Doom,
+    Hexen,
+    Udmf(String),
+}
+
+/// A thing in a map, e.g. a monster, a pickup or the player start.
+///
+/// The ID, height, special and arguments are only used by the Hexen format and UDMF, so they are
+/// always zero for a Doom format map. The meaning of the flags also depends on the format.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Thing {
+    pub tid: u16,
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+    pub angle: u16,
+    pub thing_type: u16,
+    pub flags: u16,
+    pub special: u16,
+    pub args: [i32; 5],
+}
+
+/// A line in a map. The vertexes and sidedefs are indexes into their respective lumps, and a
+/// sidedef of 0xFFFF means the line doesn't have that side.
+///
+/// Lines in the Hexen format don't have a tag, since it's one of the arguments to the special.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Linedef {
+    pub start_vertex: u16,
+    pub end_vertex: u16,
+    pub flags: u16,
+    pub special: u16,
+    pub tag: u16,
+    pub args: [i32; 5],
+    pub front_sidedef: u16,
+    pub back_sidedef: u16,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sidedef {
+    pub x_offset: i16,
+    pub y_offset: i16,
+    pub upper_texture: String,
+    pub lower_texture: String,
+    pub middle_texture: String,
+    pub sector: u16,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub x: i16,
+    pub y: i16,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sector {
+    pub floor_height: i16,
+    pub ceiling_height: i16,
+    pub floor_texture: String,
+    pub ceiling_texture: String,
+    pub light_level: i16,
+    pub special: u16,
+    pub tag: u16,
+}
+
+/// A block in a UDMF TEXTMAP lump, e.g. a thing, with its type and properties.
+type TextmapBlock = (String, HashMap<String, String>);
+
+/// The decoded lumps for a map. UDMF maps are converted into the same structures, though some of
+/// their properties, like floating point coordinates, lose precision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapData {
+    pub format: MapFormat,
+    pub things: Vec<Thing>,
+    pub linedefs: Vec<Linedef>,
+    pub sidedefs: Vec<Sidedef>,
+    pub vertexes: Vec<Vertex>,
+    pub sectors: Vec<Sector>,
+}
+
+/// Statistics that give an idea of how big and how hard a map is.
+///
+/// The monsters are counted for each group of skill levels that can have different things: easy is
+/// ITYTD and HNTR, medium is HMP, and hard is UV and NM. The items are counted for the hard skill
+/// levels, since that's how most people play.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MapStats {
+    pub easy_monsters: u32,
+    pub medium_monsters: u32,
+    pub hard_monsters: u32,
+    pub items: u32,
+    pub secrets: u32,
+    pub linedefs: u32,
+    pub sectors: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TextToken {
+    Word(String),
+    Text(String),
+    Symbol(char),
+}
+
+impl WadMetadata {
+    pub fn from_path(wad_path: impl AsRef<Path>) -> Result<WadMetadata, Report> {
+        let mut file = File::open(&wad_path)?;
+        WadMetadata::from_reader(&wad_path, &mut file)
+    }
+
+    /// Reads the metadata from any source, e.g. a WAD that's been read into memory.
+    ///
+    /// The path is only used to identify the WAD if it turns out not to be valid.
+    pub fn from_reader(
+        wad_path: impl AsRef<Path>,
+        reader: &mut (impl Read + Seek),
+    ) -> Result<WadMetadata, Report> {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = WadMetadata::read_header(&wad_path, reader)?;
+        let directory = WadMetadata::read_directory(reader, &header)?;
+        Ok(WadMetadata { header, directory })
+    }
+
+    fn read_header(
+        wad_path: &impl AsRef<Path>,
+        wad_file: &mut impl Read,
+    ) -> Result<WadHeader, Report> {
+        let mut header = [0; 12];
+        wad_file.read_exact(&mut header)?;
+        let wad_type = String::from_utf8(header[..4].to_vec())?;
+        if wad_type != "IWAD" && wad_type != "PWAD" {
+            return Err(
+                eyre!(format!("Failed to parse {}", wad_path.as_ref().display()))
+                    .suggestion("This file is likely not a WAD file"),
+            );
+        }
+        let mut cursor = Cursor::new(header[4..].to_vec());
+        let directory_entries = cursor.read_u32::<LittleEndian>()?;
+        let directory_offset = cursor.read_u32::<LittleEndian>()?;
+        Ok(WadHeader {
+            wad_type,
+            directory_entries,
+            directory_offset,
+        })
+    }
+
+    fn read_directory(
+        wad_file: &mut (impl Read + Seek),
+        header: &WadHeader,
+    ) -> Result<Vec<WadDirectoryEntry>, Report> {
+        let capacity = header.directory_entries * DIRECTORY_ENTRY_SIZE;
+        let mut directory_buf: Vec<u8> = Vec::with_capacity(capacity.try_into()?);
+        wad_file.seek(SeekFrom::Start(header.directory_offset.into()))?;
+        wad_file.read_to_end(&mut directory_buf)?;
+
+        let mut directory: Vec<WadDirectoryEntry> = Vec::new();
+        let mut cursor = Cursor::new(directory_buf);
+        let mut entries_read = 0;
+        while entries_read < header.directory_entries {
+            let lump_offset = cursor.read_u32::<LittleEndian>()?;
+            let lump_size = cursor.read_u32::<LittleEndian>()?;
+            let mut buffer = [0; 8];
+            cursor.read_exact(&mut buffer)?;
+            let lump_name = String::from_utf8(buffer.to_vec())?;
+            let lump_name = lump_name.trim_matches(char::from(0)).to_string(); // Strip UTF-8 terminator.
+            directory.push(WadDirectoryEntry {
+                lump_offset,
+                lump_size,
+                lump_name,
+            });
+            entries_read += 1;
+        }
+        Ok(directory)
+    }
+
+    /// Gets the names of the map marker lumps in the directory.
+    ///
+    /// A map marker is a lump that's followed by the lumps that define the map: THINGS for the
+    /// binary formats and TEXTMAP for UDMF. The name is usually in the ExMx or MAPxx format, but it
+    /// can be anything, e.g. START. The marker is usually empty, but some ports store scripts in
+    /// it, so its size isn't used to identify it.
+    ///
+    /// A WAD that's been merged or patched can have more than one marker with the same name. Only
+    /// the last one is returned, since that's the map the engine loads.
+    pub fn get_map_lump_names(&self) -> Vec<String> {
+        let names = self
+            .directory
+            .iter()
+            .zip(self.directory.iter().skip(1))
+            .filter(|(marker, next)| {
+                MapInfo::is_valid_map_number(&marker.lump_name)
+                    && (next.lump_name == "THINGS" || next.lump_name == "TEXTMAP")
+            })
+            .map(|(marker, _)| marker.lump_name.clone())
+            .collect::<Vec<String>>();
+        names
+            .iter()
+            .enumerate()
+            .filter(|(index, name)| {
+                !names[index + 1..]
+                    .iter()
+                    .any(|x| x.eq_ignore_ascii_case(name))
+            })
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Gets the index of a lump in the directory.
+    ///
+    /// If there's more than one lump with the name, the last one is used, which is the same rule
+    /// the engine uses when it looks up a lump.
+    pub fn get_lump_index(&self, lump_name: &str) -> Option<usize> {
+        self.directory
+            .iter()
+            .rposition(|x| x.lump_name.eq_ignore_ascii_case(lump_name))
+    }
+
+    /// Gets the range of directory indexes for the lumps that make up a map, not including the
+    /// marker.
+    ///
+    /// For the binary formats, this is the run of known map data lumps that follow the marker. For
+    /// UDMF maps, it's everything from the TEXTMAP lump up to and including ENDMAP. As with
+    /// `get_map_lump_names`, a lump is only a marker if it's followed by THINGS or TEXTMAP, and
+    /// the last marker with the name is used.
+    pub fn get_map_lump_range(&self, map_lump_name: &str) -> Option<Range<usize>> {
+        let marker_index = self.directory.windows(2).rposition(|x| {
+            x[0].lump_name.eq_ignore_ascii_case(map_lump_name)
+                && (x[1].lump_name == "THINGS" || x[1].lump_name == "TEXTMAP")
+        })?;
+        let start = marker_index + 1;
+        let mut end = start;
+        if self
+            .directory
+            .get(start)
+            .is_some_and(|x| x.lump_name == "TEXTMAP")
+        {
+            while let Some(entry) = self.directory.get(end) {
+                end += 1;
+                if entry.lump_name == "ENDMAP" {
+                    break;
+                }
+            }
+        } else {
+            while self
+                .directory
+                .get(end)
+                .is_some_and(|x| MAP_DATA_LUMP_NAMES.contains(&x.lump_name.as_str()))
+            {
+                end += 1;
+            }
+        }
+        Some(start..end)
+    }
+
+    /// Gets the index of one of the lumps that make up a map, e.g. the THINGS for MAP01.
+    pub fn get_map_lump_index(&self, map_lump_name: &str, lump_name: &str) -> Option<usize> {
+        self.get_map_lump_range(map_lump_name)?
+            .find(|x| self.directory[*x].lump_name.eq_ignore_ascii_case(lump_name))
+    }
+}
+
+impl WadReader<File> {
+    pub fn open(wad_path: impl AsRef<Path>) -> Result<WadReader<File>, Report> {
+        let file = File::open(&wad_path)?;
+        WadReader::new(&wad_path, file)
+    }
+}
+
+impl<R: Read + Seek> WadReader<R> {
+    /// Reads the header and directory from the source, which is then kept for reading the lumps.
+    ///
+    /// The path is only used to identify the WAD if it turns out not to be valid.
+    pub fn new(wad_path: impl AsRef<Path>, mut source: R) -> Result<WadReader<R>, Report> {
+        let metadata = WadMetadata::from_reader(wad_path, &mut source)?;
+        Ok(WadReader { metadata, source })
+    }
+
+    /// Reads all the data for the lump at the given index in the directory.
+    pub fn read_lump(&mut self, index: usize) -> Result<Vec<u8>, Report> {
+        let mut data = Vec::new();
+        self.get_lump_reader(index)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Gets a reader for the lump at the given index in the directory.
+    ///
+    /// This should be used for large lumps, like music or a big TEXTMAP, where you don't want to
+    /// read the whole thing into memory at once.
+    pub fn get_lump_reader(&mut self, index: usize) -> Result<Take<&mut R>, Report> {
+        let entry = self.metadata.directory.get(index).ok_or_else(|| {
+            eyre!(
+                "There is no lump at index {}. The directory has {} entries.",
+                index,
+                self.metadata.directory.len()
+            )
+        })?;
+        let size = entry.lump_size.into();
+        self.source
+            .seek(SeekFrom::Start(entry.lump_offset.into()))?;
+        Ok((&mut self.source).take(size))
+    }
+
+    /// Works out the format of a map from the lumps that follow its marker.
+    ///
+    /// For UDMF, the namespace is read from the start of the TEXTMAP lump. The rest of the lump
+    /// doesn't need to be read, which matters, because it can be several megabytes.
+    pub fn get_map_format(&mut self, map_lump_name: &str) -> Result<MapFormat, Report> {
+        if let Some(index) = self.metadata.get_map_lump_index(map_lump_name, "TEXTMAP") {
+            let mut start = Vec::new();
+            self.get_lump_reader(index)?
+                .take(UDMF_NAMESPACE_SEARCH_SIZE)
+                .read_to_end(&mut start)?;
+            let namespace = UDMF_NAMESPACE_REGEX
+                .captures(&String::from_utf8_lossy(&start))
+                .map(|x| x[1].to_lowercase())
+                .unwrap_or_default();
+            return Ok(MapFormat::Udmf(namespace));
+        }
+        if self
+            .metadata
+            .get_map_lump_index(map_lump_name, "BEHAVIOR")
+            .is_some()
+        {
+            return Ok(MapFormat::Hexen);
+        }
+        Ok(MapFormat::Doom)
+    }
+
+    /// Checks the map has all the lumps its format requires. An incomplete map wouldn't load, and
+    /// its data can't be decoded.
+    pub fn is_map_complete(&mut self, map_lump_name: &str) -> Result<bool, Report> {
+        let required_lumps: &[&str] = match self.get_map_format(map_lump_name)? {
+            MapFormat::Doom => &["THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SECTORS"],
+            MapFormat::Hexen => &[
+                "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SECTORS", "BEHAVIOR",
+            ],
+            MapFormat::Udmf(_) => &["TEXTMAP", "ENDMAP"],
+        };
+        Ok(required_lumps
+            .iter()
+            .all(|x| self.metadata.get_map_lump_index(map_lump_name, x).is_some()))
+    }
+}
+
+impl Thing {
+    /// Gets the flags of a thing in the Doom format, without the Boom and MBF flags if the
+    /// reserved flag is set. The vanilla flags are kept, which is what MBF does.
+    pub fn get_doom_flags(&self) -> u16 {
+        if self.flags & THING_FLAG_RESERVED != 0 {
+            self.flags
+                & (THING_FLAG_EASY
+                    | THING_FLAG_MEDIUM
+                    | THING_FLAG_HARD
+                    | THING_FLAG_AMBUSH
+                    | THING_FLAG_MULTIPLAYER)
+        } else {
+            self.flags
+        }
+    }
+}
+
+impl MapInfo {
+    pub fn new(number: String, name: String) -> Result<MapInfo, Report> {
+        ensure!(
+            !number.is_empty(),
+            "A number must be provided for the map. It should be the name of its marker lump."
+        );
+        ensure!(
+            MapInfo::is_valid_map_number(&number),
+            "The map number must be a lump name, with up to 8 uppercase letters, digits or symbols."
+        );
+        ensure!(!name.is_empty(), "A name must be provided for the map.");
+        let warp = Warp::from_map_number(&number);
+        Ok(MapInfo {
+            number,
+            name,
+            warp,
+            par_time: None,
+            next: None,
+            next_secret: None,
+            format: MapFormat::Doom,
+            stats: None,
+        })
+    }
+
+    /// Determines whether the number could be the name of a map marker lump.
+    ///
+    /// Any lump name could be used for a map, so the lumps that follow the marker are what really
+    /// identify it. See `WadMetadata::get_map_lump_names`.
+    pub fn is_valid_map_number(number: &str) -> bool {
+        LUMP_NAME_REGEX.is_match(number)
+    }
+}
+
+impl Warp {
+    /// Works out how to start a map from its lump name.
+    ///
+    /// The number has to survive the trip back to a lump name for `-warp` to be used, so something
+    /// like MAP001 would be loaded using its lump name, since `-warp 1` would load MAP01.
+    pub fn from_map_number(number: &str) -> Warp {
+        if let Some(captures) = DOOM2_FORMAT_REGEX.captures(number) {
+            if let Ok(map) = captures[1].parse::<u32>() {
+                if map > 0 && format!("MAP{:02}", map) == number {
+                    return Warp::Map(map);
+                }
+            }
+        }
+        if let Some(captures) = DOOM_FORMAT_REGEX.captures(number) {
+            if let (Ok(episode), Ok(map)) = (captures[1].parse::<u32>(), captures[2].parse::<u32>())
+            {
+                if episode > 0 && map > 0 && format!("E{}M{}", episode, map) == number {
+                    return Warp::Episode(episode, map);
+                }
+            }
+        }
+        Warp::LumpName(number.to_string())
+    }
+
+    /// Gets the values for the `-warp` argument, or nothing if the map has to be loaded by name.
+    pub fn get_warp_args(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Map(map) => Some(vec![map.to_string()]),
+            Self::Episode(episode, map) => Some(vec![episode.to_string(), map.to_string()]),
+            Self::LumpName(_) => None,
+        }
+    }
+}
+
+/// Entries imported before maps could have any name stored the warp as a string, e.g. "1" for
+/// MAP01 or "1 1" for E1M1, so those are converted when they're loaded.
+fn deserialize_warp<'de, D>(deserializer: D) -> Result<Warp, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredWarp {
+        Legacy(String),
+        Current(Warp),
+    }
+    Ok(match StoredWarp::deserialize(deserializer)? {
+        StoredWarp::Legacy(warp) => {
+            let numbers = warp
+                .split_whitespace()
+                .map(|x| x.parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .unwrap_or_default();
+            match numbers[..] {
+                [map] => Warp::Map(map),
+                [episode, map] => Warp::Episode(episode, map),
+                _ => Warp::LumpName(warp),
+            }
+        }
+        StoredWarp::Current(warp) => warp,
+    })
+}
+
+impl WadEntry {
+    pub fn new(
+        id: String,
+        name: String,
+        title: String,
+        release_date: String,
+        author: String,
+        maps: Vec<MapInfo>,
+    ) -> Result<WadEntry, Report> {
+        ensure!(!id.is_empty(), "The ID for the WAD entry must be set.");
+        ensure!(!name.is_empty(), "The name for the WAD entry must be set.");
+        ensure!(
+            !title.is_empty(),
+            "The title for the WAD entry must be set."
+        );
+        ensure!(
+            !release_date.is_empty(),
+            "The release date for the WAD entry must be set."
+        );
+        ensure!(
+            !author.is_empty(),
+            "The author for the WAD entry must be set."
+        );
+        Ok(WadEntry {
+            id,
+            name,
+            title,
+            release_date,
+            author,
+            maps,
+            episodes: Vec::new(),
+            game: None,
+            version: None,
+            iwad: None,
+            files: Vec::new(),
+            complevel: None,
+        })
+    }
+
+    /// Gets the map that follows `number`.
+    ///
+    /// If the WAD defines the next map, that's the one that follows, since a WAD doesn't have to
+    /// be played in the order its maps are stored in. The link is ignored if it doesn't lead to a
+    /// map in the WAD, e.g. a MAPINFO `next` that ends the game. Otherwise it's the next map in
+    /// the WAD. There's no next map after the last one.
+    pub fn get_next_map(&self, number: &str) -> Option<&MapInfo> {
+        let index = self.maps.iter().position(|x| x.number == number)?;
+        let linked = self.maps[index]
+            .next
+            .as_ref()
+            .and_then(|next| self.maps.iter().find(|x| x.number == *next));
+        linked.or_else(|| self.maps.get(index + 1))
+    }
+}
+
+impl SupplementaryFile {
+    /// DeHackEd and BEX patches are loaded with their own argument, rather than with the WADs.
+    pub fn is_dehacked(&self) -> bool {
+        let name = self.name.to_lowercase();
+        name.ends_with(".deh") || name.ends_with(".bex")
+    }
+}
+
+impl std::fmt::Display for MapFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Doom => write!(f, "Doom"),
+            Self::Hexen => write!(f, "Hexen"),
+            Self::Udmf(namespace) => write!(f, "UDMF ({})", namespace),
+        }
+    }
+}
+
+impl MapData {
+    /// Decodes the lumps for a map, in whichever format it's stored in.
+    ///
+    /// For the binary formats, if a lump isn't an exact multiple of the record size, the extra
+    /// bytes are ignored, which is what the engine does.
+    pub fn from_wad<R: Read + Seek>(
+        reader: &mut WadReader<R>,
+        map_lump_name: &str,
+    ) -> Result<MapData, Report> {
+        let format = reader.get_map_format(map_lump_name)?;
+        if let MapFormat::Udmf(_) = format {
+            let textmap = MapData::read_map_lump(reader, map_lump_name, "TEXTMAP")?;
+            return MapData::from_textmap(&String::from_utf8_lossy(&textmap), format);
+        }
+
+        let is_hexen = format == MapFormat::Hexen;
+        let thing_size = if is_hexen {
+            HEXEN_THING_SIZE
+        } else {
+            THING_SIZE
+        };
+        let things = MapData::read_map_lump(reader, map_lump_name, "THINGS")?
+            .chunks_exact(thing_size)
+            .map(|x| {
+                let mut record = Cursor::new(x);
+                if is_hexen {
+                    Ok(Thing {
+                        tid: record.read_u16::<LittleEndian>()?,
+                        x: record.read_i16::<LittleEndian>()?,
+                        y: record.read_i16::<LittleEndian>()?,
+                        z: record.read_i16::<LittleEndian>()?,
+                        angle: record.read_u16::<LittleEndian>()?,
+                        thing_type: record.read_u16::<LittleEndian>()?,
+                        flags: record.read_u16::<LittleEndian>()?,
+                        special: record.read_u8()?.into(),
+                        args: read_hexen_args(&mut record)?,
+                    })
+                } else {
+                    Ok(Thing {
+                        x: record.read_i16::<LittleEndian>()?,
+                        y: record.read_i16::<LittleEndian>()?,
+                        angle: record.read_u16::<LittleEndian>()?,
+                        thing_type: record.read_u16::<LittleEndian>()?,
+                        flags: record.read_u16::<LittleEndian>()?,
+                        ..Default::default()
+                    })
+                }
+            })
+            .collect::<Result<Vec<Thing>, Report>>()?;
+        let linedef_size = if is_hexen {
+            HEXEN_LINEDEF_SIZE
+        } else {
+            LINEDEF_SIZE
+        };
+        let linedefs = MapData::read_map_lump(reader, map_lump_name, "LINEDEFS")?
+            .chunks_exact(linedef_size)
+            .map(|x| {
+                let mut record = Cursor::new(x);
+                let start_vertex = record.read_u16::<LittleEndian>()?;
+                let end_vertex = record.read_u16::<LittleEndian>()?;
+                let flags = record.read_u16::<LittleEndian>()?;
+                let (special, tag, args) = if is_hexen {
+                    (record.read_u8()?.into(), 0, read_hexen_args(&mut record)?)
+                } else {
+                    (
+                        record.read_u16::<LittleEndian>()?,
+                        record.read_u16::<LittleEndian>()?,
+                        [0; 5],
+                    )
+                };
+                Ok(Linedef {
+                    start_vertex,
+                    end_vertex,
+                    flags,
+                    special,
+                    tag,
+                    args,
+                    front_sidedef: record.read_u16::<LittleEndian>()?,
+                    back_sidedef: record.read_u16::<LittleEndian>()?,
+                })
+            })
+            .collect::<Result<Vec<Linedef>, Report>>()?;
+        let sidedefs = MapData::read_map_lump(reader, map_lump_name, "SIDEDEFS")?
+            .chunks_exact(SIDEDEF_SIZE)
+            .map(|x| {
+                let mut record = Cursor::new(x);
+                Ok(Sidedef {
+                    x_offset: record.read_i16::<LittleEndian>()?,
+                    y_offset: record.read_i16::<LittleEndian>()?,
+                    upper_texture: read_lump_name(&mut record)?,
+                    lower_texture: read_lump_name(&mut record)?,
+                    middle_texture: read_lump_name(&mut record)?,
+                    sector: record.read_u16::<LittleEndian>()?,
+                })
+            })
+            .collect::<Result<Vec<Sidedef>, Report>>()?;
+        let vertexes = MapData::read_map_lump(reader, map_lump_name, "VERTEXES")?
+            .chunks_exact(VERTEX_SIZE)
+            .map(|x| {
+                let mut record = Cursor::new(x);
+                Ok(Vertex {
+                    x: record.read_i16::<LittleEndian>()?,
+                    y: record.read_i16::<LittleEndian>()?,
+                })
+            })
+            .collect::<Result<Vec<Vertex>, Report>>()?;
+        let sectors = MapData::read_map_lump(reader, map_lump_name, "SECTORS")?
+            .chunks_exact(SECTOR_SIZE)
+            .map(|x| {
+                let mut record = Cursor::new(x);
+                Ok(Sector {
+                    floor_height: record.read_i16::<LittleEndian>()?,
+                    ceiling_height: record.read_i16::<LittleEndian>()?,
+                    floor_texture: read_lump_name(&mut record)?,
+                    ceiling_texture: read_lump_name(&mut record)?,
+                    light_level: record.read_i16::<LittleEndian>()?,
+                    special: record.read_u16::<LittleEndian>()?,
+                    tag: record.read_u16::<LittleEndian>()?,
+                })
+            })
+            .collect::<Result<Vec<Sector>, Report>>()?;
+        Ok(MapData {
+            format,
+            things,
+            linedefs,
+            sidedefs,
+            vertexes,
+            sectors,
+        })
+    }
+
+    /// Decodes the contents of a UDMF TEXTMAP lump.
+    ///
+    /// The properties are converted to their equivalents in the Hexen format, which is what the
+    /// UDMF spec was based on. Any properties that don't have an equivalent are ignored.
+    pub fn from_textmap(contents: &str, format: MapFormat) -> Result<MapData, Report> {
+        let mut map = MapData {
+            format,
+            things: Vec::new(),
+            linedefs: Vec::new(),
+            sidedefs: Vec::new(),
+            vertexes: Vec::new(),
+            sectors: Vec::new(),
+        };
+        for (block_type, block) in MapData::parse_textmap(contents)? {
+            let number = |key: &str, default: f64| {
+                block
+                    .get(key)
+                    .and_then(|x| x.parse::<f64>().ok())
+                    .unwrap_or(default)
+            };
+            let flag = |key: &str| block.get(key).is_some_and(|x| x == "true");
+            let text = |key: &str| block.get(key).cloned().unwrap_or_else(|| "-".to_string());
+            let args = [
+                number("arg0", 0.0) as i32,
+                number("arg1", 0.0) as i32,
+                number("arg2", 0.0) as i32,
+                number("arg3", 0.0) as i32,
+                number("arg4", 0.0) as i32,
+            ];
+            match block_type.as_str() {
+                "thing" => {
+                    let mut flags = 0;
+                    for (keys, value) in [
+                        (&["skill1", "skill2"][..], THING_FLAG_EASY),
+                        (&["skill3"][..], THING_FLAG_MEDIUM),
+                        (&["skill4", "skill5"][..], THING_FLAG_HARD),
+                        (&["ambush"][..], HEXEN_THING_FLAG_AMBUSH),
+                        (&["dormant"][..], HEXEN_THING_FLAG_DORMANT),
+                        (&["single"][..], HEXEN_THING_FLAG_SINGLE),
+                        (&["coop"][..], HEXEN_THING_FLAG_COOP),
+                        (&["dm"][..], HEXEN_THING_FLAG_DEATHMATCH),
+                        (&["friend"][..], HEXEN_THING_FLAG_FRIENDLY),
+                    ] {
+                        if keys.iter().any(|x| flag(x)) {
+                            flags |= value;
+                        }
+                    }
+                    map.things.push(Thing {
+                        tid: number("id", 0.0) as u16,
+                        x: number("x", 0.0) as i16,
+                        y: number("y", 0.0) as i16,
+                        z: number("height", 0.0) as i16,
+                        angle: number("angle", 0.0) as u16,
+                        thing_type: number("type", 0.0) as u16,
+                        flags,
+                        special: number("special", 0.0) as u16,
+                        args,
+                    });
+                }
+                "linedef" => {
+                    let flags = LINEDEF_FLAG_NAMES
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, name)| flag(name))
+                        .fold(0, |flags, (bit, _)| flags | 1 << bit);
+                    map.linedefs.push(Linedef {
+                        start_vertex: number("v1", 0.0) as u16,
+                        end_vertex: number("v2", 0.0) as u16,
+                        flags,
+                        special: number("special", 0.0) as u16,
+                        tag: number("id", 0.0).max(0.0) as u16,
+                        args,
+                        front_sidedef: number("sidefront", 0.0) as u16,
+                        // This is -1 when there isn't a back side, which becomes 0xFFFF, the same
+                        // as the binary formats.
+                        back_sidedef: number("sideback", -1.0) as i32 as u16,
+                    });
+                }
+                "sidedef" => map.sidedefs.push(Sidedef {
+                    x_offset: number("offsetx", 0.0) as i16,
+                    y_offset: number("offsety", 0.0) as i16,
+                    upper_texture: text("texturetop"),
+                    lower_texture: text("texturebottom"),
+                    middle_texture: text("texturemiddle"),
+                    sector: number("sector", 0.0) as u16,
+                }),
+                "vertex" => map.vertexes.push(Vertex {
+                    x: number("x", 0.0) as i16,
+                    y: number("y", 0.0) as i16,
+                }),
+                "sector" => {
+                    // ZDoom has a property for a secret, rather than using a special, so it gets
+                    // converted into the flag ZDoom uses for the Hexen format.
+                    let mut special = number("special", 0.0) as u16;
+                    if flag("secret") {
+                        special |= SECTOR_SPECIAL_ZDOOM_SECRET_FLAG;
+                    }
+                    map.sectors.push(Sector {
+                        floor_height: number("heightfloor", 0.0) as i16,
+                        ceiling_height: number("heightceiling", 0.0) as i16,
+                        floor_texture: text("texturefloor"),
+                        ceiling_texture: text("textureceiling"),
+                        light_level: number("lightlevel", 160.0) as i16,
+                        special,
+                        tag: number("id", 0.0).max(0.0) as u16,
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn get_stats(&self) -> MapStats {
+        let mut stats = MapStats {
+            linedefs: self.linedefs.len().try_into().unwrap_or(u32::MAX),
+            sectors: self.sectors.len().try_into().unwrap_or(u32::MAX),
+            ..Default::default()
+        };
+        for thing in &self.things {
+            let (in_single_player, friendly) = match self.format {
+                MapFormat::Doom => {
+                    // Boom's "not in coop" flag only applies to coop netgames, so those things are
+                    // still in single player.
+                    let flags = thing.get_doom_flags();
+                    (
+                        flags & THING_FLAG_MULTIPLAYER == 0,
+                        flags & THING_FLAG_FRIENDLY != 0,
+                    )
+                }
+                MapFormat::Hexen | MapFormat::Udmf(_) => (
+                    thing.flags & HEXEN_THING_FLAG_SINGLE != 0,
+                    thing.flags & HEXEN_THING_FLAG_FRIENDLY != 0,
+                ),
+            };
+            if !in_single_player {
+                continue;
+            }
+            if MONSTER_THING_TYPES.contains(&thing.thing_type) {
+                if friendly {
+                    continue;
+                }
+                if thing.flags & THING_FLAG_EASY != 0 {
+                    stats.easy_monsters += 1;
+                }
+                if thing.flags & THING_FLAG_MEDIUM != 0 {
+                    stats.medium_monsters += 1;
+                }
+                if thing.flags & THING_FLAG_HARD != 0 {
+                    stats.hard_monsters += 1;
+                }
+            } else if ITEM_THING_TYPES.contains(&thing.thing_type)
+                && thing.flags & THING_FLAG_HARD != 0
+            {
+                stats.items += 1;
+            }
+        }
+        stats.secrets = self
+            .sectors
+            .iter()
+            .filter(|x| match self.format {
+                MapFormat::Doom => {
+                    x.special == SECTOR_SPECIAL_SECRET
+                        || x.special & SECTOR_SPECIAL_BOOM_SECRET_FLAG != 0
+                }
+                MapFormat::Hexen | MapFormat::Udmf(_) => {
+                    x.special & SECTOR_SPECIAL_ZDOOM_SECRET_FLAG != 0
+                }
+            })
+            .count()
+            .try_into()
+            .unwrap_or(u32::MAX);
+        stats
+    }
+
+    fn read_map_lump<R: Read + Seek>(
+        reader: &mut WadReader<R>,
+        map_lump_name: &str,
+        lump_name: &str,
+    ) -> Result<Vec<u8>, Report> {
+        let index = reader
+            .metadata
+            .get_map_lump_index(map_lump_name, lump_name)
+            .ok_or_else(|| eyre!("{} does not have a {} lump", map_lump_name, lump_name))?;
+        reader.read_lump(index)
+    }
+
+    /// Parses the blocks in a TEXTMAP lump, e.g. `thing { type = 3004; x = 64.0; }`, into their
+    /// type and properties. The keys are all lowercase, and top level assignments, like the
+    /// namespace, are skipped.
+    fn parse_textmap(contents: &str) -> Result<Vec<TextmapBlock>, Report> {
+        let invalid = || eyre!("The TEXTMAP lump is not valid UDMF");
+        let mut blocks = Vec::new();
+        let mut tokens = MapData::tokenize_textmap(contents).into_iter();
+        while let Some(token) = tokens.next() {
+            let identifier = match token {
+                TextToken::Word(word) => word.to_lowercase(),
+                _ => return Err(invalid()),
+            };
+            match tokens.next() {
+                Some(TextToken::Symbol('=')) => {
+                    tokens.next();
+                    if tokens.next() != Some(TextToken::Symbol(';')) {
+                        return Err(invalid());
+                    }
+                }
+                Some(TextToken::Symbol('{')) => {
+                    let mut block = HashMap::new();
+                    loop {
+                        let key = match tokens.next() {
+                            Some(TextToken::Symbol('}')) => break,
+                            Some(TextToken::Word(key)) => key.to_lowercase(),
+                            _ => return Err(invalid()),
+                        };
+                        if tokens.next() != Some(TextToken::Symbol('=')) {
+                            return Err(invalid());
+                        }
+                        let value = match tokens.next() {
+                            Some(TextToken::Word(value)) => value.to_lowercase(),
+                            Some(TextToken::Text(value)) => value,
+                            _ => return Err(invalid()),
+                        };
+                        if tokens.next() != Some(TextToken::Symbol(';')) {
+                            return Err(invalid());
+                        }
+                        block.insert(key, value);
+                    }
+                    blocks.push((identifier, block));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Splits a TEXTMAP lump into tokens. Unlike MAPINFO, `;` is a terminator rather than the start
+    /// of a comment, so the lump needs its own tokenizer.
+    fn tokenize_textmap(contents: &str) -> Vec<TextToken> {
+        let mut tokens = Vec::new();
+        let mut chars = contents.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    let mut text = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => {
+                                if let Some(escaped) = chars.next() {
+                                    text.push(escaped);
+                                }
+                            }
+                            _ => text.push(c),
+                        }
+                    }
+                    tokens.push(TextToken::Text(text));
+                }
+                '/' if chars.peek() == Some(&'/') => {
+                    while chars.peek().is_some_and(|x| *x != '\n') {
+                        chars.next();
+                    }
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    let mut previous = ' ';
+                    for c in chars.by_ref() {
+                        if previous == '*' && c == '/' {
+                            break;
+                        }
+                        previous = c;
+                    }
+                }
+                '{' | '}' | '=' | ';' => tokens.push(TextToken::Symbol(c)),
+                c if c.is_whitespace() => {}
+                _ => {
+                    let mut word = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || "{}=;\"/".contains(next) {
+                            break;
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+                    tokens.push(TextToken::Word(word));
+                }
+            }
+        }
+        tokens
+    }
+}
+
+impl MapStats {
+    /// Gets the statistics for a map.
+    ///
+    /// None are returned for a map that's missing any of the lumps its format requires, since that
+    /// would be an incomplete map that wouldn't load, and it's not worth failing an import over.
+    /// The same goes for a map that can't be decoded, like a malformed TEXTMAP, but a warning is
+    /// logged for that one.
+    pub fn from_wad<R: Read + Seek>(
+        reader: &mut WadReader<R>,
+        map_lump_name: &str,
+    ) -> Result<Option<MapStats>, Report> {
+        if !reader.is_map_complete(map_lump_name)? {
+            return Ok(None);
+        }
+        match MapData::from_wad(reader, map_lump_name) {
+            Ok(map) => Ok(Some(map.get_stats())),
+            Err(report) => {
+                warn!(
+                    "The statistics for {} can't be read: {}",
+                    map_lump_name, report
+                );
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl MapDefinitions {
+    /// Reads the map information from all the supported lumps in a WAD, which is played with the
+    /// IWAD with the given ID, if it's known.
+    pub fn from_wad<R: Read + Seek>(
+        reader: &mut WadReader<R>,
+        iwad: Option<&str>,
+    ) -> Result<MapDefinitions, Report> {
+        let indexes = reader
+            .metadata
+            .directory
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| MAP_DEFINITION_LUMP_NAMES.contains(&x.lump_name.as_str()))
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+        let mut lumps = Vec::new();
+        for index in indexes {
+            let lump_name = reader.metadata.directory[index].lump_name.clone();
+            lumps.push((lump_name, reader.read_lump(index)?));
+        }
+        Ok(MapDefinitions::from_lumps(&lumps, iwad))
+    }
+
+    /// Reads the map information from a list of lumps, in the order they appear in the WAD, or
+    /// package. Any lumps that don't define maps are ignored.
+    ///
+    /// A WAD can contain several of these lumps, to support different source ports, so they are
+    /// all read and merged together, in the order of precedence in `MAP_DEFINITION_LUMP_NAMES`.
+    /// If the same lump appears more than once, the last one wins, which is also what the source
+    /// ports do. The IWAD is only needed for a DEHACKED lump.
+    pub fn from_lumps(lumps: &[(String, Vec<u8>)], iwad: Option<&str>) -> MapDefinitions {
+        let mut definitions = MapDefinitions::default();
+        for lump_name in MAP_DEFINITION_LUMP_NAMES.iter() {
+            for (_, data) in lumps.iter().rev().filter(|(name, _)| name == lump_name) {
+                let contents = String::from_utf8_lossy(data);
+                let parsed = match *lump_name {
+                    "EMAPINFO" => MapDefinitions::parse_emapinfo(&contents),
+                    "DEHACKED" => MapDefinitions::parse_dehacked(&contents, iwad),
+                    _ => MapDefinitions::parse_mapinfo(&contents),
+                };
+                definitions.merge(parsed);
+            }
+        }
+        definitions
+    }
+
+    /// Parses the contents of a UMAPINFO, MAPINFO or ZMAPINFO lump.
+    ///
+    /// UMAPINFO and the newer ZDoom format are structurally the same, with the properties of each
+    /// map enclosed in braces. The older ZDoom format and the Hexen format put each property on its
+    /// own line after the map, without the braces. Hexen also uses numbers rather than lump names
+    /// to identify maps, so these get converted into the MAPxx format.
+    ///
+    /// Anything that isn't a map or an episode is skipped.
+    pub fn parse_mapinfo(contents: &str) -> MapDefinitions {
+        let lines = MapDefinitions::tokenize_mapinfo(contents);
+        let mut definitions = MapDefinitions::default();
+        let mut index = 0;
+        while index < lines.len() {
+            let line = &lines[index];
+            index += 1;
+            let keyword = match &line[0] {
+                TextToken::Word(word) => word.to_lowercase(),
+                _ => continue,
+            };
+            if !MAPINFO_TOP_LEVEL_KEYWORDS.contains(&keyword.as_str()) {
+                continue;
+            }
+            let properties = MapDefinitions::take_mapinfo_properties(&lines, &mut index);
+            let lump_name = match line.get(1) {
+                Some(TextToken::Word(word)) | Some(TextToken::Text(word)) => {
+                    MapDefinitions::get_mapinfo_lump_name(word)
+                }
+                _ => continue,
+            };
+            // In the ZDoom format, a name following `lookup` refers to an entry in the LANGUAGE
+            // lump, which isn't supported, so the name is ignored.
+            let name = match line.get(2) {
+                Some(TextToken::Text(text)) => Some(text.clone()),
+                _ => None,
+            };
+            match keyword.as_str() {
+                "map" => {
+                    let mut map = MapDefinition {
+                        lump_name,
+                        name,
+                        ..Default::default()
+                    };
+                    for (key, values) in properties {
+                        let value = values.first().cloned();
+                        match key.as_str() {
+                            "levelname" => map.name = value,
+                            "next" => {
+                                map.next = value.and_then(|x| MapDefinitions::get_next_map(&x))
+                            }
+                            "nextsecret" | "secretnext" => {
+                                map.next_secret =
+                                    value.and_then(|x| MapDefinitions::get_next_map(&x))
+                            }
+                            "partime" | "par" => map.par_time = value.and_then(|x| x.parse().ok()),
+                            // UMAPINFO defines the episode on its first map, with the values
+                            // being the menu graphic, the name and the key. The value can also be
+                            // `clear`, which doesn't define anything.
+                            "episode" if values.len() > 1 => {
+                                definitions.episodes.push(EpisodeDefinition {
+                                    map: map.lump_name.clone(),
+                                    name: values[1].clone(),
+                                    key: values.get(2).cloned(),
+                                });
+                            }
+                            _ => {}
+                        }
+                    }
+                    definitions.maps.push(map);
+                }
+                "episode" => {
+                    let mut name = name;
+                    let mut key = None;
+                    for (property, values) in properties {
+                        match property.as_str() {
+                            "name" => name = values.first().cloned(),
+                            "key" => key = values.first().cloned(),
+                            _ => {}
+                        }
+                    }
+                    if let Some(name) = name {
+                        definitions.episodes.push(EpisodeDefinition {
+                            map: lump_name,
+                            name,
+                            key,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        definitions
+    }
+
+    /// Parses the contents of an EMAPINFO lump, which is the format used by Eternity.
+    ///
+    /// Each map is a section, e.g. `[MAP01]`, with `key = value` properties below it. The values
+    /// don't need to be quoted.
+    pub fn parse_emapinfo(contents: &str) -> MapDefinitions {
+        let mut definitions = MapDefinitions::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.starts_with('[') && line.ends_with(']') {
+                definitions.maps.push(MapDefinition {
+                    lump_name: line[1..line.len() - 1].trim().to_uppercase(),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let map = match definitions.maps.last_mut() {
+                Some(map) => map,
+                None => continue,
+            };
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"').to_string();
+                match key.trim().to_lowercase().as_str() {
+                    "levelname" => map.name = Some(value),
+                    "partime" => map.par_time = value.parse().ok(),
+                    "nextlevel" => map.next = MapDefinitions::get_next_map(&value),
+                    "nextsecret" => map.next_secret = MapDefinitions::get_next_map(&value),
+                    _ => {}
+                }
+            }
+        }
+        definitions
+    }
+
+    /// Parses the map names and par times from a DEHACKED lump, for a WAD that's played with the
+    /// IWAD with the given ID.
+    ///
+    /// Doom II, Plutonia and TNT each have their own set of map names in the executable, and a
+    /// patch can replace the names in any of them, so only the set for the IWAD is used: `PHUSTR`
+    /// for Plutonia, `THUSTR` for TNT, and `HUSTR` for anything else, including an unknown IWAD.
+    /// The Doom maps are always in the `HUSTR` set.
+    ///
+    /// The names can be replaced in the `[STRINGS]` section of a BEX patch, or with a classic
+    /// `Text` block whose original text is one of the stock names. The names are prefixed with the
+    /// level number for the automap, e.g. "level 1: entryway", so the prefix is removed. The par
+    /// times come from the `[PARS]` section.
+    pub fn parse_dehacked(contents: &str, iwad: Option<&str>) -> MapDefinitions {
+        let (prefix, game) = match iwad.map(|x| x.to_uppercase()).as_deref() {
+            Some("PLUTONIA") => ("PHUSTR", Game::Plutonia),
+            Some("TNT") => ("THUSTR", Game::Tnt),
+            _ => ("HUSTR", Game::Doom2),
+        };
+        let mut definitions = MapDefinitions::default();
+        let mut section = String::new();
+        let mut lines = contents.lines();
+        while let Some(line) = lines.next() {
+            let mut line = line.trim().to_string();
+            if line.starts_with('[') {
+                section = line.to_uppercase();
+                continue;
+            }
+            if let Some(captures) = DEHACKED_TEXT_REGEX.captures(&line) {
+                // A classic block also ends the BEX section before it.
+                section.clear();
+                let (old_length, new_length) =
+                    match (captures[1].parse::<usize>(), captures[2].parse::<usize>()) {
+                        (Ok(old_length), Ok(new_length)) => (old_length, new_length),
+                        _ => continue,
+                    };
+                // The lengths count characters rather than lines, and either text can span more
+                // than one line, so the lines are read until there are enough characters.
+                let length = old_length.saturating_add(new_length);
+                let mut text: Vec<char> = Vec::new();
+                let mut text_lines = 0;
+                while text.len() < length {
+                    let next_line = match lines.next() {
+                        Some(next_line) => next_line,
+                        None => break,
+                    };
+                    if text_lines > 0 {
+                        text.push('\n');
+                    }
+                    text.extend(next_line.chars());
+                    text_lines += 1;
+                }
+                if text.len() < length {
+                    continue;
+                }
+                let old_text = text[..old_length].iter().collect::<String>();
+                if let Some(lump_name) = MapDefinitions::get_stock_map_lump_name(&old_text, game) {
+                    let new_text = text[old_length..length].iter().collect::<String>();
+                    definitions.set_dehacked_map_name(lump_name, &new_text);
+                }
+                continue;
+            }
+            // A value in the strings section can be continued on the next line with a backslash.
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some(next_line) => line.push_str(next_line.trim()),
+                    None => break,
+                }
+            }
+            match section.as_str() {
+                "[STRINGS]" => {
+                    let (key, value) = match line.split_once('=') {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    let captures = match DEHACKED_MAP_NAME_KEY_REGEX.captures(key.trim()) {
+                        Some(captures) => captures,
+                        None => continue,
+                    };
+                    let lump_name = match (captures.get(2), captures.get(3)) {
+                        (Some(number), _) if &captures[1] == prefix => {
+                            match number.as_str().parse::<u32>() {
+                                Ok(number) => format!("MAP{:02}", number),
+                                Err(_) => continue,
+                            }
+                        }
+                        (_, Some(episode_map)) if &captures[1] == "HUSTR" => {
+                            episode_map.as_str().to_string()
+                        }
+                        _ => continue,
+                    };
+                    definitions.set_dehacked_map_name(lump_name, value);
+                }
+                "[PARS]" => {
+                    let mut parts = line.split_whitespace();
+                    if !parts.next().is_some_and(|x| x.eq_ignore_ascii_case("par")) {
+                        continue;
+                    }
+                    let numbers = parts
+                        .map(|x| x.parse::<u32>())
+                        .collect::<Result<Vec<u32>, _>>();
+                    let (lump_name, par_time) = match numbers.as_deref() {
+                        Ok([map, par_time]) => (format!("MAP{:02}", map), *par_time),
+                        Ok([episode, map, par_time]) => {
+                            (format!("E{}M{}", episode, map), *par_time)
+                        }
+                        _ => continue,
+                    };
+                    match definitions.get_mut(&lump_name) {
+                        Some(map) => map.par_time = Some(par_time),
+                        None => definitions.maps.push(MapDefinition {
+                            lump_name,
+                            par_time: Some(par_time),
+                            ..Default::default()
+                        }),
+                    }
+                }
+                _ => {}
+            }
+        }
+        definitions
+    }
+
+    /// Gets the map for one of the stock names in the executable, e.g. "level 1: entryway", from
+    /// the set of names for the game. The Doom names can't be confused with any others, since
+    /// they're the only ones with the map in the prefix.
+    fn get_stock_map_lump_name(text: &str, game: Game) -> Option<String> {
+        let captures = DEHACKED_STOCK_MAP_NAME_REGEX.captures(text.trim())?;
+        match (captures.get(1), captures.get(2)) {
+            (Some(number), _) => {
+                let lump_name = format!("MAP{:02}", number.as_str().parse::<u32>().ok()?);
+                let stock_name = game.get_map_names()?.get(lump_name.as_str())?;
+                if stock_name.eq_ignore_ascii_case(captures[3].trim()) {
+                    Some(lump_name)
+                } else {
+                    None
+                }
+            }
+            (_, Some(episode_map)) => Some(episode_map.as_str().to_uppercase()),
+            _ => None,
+        }
+    }
+
+    /// Sets the name of a map from a DEHACKED lump, without the automap prefix. A later name for
+    /// the same map replaces an earlier one, which is what the source ports do.
+    fn set_dehacked_map_name(&mut self, lump_name: String, value: &str) {
+        let name = DEHACKED_MAP_NAME_PREFIX_REGEX
+            .replace(value.trim(), "")
+            .to_string();
+        if name.is_empty() {
+            return;
+        }
+        match self.get_mut(&lump_name) {
+            Some(map) => map.name = Some(name),
+            None => self.maps.push(MapDefinition {
+                lump_name,
+                name: Some(name),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn get(&self, lump_name: &str) -> Option<&MapDefinition> {
+        self.maps.iter().find(|x| x.lump_name == lump_name)
+    }
+
+    fn get_mut(&mut self, lump_name: &str) -> Option<&mut MapDefinition> {
+        self.maps.iter_mut().find(|x| x.lump_name == lump_name)
+    }
+
+    /// Merges definitions from a lump with a lower precedence.
+    ///
+    /// Any property that's already set is kept. Episodes are only taken from the other lump if
+    /// none have been defined yet, since it doesn't make sense to mix episodes from two lumps.
+    pub fn merge(&mut self, other: MapDefinitions) {
+        for other_map in other.maps {
+            match self.get_mut(&other_map.lump_name) {
+                Some(map) => {
+                    map.name = map.name.take().or(other_map.name);
+                    map.par_time = map.par_time.or(other_map.par_time);
+                    map.next = map.next.take().or(other_map.next);
+                    map.next_secret = map.next_secret.take().or(other_map.next_secret);
+                }
+                None => self.maps.push(other_map),
+            }
+        }
+        if self.episodes.is_empty() {
+            self.episodes = other.episodes;
+        }
+    }
+
+    /// Splits the contents of a MAPINFO lump into lines of tokens.
+    ///
+    /// Braces are always put on a line of their own, which makes it easy to find where a block
+    /// begins and ends. Comments can use `//`, `/* */` or, in the older formats, `;`.
+    fn tokenize_mapinfo(contents: &str) -> Vec<Vec<TextToken>> {
+        let mut lines: Vec<Vec<TextToken>> = Vec::new();
+        let mut line: Vec<TextToken> = Vec::new();
+        let mut chars = contents.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\n' => lines.push(std::mem::take(&mut line)),
+                '"' => {
+                    let mut text = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => {
+                                if let Some(escaped) = chars.next() {
+                                    text.push(escaped);
+                                }
+                            }
+                            _ => text.push(c),
+                        }
+                    }
+                    line.push(TextToken::Text(text));
+                }
+                '/' if chars.peek() == Some(&'/') => {
+                    while chars.peek().is_some_and(|x| *x != '\n') {
+                        chars.next();
+                    }
+                }
+                ';' => {
+                    while chars.peek().is_some_and(|x| *x != '\n') {
+                        chars.next();
+                    }
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    let mut previous = ' ';
+                    for c in chars.by_ref() {
+                        if previous == '*' && c == '/' {
+                            break;
+                        }
+                        previous = c;
+                    }
+                }
+                '{' | '}' => {
+                    lines.push(std::mem::take(&mut line));
+                    lines.push(vec![TextToken::Symbol(c)]);
+                }
+                '=' | ',' => line.push(TextToken::Symbol(c)),
+                c if c.is_whitespace() => {}
+                _ => {
+                    let mut word = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || "{}=,\";".contains(next) {
+                            break;
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+                    line.push(TextToken::Word(word));
+                }
+            }
+        }
+        lines.push(line);
+        lines.into_iter().filter(|x| !x.is_empty()).collect()
+    }
+
+    /// Takes the properties that follow a top level definition, and moves the index past them.
+    ///
+    /// Inside a block, a property is a key, optionally followed by `=` and a comma separated list
+    /// of values, and line breaks don't matter. Without a block, each property is a key followed
+    /// by its values on the same line, and the properties end at the next top level keyword.
+    ///
+    /// Each property is returned as its lowercase key, along with its values.
+    fn take_mapinfo_properties(
+        lines: &[Vec<TextToken>],
+        index: &mut usize,
+    ) -> Vec<(String, Vec<String>)> {
+        let mut properties = Vec::new();
+        if lines.get(*index) != Some(&vec![TextToken::Symbol('{')]) {
+            while let Some(line) = lines.get(*index) {
+                let key = match &line[0] {
+                    TextToken::Word(key) => key.to_lowercase(),
+                    _ => break,
+                };
+                if MAPINFO_TOP_LEVEL_KEYWORDS.contains(&key.as_str()) {
+                    break;
+                }
+                let values = line[1..]
+                    .iter()
+                    .filter_map(|x| match x {
+                        TextToken::Word(value) | TextToken::Text(value) => Some(value.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                properties.push((key, values));
+                *index += 1;
+            }
+            return properties;
+        }
+
+        // Nested blocks aren't used by any of the properties we're interested in, so they're
+        // skipped.
+        *index += 1;
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        while let Some(line) = lines.get(*index) {
+            *index += 1;
+            match &line[0] {
+                TextToken::Symbol('{') => depth += 1,
+                TextToken::Symbol('}') if depth == 0 => break,
+                TextToken::Symbol('}') => depth -= 1,
+                _ if depth == 0 => tokens.extend(line.iter()),
+                _ => {}
+            }
+        }
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            let key = match token {
+                TextToken::Word(key) => key.to_lowercase(),
+                _ => continue,
+            };
+            let mut values = Vec::new();
+            if tokens.next_if_eq(&&TextToken::Symbol('=')).is_some() {
+                while let Some(TextToken::Word(value)) | Some(TextToken::Text(value)) =
+                    tokens.next()
+                {
+                    values.push(value.clone());
+                    if tokens.next_if_eq(&&TextToken::Symbol(',')).is_none() {
+                        break;
+                    }
+                }
+            }
+            properties.push((key, values));
+        }
+        properties
+    }
+
+    /// Hexen identifies maps by number, so these are converted to the MAPxx format.
+    fn get_mapinfo_lump_name(value: &str) -> String {
+        match value.parse::<u32>() {
+            Ok(number) => format!("MAP{:02}", number),
+            Err(_) => value.to_uppercase(),
+        }
+    }
+
+    /// The next map can also be one of the special values for ending the game, like `EndGame1` or
+    /// `EndTitle`, which aren't maps, so they're ignored.
+    fn get_next_map(value: &str) -> Option<String> {
+        if value.is_empty() || value.to_lowercase().starts_with("end") {
+            return None;
+        }
+        Some(MapDefinitions::get_mapinfo_lump_name(value))
+    }
+}
+
+/// Reads the five single byte arguments for an action special in the Hexen format.
+fn read_hexen_args(reader: &mut impl Read) -> Result<[i32; 5], Report> {
+    let mut args = [0; 5];
+    reader.read_exact(&mut args)?;
+    Ok(args.map(i32::from))
+}
+
+/// Reads an 8 byte name, e.g. a texture name, which is padded with null bytes if it's shorter.
+fn read_lump_name(reader: &mut impl Read) -> Result<String, Report> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer)
+        .trim_end_matches(char::from(0))
+        .to_string())
+}
+
+#[cfg(test)]
+mod wadmetadata {
+    mod get_map_lump_names {
+        use super::super::WadMetadata;
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_return_the_map_markers() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                    ("LINEDEFS", b"linedefs"),
+                    ("E1M1", b""),
+                    ("THINGS", b"things"),
+                    ("D_E1M1", b"music"),
+                    ("MAP02", b"not a marker"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            let maps = metadata.get_map_lump_names();
+            assert_eq!(maps, vec!["MAP01", "E1M1"]);
+        }
+
+        #[test]
+        fn should_return_the_markers_for_hexen_and_udmf_maps() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b"script in the marker"),
+                    ("THINGS", b"things"),
+                    ("BEHAVIOR", b"acs"),
+                    ("MAP02", b""),
+                    ("TEXTMAP", b"namespace = \"zdoom\";"),
+                    ("ZNODES", b"nodes"),
+                    ("ENDMAP", b""),
+                    ("MAP03", b""),
+                    ("D_RUNNIN", b"music"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            let maps = metadata.get_map_lump_names();
+            assert_eq!(maps, vec!["MAP01", "MAP02"]);
+        }
+
+        #[test]
+        fn should_return_markers_with_any_name() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("START", b""),
+                    ("TEXTMAP", b"namespace = \"zdoom\";"),
+                    ("ENDMAP", b""),
+                    ("MAP100", b""),
+                    ("THINGS", b"things"),
+                    ("E5M1", b""),
+                    ("THINGS", b"things"),
+                    ("E1M10", b""),
+                    ("THINGS", b"things"),
+                    ("lower", b""),
+                    ("THINGS", b"things"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            let maps = metadata.get_map_lump_names();
+            assert_eq!(maps, vec!["START", "MAP100", "E5M1", "E1M10"]);
+        }
+
+        #[test]
+        fn should_return_the_last_of_the_markers_with_the_same_name() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                    ("MAP02", b""),
+                    ("THINGS", b"things"),
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            let maps = metadata.get_map_lump_names();
+            assert_eq!(maps, vec!["MAP02", "MAP01"]);
+        }
+    }
+
+    mod get_lump_index {
+        use super::super::WadMetadata;
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_return_the_last_lump_with_the_name() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("DEHACKED", b"first"),
+                    ("PLAYPAL", b""),
+                    ("DEHACKED", b"second"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            assert_eq!(metadata.get_lump_index("DEHACKED"), Some(2));
+            assert_eq!(metadata.get_lump_index("dehacked"), Some(2));
+            assert_eq!(metadata.get_lump_index("COLORMAP"), None);
+        }
+    }
+
+    mod get_map_lump_range {
+        use super::super::WadMetadata;
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_return_the_lumps_following_the_marker() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                    ("LINEDEFS", b"linedefs"),
+                    ("SECTORS", b"sectors"),
+                    ("D_RUNNIN", b"music"),
+                    ("MAP02", b""),
+                    ("THINGS", b"things"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            assert_eq!(metadata.get_map_lump_range("MAP01"), Some(1..4));
+            assert_eq!(metadata.get_map_lump_range("MAP02"), Some(6..7));
+            assert_eq!(metadata.get_map_lump_range("MAP03"), None);
+        }
+
+        #[test]
+        fn should_return_the_lumps_following_the_last_marker_with_the_name() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                    ("LINEDEFS", b"linedefs"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            assert_eq!(metadata.get_map_lump_range("MAP01"), Some(3..5));
+        }
+
+        #[test]
+        fn should_ignore_a_later_lump_with_the_name_that_is_not_a_marker() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("START", b""),
+                    ("THINGS", b"things"),
+                    ("LINEDEFS", b"linedefs"),
+                    ("START", b"start"),
+                    ("CREDIT", b"credit"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            assert_eq!(metadata.get_map_lump_range("START"), Some(1..3));
+        }
+
+        #[test]
+        fn should_return_the_lumps_up_to_endmap_for_a_udmf_map() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("TEXTMAP", b"namespace = \"zdoom\";"),
+                    ("ZNODES", b"nodes"),
+                    ("ENDMAP", b""),
+                    ("MAP02", b""),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            assert_eq!(metadata.get_map_lump_range("MAP01"), Some(1..4));
+        }
+    }
+
+    mod get_map_lump_index {
+        use super::super::WadMetadata;
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_only_find_lumps_belonging_to_the_map() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"map01 things"),
+                    ("MAP02", b""),
+                    ("THINGS", b"map02 things"),
+                    ("LINEDEFS", b"map02 linedefs"),
+                ],
+            );
+            let metadata = WadMetadata::from_path(wad.path()).unwrap();
+            assert_eq!(metadata.get_map_lump_index("MAP01", "THINGS"), Some(1));
+            assert_eq!(metadata.get_map_lump_index("MAP02", "THINGS"), Some(3));
+            assert_eq!(metadata.get_map_lump_index("MAP01", "LINEDEFS"), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod wadreader {
+    mod read_lump {
+        use super::super::WadReader;
+        use std::io::Cursor;
+        use test_helpers::wad::{build_wad, write_wad};
+
+        #[test]
+        fn should_read_the_lump_data() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"things"),
+                    ("LINEDEFS", b"linedefs"),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            assert_eq!(reader.read_lump(0).unwrap(), b"");
+            assert_eq!(reader.read_lump(2).unwrap(), b"linedefs");
+            assert_eq!(reader.read_lump(1).unwrap(), b"things");
+        }
+
+        #[test]
+        fn should_read_the_lump_data_from_memory() {
+            let data = build_wad("PWAD", &[("DEHACKED", b"Patch File for DeHackEd v3.0")]);
+            let mut reader = WadReader::new("embedded.wad", Cursor::new(data)).unwrap();
+            assert_eq!(reader.metadata.header.wad_type, "PWAD");
+            assert_eq!(
+                reader.read_lump(0).unwrap(),
+                b"Patch File for DeHackEd v3.0"
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_index_outside_the_directory() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(wad.path(), "PWAD", &[("MAP01", b"")]);
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let result = reader.read_lump(1);
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "There is no lump at index 1. The directory has 1 entries."
+            );
+        }
+    }
+
+    mod get_lump_reader {
+        use super::super::WadReader;
+        use std::io::Read;
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_read_the_lump_in_chunks() {
+            let music = vec![7u8; 10000];
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[("D_RUNNIN", &music), ("ENDOOM", b"endoom")],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let mut lump_reader = reader.get_lump_reader(0).unwrap();
+            let mut chunk = [0u8; 4096];
+            let mut total = 0;
+            loop {
+                let read = lump_reader.read(&mut chunk).unwrap();
+                if read == 0 {
+                    break;
+                }
+                assert!(chunk[..read].iter().all(|x| *x == 7));
+                total += read;
+            }
+            assert_eq!(total, 10000);
+        }
+    }
+
+    mod get_map_format {
+        use super::super::{MapFormat, WadReader};
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_detect_the_format_from_the_map_lumps() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b""),
+                    ("SECTORS", b""),
+                    ("MAP02", b""),
+                    ("THINGS", b""),
+                    ("BEHAVIOR", b""),
+                    ("MAP03", b""),
+                    (
+                        "TEXTMAP",
+                        b"// Comment\nNamespace = \"Eternity\";\nthing {}",
+                    ),
+                    ("ENDMAP", b""),
+                    ("MAP04", b""),
+                    ("TEXTMAP", b"thing {}"),
+                    ("ENDMAP", b""),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            assert_eq!(reader.get_map_format("MAP01").unwrap(), MapFormat::Doom);
+            assert_eq!(reader.get_map_format("MAP02").unwrap(), MapFormat::Hexen);
+            assert_eq!(
+                reader.get_map_format("MAP03").unwrap(),
+                MapFormat::Udmf("eternity".to_string())
+            );
+            assert_eq!(
+                reader.get_map_format("MAP04").unwrap(),
+                MapFormat::Udmf(String::new())
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod mapdata {
+    mod from_wad {
+        use super::super::{
+            Linedef, MapData, MapFormat, Sector, Sidedef, Thing, Vertex, WadReader,
+        };
+        use test_helpers::wad::{
+            build_hexen_square_room, build_hexen_things, build_sectors, build_square_room,
+            build_things, write_wad,
+        };
+
+        #[test]
+        fn should_decode_the_map_lumps() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            let things = build_things(&[(1, 7), (3004, 4)]);
+            let (linedefs, sidedefs, vertexes) = build_square_room(256);
+            let sectors = build_sectors(&[9]);
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", &things),
+                    ("LINEDEFS", &linedefs),
+                    ("SIDEDEFS", &sidedefs),
+                    ("VERTEXES", &vertexes),
+                    ("SECTORS", &sectors),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let map = MapData::from_wad(&mut reader, "MAP01").unwrap();
+            assert_eq!(
+                map.things,
+                vec![
+                    Thing {
+                        angle: 90,
+                        thing_type: 1,
+                        flags: 7,
+                        ..Default::default()
+                    },
+                    Thing {
+                        angle: 90,
+                        thing_type: 3004,
+                        flags: 4,
+                        ..Default::default()
+                    },
+                ]
+            );
+            assert_eq!(map.linedefs.len(), 4);
+            assert_eq!(
+                map.linedefs[3],
+                Linedef {
+                    start_vertex: 3,
+                    end_vertex: 0,
+                    flags: 1,
+                    special: 0,
+                    tag: 0,
+                    args: [0; 5],
+                    front_sidedef: 3,
+                    back_sidedef: 0xFFFF,
+                }
+            );
+            assert_eq!(
+                map.sidedefs[0],
+                Sidedef {
+                    x_offset: 0,
+                    y_offset: 0,
+                    upper_texture: "-".to_string(),
+                    lower_texture: "-".to_string(),
+                    middle_texture: "STARTAN3".to_string(),
+                    sector: 0,
+                }
+            );
+            assert_eq!(map.vertexes[2], Vertex { x: 256, y: 256 });
+            assert_eq!(
+                map.sectors,
+                vec![Sector {
+                    floor_height: 0,
+                    ceiling_height: 128,
+                    floor_texture: "FLOOR4_8".to_string(),
+                    ceiling_texture: "CEIL3_5".to_string(),
+                    light_level: 160,
+                    special: 9,
+                    tag: 0,
+                }]
+            );
+        }
+
+        #[test]
+        fn should_decode_a_hexen_format_map() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            let things = build_hexen_things(&[(1, 0x707), (3004, 0x104)]);
+            let (linedefs, sidedefs, vertexes) = build_hexen_square_room(256);
+            let sectors = build_sectors(&[0]);
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", &things),
+                    ("LINEDEFS", &linedefs),
+                    ("SIDEDEFS", &sidedefs),
+                    ("VERTEXES", &vertexes),
+                    ("SECTORS", &sectors),
+                    ("BEHAVIOR", b"ACS\0"),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let map = MapData::from_wad(&mut reader, "MAP01").unwrap();
+            assert_eq!(map.format, MapFormat::Hexen);
+            assert_eq!(
+                map.things[1],
+                Thing {
+                    angle: 90,
+                    thing_type: 3004,
+                    flags: 0x104,
+                    ..Default::default()
+                }
+            );
+            assert_eq!(
+                map.linedefs[0],
+                Linedef {
+                    start_vertex: 0,
+                    end_vertex: 1,
+                    flags: 1,
+                    special: 80,
+                    tag: 0,
+                    args: [1, 0, 0, 0, 0],
+                    front_sidedef: 0,
+                    back_sidedef: 0xFFFF,
+                }
+            );
+            assert_eq!(map.sidedefs.len(), 4);
+            assert_eq!(map.vertexes[2], Vertex { x: 256, y: 256 });
+            assert_eq!(map.sectors.len(), 1);
+        }
+
+        #[test]
+        fn should_decode_a_udmf_map() {
+            let textmap = br#"
+                // Written by hand.
+                namespace = "zdoom";
+                thing { x = 32.0; y = 64.5; angle = 90; type = 3004;
+                        skill1 = true; skill4 = true; single = true; friend = true; }
+                vertex { x = 0.0; y = 0.0; }
+                vertex { x = 0.0; y = 128.0; }
+                linedef { v1 = 0; v2 = 1; sidefront = 0; blocking = true; twosided = false;
+                          special = 80; arg0 = 5; id = 3; }
+                sidedef { sector = 0; texturemiddle = "STARTAN3"; offsetx = 8; }
+                sector { heightceiling = 128; texturefloor = "FLOOR4_8";
+                         textureceiling = "CEIL3_5"; secret = true; /* Boom style */ }
+            "#;
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[("MAP01", b""), ("TEXTMAP", textmap), ("ENDMAP", b"")],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let map = MapData::from_wad(&mut reader, "MAP01").unwrap();
+            assert_eq!(map.format, MapFormat::Udmf("zdoom".to_string()));
+            assert_eq!(
+                map.things,
+                vec![Thing {
+                    x: 32,
+                    y: 64,
+                    angle: 90,
+                    thing_type: 3004,
+                    flags: 0x2105,
+                    ..Default::default()
+                }]
+            );
+            assert_eq!(
+                map.linedefs,
+                vec![Linedef {
+                    start_vertex: 0,
+                    end_vertex: 1,
+                    flags: 1,
+                    special: 80,
+                    tag: 3,
+                    args: [5, 0, 0, 0, 0],
+                    front_sidedef: 0,
+                    back_sidedef: 0xFFFF,
+                }]
+            );
+            assert_eq!(
+                map.sidedefs,
+                vec![Sidedef {
+                    x_offset: 8,
+                    y_offset: 0,
+                    upper_texture: "-".to_string(),
+                    lower_texture: "-".to_string(),
+                    middle_texture: "STARTAN3".to_string(),
+                    sector: 0,
+                }]
+            );
+            assert_eq!(map.vertexes[1], Vertex { x: 0, y: 128 });
+            assert_eq!(
+                map.sectors,
+                vec![Sector {
+                    floor_height: 0,
+                    ceiling_height: 128,
+                    floor_texture: "FLOOR4_8".to_string(),
+                    ceiling_texture: "CEIL3_5".to_string(),
+                    light_level: 160,
+                    special: 0x400,
+                    tag: 0,
+                }]
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_invalid_textmap() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("TEXTMAP", b"namespace = \"zdoom\"; thing { x 32; }"),
+                    ("ENDMAP", b""),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let result = MapData::from_wad(&mut reader, "MAP01");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "The TEXTMAP lump is not valid UDMF"
+            );
+        }
+
+        #[test]
+        fn should_ignore_a_partial_record_at_the_end_of_a_lump() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            let mut things = build_things(&[(1, 7)]);
+            things.extend_from_slice(&[1, 2, 3]);
+            let (linedefs, sidedefs, vertexes) = build_square_room(256);
+            let sectors = build_sectors(&[0]);
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", &things),
+                    ("LINEDEFS", &linedefs),
+                    ("SIDEDEFS", &sidedefs),
+                    ("VERTEXES", &vertexes),
+                    ("SECTORS", &sectors),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let map = MapData::from_wad(&mut reader, "MAP01").unwrap();
+            assert_eq!(map.things.len(), 1);
+        }
+
+        #[test]
+        fn should_return_an_error_when_a_lump_is_missing() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            let things = build_things(&[(1, 7)]);
+            write_wad(wad.path(), "PWAD", &[("MAP01", b""), ("THINGS", &things)]);
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let result = MapData::from_wad(&mut reader, "MAP01");
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "MAP01 does not have a LINEDEFS lump"
+            );
+        }
+    }
+
+    mod get_stats {
+        use super::super::{MapData, MapFormat, MapStats, Sector, Thing};
+
+        fn thing(thing_type: u16, flags: u16) -> Thing {
+            Thing {
+                thing_type,
+                flags,
+                ..Default::default()
+            }
+        }
+
+        fn sector(special: u16) -> Sector {
+            Sector {
+                floor_height: 0,
+                ceiling_height: 128,
+                floor_texture: "FLOOR4_8".to_string(),
+                ceiling_texture: "CEIL3_5".to_string(),
+                light_level: 160,
+                special,
+                tag: 0,
+            }
+        }
+
+        #[test]
+        fn should_count_the_monsters_for_each_skill() {
+            let map = MapData {
+                format: MapFormat::Doom,
+                things: vec![
+                    thing(1, 7),       // Player 1 start
+                    thing(3004, 7),    // Zombieman on all skills
+                    thing(3001, 6),    // Imp on HMP and UV
+                    thing(3003, 4),    // Baron on UV
+                    thing(9, 1),       // Shotgun guy on HNTR
+                    thing(16, 0x17),   // Cyberdemon in multiplayer only
+                    thing(3002, 0x87), // Friendly demon
+                    thing(58, 0x47),   // Spectre not in coop, which is still in single player
+                    thing(58, 0x147),  // Spectre with junk flags, so the Boom flags are ignored
+                    thing(2035, 7),    // Barrel
+                ],
+                linedefs: Vec::new(),
+                sidedefs: Vec::new(),
+                vertexes: Vec::new(),
+                sectors: Vec::new(),
+            };
+            let stats = map.get_stats();
+            assert_eq!(stats.easy_monsters, 4);
+            assert_eq!(stats.medium_monsters, 4);
+            assert_eq!(stats.hard_monsters, 5);
+        }
+
+        #[test]
+        fn should_not_count_lost_souls() {
+            let map = MapData {
+                format: MapFormat::Doom,
+                things: vec![
+                    thing(3004, 7), // Zombieman on all skills
+                    thing(3006, 7), // Lost Soul on all skills
+                ],
+                linedefs: Vec::new(),
+                sidedefs: Vec::new(),
+                vertexes: Vec::new(),
+                sectors: Vec::new(),
+            };
+            let stats = map.get_stats();
+            assert_eq!(stats.easy_monsters, 1);
+            assert_eq!(stats.medium_monsters, 1);
+            assert_eq!(stats.hard_monsters, 1);
+        }
+
+        #[test]
+        fn should_count_the_items_secrets_lines_and_sectors() {
+            let map = MapData {
+                format: MapFormat::Doom,
+                things: vec![
+                    thing(2013, 7), // Soulsphere
+                    thing(2014, 4), // Health bonus on UV
+                    thing(2014, 1), // Health bonus on HNTR only
+                    thing(2001, 7), // Shotgun, which doesn't count
+                    thing(2025, 7), // Radiation suit, which doesn't count
+                ],
+                linedefs: Vec::new(),
+                sidedefs: Vec::new(),
+                vertexes: Vec::new(),
+                sectors: vec![sector(0), sector(9), sector(0x80 | 0x08), sector(8)],
+            };
+            assert_eq!(
+                map.get_stats(),
+                MapStats {
+                    items: 2,
+                    secrets: 2,
+                    sectors: 4,
+                    ..Default::default()
+                }
+            );
+        }
+
+        #[test]
+        fn should_use_the_hexen_flags_for_hexen_and_udmf_maps() {
+            let things = vec![
+                thing(3004, 0x107),  // Zombieman on all skills
+                thing(3001, 0x706),  // Imp on HMP and UV in all modes
+                thing(3003, 0x604),  // Baron in multiplayer only
+                thing(3002, 0x2107), // Friendly demon
+                thing(2013, 0x104),  // Soulsphere
+            ];
+            // Sector 9 isn't a secret in the Hexen format, it's a ZDoom damaging sector.
+            let sectors = vec![sector(9), sector(0x400), sector(0x400 | 0x41)];
+            for format in [MapFormat::Hexen, MapFormat::Udmf("zdoom".to_string())] {
+                let map = MapData {
+                    format,
+                    things: things.clone(),
+                    linedefs: Vec::new(),
+                    sidedefs: Vec::new(),
+                    vertexes: Vec::new(),
+                    sectors: sectors.clone(),
+                };
+                assert_eq!(
+                    map.get_stats(),
+                    MapStats {
+                        easy_monsters: 1,
+                        medium_monsters: 2,
+                        hard_monsters: 2,
+                        items: 1,
+                        secrets: 2,
+                        linedefs: 0,
+                        sectors: 3,
+                    }
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod mapstats {
+    mod from_wad {
+        use super::super::{MapStats, WadReader};
+        use test_helpers::wad::{
+            build_hexen_square_room, build_hexen_things, build_sectors, build_square_room,
+            build_things, write_wad,
+        };
+
+        #[test]
+        fn should_return_the_stats_for_a_doom_format_map() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            let things = build_things(&[(1, 7), (3004, 7), (3001, 4)]);
+            let (linedefs, sidedefs, vertexes) = build_square_room(256);
+            let sectors = build_sectors(&[9]);
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", &things),
+                    ("LINEDEFS", &linedefs),
+                    ("SIDEDEFS", &sidedefs),
+                    ("VERTEXES", &vertexes),
+                    ("SECTORS", &sectors),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let stats = MapStats::from_wad(&mut reader, "MAP01").unwrap();
+            assert_eq!(
+                stats,
+                Some(MapStats {
+                    easy_monsters: 1,
+                    medium_monsters: 1,
+                    hard_monsters: 2,
+                    items: 0,
+                    secrets: 1,
+                    linedefs: 4,
+                    sectors: 1,
+                })
+            );
+        }
+
+        #[test]
+        fn should_return_the_stats_for_hexen_and_udmf_maps() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            let things = build_hexen_things(&[(1, 0x707), (3004, 0x107)]);
+            let (linedefs, sidedefs, vertexes) = build_hexen_square_room(256);
+            let sectors = build_sectors(&[0x400]);
+            let textmap = br#"
+                namespace = "zdoom";
+                thing { type = 3004; skill4 = true; single = true; }
+                thing { type = 3001; skill4 = true; coop = true; }
+            "#;
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", &things),
+                    ("LINEDEFS", &linedefs),
+                    ("SIDEDEFS", &sidedefs),
+                    ("VERTEXES", &vertexes),
+                    ("SECTORS", &sectors),
+                    ("BEHAVIOR", b"ACS\0"),
+                    ("MAP02", b""),
+                    ("TEXTMAP", textmap),
+                    ("ENDMAP", b""),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            assert_eq!(
+                MapStats::from_wad(&mut reader, "MAP01").unwrap(),
+                Some(MapStats {
+                    easy_monsters: 1,
+                    medium_monsters: 1,
+                    hard_monsters: 1,
+                    items: 0,
+                    secrets: 1,
+                    linedefs: 4,
+                    sectors: 1,
+                })
+            );
+            assert_eq!(
+                MapStats::from_wad(&mut reader, "MAP02").unwrap(),
+                Some(MapStats {
+                    hard_monsters: 1,
+                    ..Default::default()
+                })
+            );
+        }
+
+        #[test]
+        fn should_not_return_stats_for_incomplete_hexen_or_udmf_maps() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    ("THINGS", b"hexen things"),
+                    ("BEHAVIOR", b"acs"),
+                    ("MAP02", b""),
+                    ("TEXTMAP", b"namespace = \"zdoom\";"),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            assert_eq!(MapStats::from_wad(&mut reader, "MAP01").unwrap(), None);
+            assert_eq!(MapStats::from_wad(&mut reader, "MAP02").unwrap(), None);
+        }
+
+        #[test]
+        fn should_not_return_stats_for_a_map_with_a_truncated_textmap() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("MAP01", b""),
+                    (
+                        "TEXTMAP",
+                        b"namespace = \"zdoom\";\nthing\n{\nx = 32.0;\ny = 3",
+                    ),
+                    ("ENDMAP", b""),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            assert_eq!(MapStats::from_wad(&mut reader, "MAP01").unwrap(), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod mapdefinitions {
+    mod parse_mapinfo {
+        use super::super::{EpisodeDefinition, MapDefinition, MapDefinitions};
+
+        #[test]
+        fn should_parse_umapinfo() {
+            let contents = r#"
+                // The first episode.
+                MAP MAP01
+                {
+                    levelname = "The Beginning"
+                    episode = "M_EPI1", "First Steps", "f"
+                    next = "MAP02"
+                    nextsecret = "MAP31"
+                    partime = 90
+                    /* Multi-line
+                       comment */
+                    music = "D_RUNNIN"
+                }
+
+                MAP MAP02
+                {
+                    levelname = "The End"
+                    endgame = true
+                }
+            "#;
+            let definitions = MapDefinitions::parse_mapinfo(contents);
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "MAP01".to_string(),
+                        name: Some("The Beginning".to_string()),
+                        par_time: Some(90),
+                        next: Some("MAP02".to_string()),
+                        next_secret: Some("MAP31".to_string()),
+                    },
+                    MapDefinition {
+                        lump_name: "MAP02".to_string(),
+                        name: Some("The End".to_string()),
+                        ..Default::default()
+                    },
+                ]
+            );
+            assert_eq!(
+                definitions.episodes,
+                vec![EpisodeDefinition {
+                    map: "MAP01".to_string(),
+                    name: "First Steps".to_string(),
+                    key: Some("f".to_string()),
+                }]
+            );
+        }
+
+        #[test]
+        fn should_parse_a_block_without_line_breaks() {
+            let contents =
+                "MAP MAP07 { levelname = \"Dead Simple\" partime = 60 intertext = \"a\", \"b\" next = \"MAP08\" }";
+            let definitions = MapDefinitions::parse_mapinfo(contents);
+            assert_eq!(
+                definitions.maps,
+                vec![MapDefinition {
+                    lump_name: "MAP07".to_string(),
+                    name: Some("Dead Simple".to_string()),
+                    par_time: Some(60),
+                    next: Some("MAP08".to_string()),
+                    next_secret: None,
+                }]
+            );
+        }
 
-pub struct WadMetadata {
-    pub header: WadHeader,
-    pub directory: Vec<WadDirectoryEntry>,
-}
+        #[test]
+        fn should_parse_the_new_zdoom_format() {
+            let contents = r#"
+                clearepisodes
+                episode MAP01
+                {
+                    name = "Hell Revealed"
+                    key = "h"
+                }
 
-/// Structure that describes a map in the game.
-///
-/// The number will either be in MAPxx, for DOOM2, or ExMx for DOOM. For example, MAP03, or E1M3.
-/// The map will also have a name. Newer WADs contain MAPINFO lumps in the WAD, but the original
-/// IWADs did not contain these.
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct MapInfo {
-    /// The map number. Either MAPxx or ExMx.
-    pub number: String,
-    /// The name of the map.
-    pub name: String,
-    /// The value to be used with the `-warp` argument on source ports. For DOOM2, this will be the
-    /// xx part of the number, e.g., for MAP01, `warp` will be 1, and for MAP12, it will be 12. For
-    /// DOOM, E1M1 will turn into "1 1". It's due to the latter that we store this as a string
-    /// rather than an integer.
-    pub warp: String,
-}
+                gameinfo
+                {
+                    titlemusic = "D_DM2TTL"
+                }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct WadEntry {
-    pub id: String,
-    pub name: String,
-    pub title: String,
-    pub release_date: String,
-    pub author: String,
-    pub maps: Vec<MapInfo>,
-}
+                map MAP01 "Title Screen Hall" {
+                    next = "MAP02"
+                    secretnext = "MAP31"
+                    par = 120
+                    sky1 = "SKY1", 0
+                }
 
-impl WadMetadata {
-    pub fn from_path(wad_path: impl AsRef<Path>) -> Result<WadMetadata, Report> {
-        let mut file = File::open(&wad_path)?;
-        let header = WadMetadata::read_header(&wad_path, &mut file)?;
-        let directory = WadMetadata::read_directory(&mut file, &header)?;
-        Ok(WadMetadata { header, directory })
-    }
+                map MAP02 lookup "HUSTR_2"
+                {
+                    next = EndGame1
+                }
+            "#;
+            let definitions = MapDefinitions::parse_mapinfo(contents);
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "MAP01".to_string(),
+                        name: Some("Title Screen Hall".to_string()),
+                        par_time: Some(120),
+                        next: Some("MAP02".to_string()),
+                        next_secret: Some("MAP31".to_string()),
+                    },
+                    MapDefinition {
+                        lump_name: "MAP02".to_string(),
+                        ..Default::default()
+                    },
+                ]
+            );
+            assert_eq!(
+                definitions.episodes,
+                vec![EpisodeDefinition {
+                    map: "MAP01".to_string(),
+                    name: "Hell Revealed".to_string(),
+                    key: Some("h".to_string()),
+                }]
+            );
+        }
 
-    fn read_header(
-        wad_path: &impl AsRef<Path>,
-        wad_file: &mut impl Read,
-    ) -> Result<WadHeader, Report> {
-        let mut header = [0; 12];
-        wad_file.read_exact(&mut header)?;
-        let wad_type = String::from_utf8(header[..4].to_vec())?;
-        if wad_type != "IWAD" && wad_type != "PWAD" {
-            return Err(
-                eyre!(format!("Failed to parse {}", wad_path.as_ref().display()))
-                    .suggestion("This file is likely not a WAD file"),
+        #[test]
+        fn should_parse_the_old_zdoom_format() {
+            let contents = r#"
+                map E1M1 "Into the Void"
+                next E1M2
+                secretnext E1M9
+                par 45
+                music D_E1M1
+
+                map E1M2 "Deeper"
+                next E1M3
+            "#;
+            let definitions = MapDefinitions::parse_mapinfo(contents);
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "E1M1".to_string(),
+                        name: Some("Into the Void".to_string()),
+                        par_time: Some(45),
+                        next: Some("E1M2".to_string()),
+                        next_secret: Some("E1M9".to_string()),
+                    },
+                    MapDefinition {
+                        lump_name: "E1M2".to_string(),
+                        name: Some("Deeper".to_string()),
+                        next: Some("E1M3".to_string()),
+                        ..Default::default()
+                    },
+                ]
             );
         }
-        let mut cursor = Cursor::new(header[4..].to_vec());
-        let directory_entries = cursor.read_u32::<LittleEndian>()?;
-        let directory_offset = cursor.read_u32::<LittleEndian>()?;
-        Ok(WadHeader {
-            wad_type,
-            directory_entries,
-            directory_offset,
-        })
-    }
 
-    fn read_directory(
-        wad_file: &mut (impl Read + std::io::Seek),
-        header: &WadHeader,
-    ) -> Result<Vec<WadDirectoryEntry>, Report> {
-        let capacity = header.directory_entries * DIRECTORY_ENTRY_SIZE;
-        let mut directory_buf: Vec<u8> = Vec::with_capacity(capacity.try_into()?);
-        wad_file.seek(SeekFrom::Start(header.directory_offset.into()))?;
-        wad_file.read_to_end(&mut directory_buf)?;
+        #[test]
+        fn should_parse_the_hexen_format() {
+            let contents = r#"
+                ; Hexen uses numbers for the maps.
+                map 1 "WINNOWING HALL"
+                warptrans 1
+                next 2
+                cluster 1
+                sky1 SKY2 0
 
-        let mut directory: Vec<WadDirectoryEntry> = Vec::new();
-        let mut cursor = Cursor::new(directory_buf);
-        let mut entries_read = 0;
-        while entries_read < header.directory_entries {
-            let lump_offset = cursor.read_u32::<LittleEndian>()?;
-            let lump_size = cursor.read_u32::<LittleEndian>()?;
-            let mut buffer = [0; 8];
-            cursor.read_exact(&mut buffer)?;
-            let lump_name = String::from_utf8(buffer.to_vec())?;
-            let lump_name = lump_name.trim_matches(char::from(0)).to_string(); // Strip UTF-8 terminator.
-            directory.push(WadDirectoryEntry {
-                lump_offset,
-                lump_size,
-                lump_name,
-            });
-            entries_read += 1;
+                map 2 "SEVEN PORTALS"
+                warptrans 2
+                next 3
+            "#;
+            let definitions = MapDefinitions::parse_mapinfo(contents);
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "MAP01".to_string(),
+                        name: Some("WINNOWING HALL".to_string()),
+                        next: Some("MAP02".to_string()),
+                        ..Default::default()
+                    },
+                    MapDefinition {
+                        lump_name: "MAP02".to_string(),
+                        name: Some("SEVEN PORTALS".to_string()),
+                        next: Some("MAP03".to_string()),
+                        ..Default::default()
+                    },
+                ]
+            );
         }
-        Ok(directory)
     }
-}
 
-impl MapInfo {
-    pub fn new(number: String, name: String) -> Result<MapInfo, Report> {
-        ensure!(
-            !number.is_empty(),
-            "A number must be provided for the map. It should be in the DOOM or DOOM2 format."
-        );
-        ensure!(
-            MapInfo::is_valid_map_number(&number),
-            "The map number must be in the DOOM or DOOM2 format. Valid values are ExMx or MAPxx."
-        );
-        ensure!(!name.is_empty(), "A name must be provided for the map.");
+    mod parse_emapinfo {
+        use super::super::{MapDefinition, MapDefinitions};
 
-        let warp: String;
-        if DOOM2_FORMAT_REGEX.is_match(&number) {
-            let num_part = &number[3..];
-            if num_part.starts_with('0') {
-                warp = String::from(num_part.chars().nth(1).unwrap());
-            } else {
-                warp = String::from(num_part);
-            }
-        } else {
-            warp = format!(
-                "{} {}",
-                number.chars().nth(1).unwrap(),
-                number.chars().nth(3).unwrap()
+        #[test]
+        fn should_parse_the_map_sections() {
+            let contents = "[MAP01]\n\
+                            levelname = Sunder Gate\n\
+                            partime = 60\n\
+                            nextlevel = MAP02\n\
+                            nextsecret = MAP31\n\
+                            music = RUNNIN\n\
+                            \n\
+                            [map02]\n\
+                            levelname = \"Quoted Name\"\n";
+            let definitions = MapDefinitions::parse_emapinfo(contents);
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "MAP01".to_string(),
+                        name: Some("Sunder Gate".to_string()),
+                        par_time: Some(60),
+                        next: Some("MAP02".to_string()),
+                        next_secret: Some("MAP31".to_string()),
+                    },
+                    MapDefinition {
+                        lump_name: "MAP02".to_string(),
+                        name: Some("Quoted Name".to_string()),
+                        ..Default::default()
+                    },
+                ]
             );
         }
-        Ok(MapInfo { number, name, warp })
     }
 
-    pub fn is_valid_map_number(number: &str) -> bool {
-        DOOM_FORMAT_REGEX.is_match(number) || DOOM2_FORMAT_REGEX.is_match(number)
+    mod parse_dehacked {
+        use super::super::{MapDefinition, MapDefinitions};
+
+        #[test]
+        fn should_parse_the_bex_strings_and_pars() {
+            let contents = "Patch File for DeHackEd v3.0\n\
+                            Doom version = 21\n\
+                            \n\
+                            Thing 1 (Player)\n\
+                            Hit points = 200\n\
+                            \n\
+                            [STRINGS]\n\
+                            HUSTR_1 = level 1: the first \\\n\
+                            level\n\
+                            HUSTR_E1M1 = E1M1: Hangar Redux\n\
+                            PHUSTR_2 = level 2: the second level\n\
+                            GOTBLUECARD = Picked up a blue keycard.\n\
+                            \n\
+                            [PARS]\n\
+                            par 1 30\n\
+                            par 1 1 45\n\
+                            par 3 90\n";
+            let definitions = MapDefinitions::parse_dehacked(contents, None);
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "MAP01".to_string(),
+                        name: Some("the first level".to_string()),
+                        par_time: Some(30),
+                        ..Default::default()
+                    },
+                    MapDefinition {
+                        lump_name: "E1M1".to_string(),
+                        name: Some("Hangar Redux".to_string()),
+                        par_time: Some(45),
+                        ..Default::default()
+                    },
+                    MapDefinition {
+                        lump_name: "MAP03".to_string(),
+                        par_time: Some(90),
+                        ..Default::default()
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn should_use_the_strings_for_the_iwad() {
+            let contents = "[STRINGS]\n\
+                            HUSTR_1 = level 1: doom 2 name\n\
+                            PHUSTR_1 = level 1: plutonia name\n\
+                            THUSTR_1 = level 1: tnt name\n\
+                            PHUSTR_2 = level 2: first\n\
+                            PHUSTR_2 = level 2: second\n";
+            let definitions = MapDefinitions::parse_dehacked(contents, Some("PLUTONIA"));
+            assert_eq!(
+                definitions.get("MAP01").unwrap().name,
+                Some("plutonia name".to_string())
+            );
+            assert_eq!(
+                definitions.get("MAP02").unwrap().name,
+                Some("second".to_string())
+            );
+
+            let definitions = MapDefinitions::parse_dehacked(contents, Some("TNT"));
+            assert_eq!(
+                definitions.get("MAP01").unwrap().name,
+                Some("tnt name".to_string())
+            );
+            assert_eq!(definitions.get("MAP02"), None);
+
+            let definitions = MapDefinitions::parse_dehacked(contents, Some("FREEDOOM2"));
+            assert_eq!(
+                definitions.get("MAP01").unwrap().name,
+                Some("doom 2 name".to_string())
+            );
+        }
+
+        #[test]
+        fn should_parse_the_text_blocks_that_replace_the_stock_map_names() {
+            let contents = "Patch File for DeHackEd v3.0\r\n\
+                            \r\n\
+                            Text 17 14\r\n\
+                            level 1: entrywaylevel 1: Start\r\n\
+                            \r\n\
+                            Text 12 9\r\n\
+                            E1M1: Hangar\r\n\
+                            Hangar 2\r\n\
+                            \r\n\
+                            Text 13 4\r\n\
+                            level 2: wellGone\r\n\
+                            \r\n\
+                            Text 19 12\r\n\
+                            level 2: underhallsWet Basement\r\n\
+                            Text 10 4\r\n\
+                            Not a map!Nope\r\n";
+            let definitions = MapDefinitions::parse_dehacked(contents, Some("DOOM2"));
+            assert_eq!(
+                definitions.maps,
+                vec![
+                    MapDefinition {
+                        lump_name: "MAP01".to_string(),
+                        name: Some("Start".to_string()),
+                        ..Default::default()
+                    },
+                    MapDefinition {
+                        lump_name: "E1M1".to_string(),
+                        name: Some("Hangar 2".to_string()),
+                        ..Default::default()
+                    },
+                    MapDefinition {
+                        lump_name: "MAP02".to_string(),
+                        name: Some("Wet Basement".to_string()),
+                        ..Default::default()
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn should_ignore_the_text_blocks_for_the_stock_names_of_another_iwad() {
+            let contents = "Text 14 8\nlevel 1: congoJungle 1\n";
+            let definitions = MapDefinitions::parse_dehacked(contents, Some("DOOM2"));
+            assert!(definitions.maps.is_empty());
+            let definitions = MapDefinitions::parse_dehacked(contents, Some("PLUTONIA"));
+            assert_eq!(
+                definitions.get("MAP01").unwrap().name,
+                Some("Jungle 1".to_string())
+            );
+        }
     }
-}
 
-impl WadEntry {
-    pub fn new(
-        id: String,
-        name: String,
-        title: String,
-        release_date: String,
-        author: String,
-        maps: Vec<MapInfo>,
-    ) -> Result<WadEntry, Report> {
-        ensure!(!id.is_empty(), "The ID for the WAD entry must be set.");
-        ensure!(!name.is_empty(), "The name for the WAD entry must be set.");
-        ensure!(
-            !title.is_empty(),
-            "The title for the WAD entry must be set."
-        );
-        ensure!(
-            !release_date.is_empty(),
-            "The release date for the WAD entry must be set."
-        );
-        ensure!(
-            !author.is_empty(),
-            "The author for the WAD entry must be set."
-        );
-        Ok(WadEntry {
-            id,
-            name,
-            title,
-            release_date,
-            author,
-            maps,
-        })
+    mod from_wad {
+        use super::super::{MapDefinition, MapDefinitions, WadReader};
+        use test_helpers::wad::write_wad;
+
+        #[test]
+        fn should_merge_the_lumps_in_order_of_precedence() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(
+                wad.path(),
+                "PWAD",
+                &[
+                    ("DEHACKED", b"[STRINGS]\nHUSTR_1 = level 1: deh name\nHUSTR_2 = level 2: deh only\n[PARS]\npar 1 30\n"),
+                    ("MAPINFO", b"map MAP01 \"mapinfo name\"\nnext MAP03\n"),
+                    ("UMAPINFO", b"MAP MAP01 { levelname = \"umapinfo name\" }\n"),
+                    ("MAP01", b""),
+                    ("MAP02", b""),
+                ],
+            );
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let definitions = MapDefinitions::from_wad(&mut reader, None).unwrap();
+            assert_eq!(
+                definitions.get("MAP01").unwrap(),
+                &MapDefinition {
+                    lump_name: "MAP01".to_string(),
+                    name: Some("umapinfo name".to_string()),
+                    par_time: Some(30),
+                    next: Some("MAP03".to_string()),
+                    next_secret: None,
+                }
+            );
+            assert_eq!(
+                definitions.get("MAP02").unwrap().name,
+                Some("deh only".to_string())
+            );
+        }
+
+        #[test]
+        fn should_return_no_definitions_when_the_wad_has_no_map_information() {
+            let wad = assert_fs::NamedTempFile::new("test.wad").unwrap();
+            write_wad(wad.path(), "PWAD", &[("MAP01", b""), ("THINGS", b"things")]);
+            let mut reader = WadReader::open(wad.path()).unwrap();
+            let definitions = MapDefinitions::from_wad(&mut reader, None).unwrap();
+            assert!(definitions.maps.is_empty());
+            assert!(definitions.episodes.is_empty());
+        }
     }
 }
 
 #[cfg(test)]
 mod mapinfo {
     mod new {
-        use super::super::MapInfo;
+        use super::super::{MapInfo, Warp};
 
         #[test]
         fn should_permit_a_map_in_the_doom2_format() {
             let map = MapInfo::new("MAP01".to_string(), "Entryway".to_string()).unwrap();
             assert_eq!("MAP01", map.number);
             assert_eq!("Entryway", map.name);
-            assert_eq!("1", map.warp);
+            assert_eq!(Warp::Map(1), map.warp);
 
             let map = MapInfo::new("MAP12".to_string(), "The Factory".to_string()).unwrap();
             assert_eq!("MAP12", map.number);
             assert_eq!("The Factory", map.name);
-            assert_eq!("12", map.warp);
+            assert_eq!(Warp::Map(12), map.warp);
         }
 
         #[test]
@@ -218,12 +3135,39 @@ mod mapinfo {
             let map = MapInfo::new("E1M1".to_string(), "Hanger".to_string()).unwrap();
             assert_eq!("E1M1", map.number);
             assert_eq!("Hanger", map.name);
-            assert_eq!("1 1", map.warp);
+            assert_eq!(Warp::Episode(1, 1), map.warp);
 
             let map = MapInfo::new("E4M8".to_string(), "Hanger".to_string()).unwrap();
             assert_eq!("E4M8", map.number);
             assert_eq!("Hanger", map.name);
-            assert_eq!("4 8", map.warp);
+            assert_eq!(Warp::Episode(4, 8), map.warp);
+        }
+
+        #[test]
+        fn should_permit_maps_beyond_the_classic_numbering() {
+            let map = MapInfo::new("MAP100".to_string(), "Extra".to_string()).unwrap();
+            assert_eq!(Warp::Map(100), map.warp);
+
+            let map = MapInfo::new("E5M1".to_string(), "Sigil".to_string()).unwrap();
+            assert_eq!(Warp::Episode(5, 1), map.warp);
+
+            let map = MapInfo::new("E1M10".to_string(), "Sewers".to_string()).unwrap();
+            assert_eq!(Warp::Episode(1, 10), map.warp);
+        }
+
+        #[test]
+        fn should_permit_a_map_with_a_custom_name() {
+            let map = MapInfo::new("START".to_string(), "Hub".to_string()).unwrap();
+            assert_eq!("START", map.number);
+            assert_eq!(Warp::LumpName("START".to_string()), map.warp);
+
+            // These can't be reached with `-warp`, because it would load MAP01 and E1M1.
+            let map = MapInfo::new("MAP001".to_string(), "Entryway".to_string()).unwrap();
+            assert_eq!(Warp::LumpName("MAP001".to_string()), map.warp);
+            let map = MapInfo::new("E01M01".to_string(), "Hanger".to_string()).unwrap();
+            assert_eq!(Warp::LumpName("E01M01".to_string()), map.warp);
+            let map = MapInfo::new("MAP00".to_string(), "Title".to_string()).unwrap();
+            assert_eq!(Warp::LumpName("MAP00".to_string()), map.warp);
         }
 
         #[test]
@@ -232,7 +3176,7 @@ mod mapinfo {
             assert!(result.is_err());
             assert_eq!(
                 result.unwrap_err().to_string(),
-                "A number must be provided for the map. It should be in the DOOM or DOOM2 format."
+                "A number must be provided for the map. It should be the name of its marker lump."
             );
         }
 
@@ -247,43 +3191,42 @@ mod mapinfo {
         }
 
         #[test]
-        fn should_reject_map_number_in_wrong_format() {
-            let result = MapInfo::new("MAP1".to_string(), "Entryway".to_string());
-            assert!(result.is_err());
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                "The map number must be in the DOOM or DOOM2 format. Valid values are ExMx or MAPxx."
-            );
+        fn should_reject_map_number_that_is_not_a_lump_name() {
+            for number in ["map01", "e1m1", "MAP 01", "MAP000001"] {
+                let result = MapInfo::new(number.to_string(), "Entryway".to_string());
+                assert!(result.is_err());
+                assert_eq!(
+                    result.unwrap_err().to_string(),
+                    "The map number must be a lump name, with up to 8 uppercase letters, digits or symbols."
+                );
+            }
+        }
+    }
 
-            let result = MapInfo::new("map01".to_string(), "Entryway".to_string());
-            assert!(result.is_err());
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                "The map number must be in the DOOM or DOOM2 format. Valid values are ExMx or MAPxx."
-            );
+    mod deserialize {
+        use super::super::{MapInfo, Warp};
 
-            let result = MapInfo::new("MA01".to_string(), "Entryway".to_string());
-            assert!(result.is_err());
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                "The map number must be in the DOOM or DOOM2 format. Valid values are ExMx or MAPxx."
-            );
+        #[test]
+        fn should_convert_the_warp_from_an_older_entry() {
+            let map: MapInfo = serde_json::from_str(
+                r#"{ "number": "MAP12", "name": "The Factory", "warp": "12" }"#,
+            )
+            .unwrap();
+            assert_eq!(map.warp, Warp::Map(12));
 
-            let result = MapInfo::new("e1m1".to_string(), "Hanger".to_string());
-            assert!(result.is_err());
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                "The map number must be in the DOOM or DOOM2 format. Valid values are ExMx or MAPxx."
-            );
+            let map: MapInfo = serde_json::from_str(
+                r#"{ "number": "E4M8", "name": "Unto the Cruel", "warp": "4 8" }"#,
+            )
+            .unwrap();
+            assert_eq!(map.warp, Warp::Episode(4, 8));
+        }
 
-            // DOOM.WAD contains lumps named `D_ExMx`, which are music data for the maps. The original
-            // regex matched on these lumps.
-            let result = MapInfo::new("D_E1M1".to_string(), "Hanger".to_string());
-            assert!(result.is_err());
-            assert_eq!(
-                result.unwrap_err().to_string(),
-                "The map number must be in the DOOM or DOOM2 format. Valid values are ExMx or MAPxx."
-            );
+        #[test]
+        fn should_read_the_warp_that_was_written() {
+            let map = MapInfo::new("START".to_string(), "Hub".to_string()).unwrap();
+            let json = serde_json::to_string(&map).unwrap();
+            let map: MapInfo = serde_json::from_str(&json).unwrap();
+            assert_eq!(map.warp, Warp::LumpName("START".to_string()));
         }
     }
 }
@@ -428,4 +3371,78 @@ mod wadentry {
             );
         }
     }
+
+    mod get_next_map {
+        use super::super::MapInfo;
+        use super::super::WadEntry;
+
+        fn get_wad_entry() -> WadEntry {
+            let mut maps = vec![
+                MapInfo::new("MAP01".to_string(), "Entryway".to_string()).unwrap(),
+                MapInfo::new("MAP02".to_string(), "Underhalls".to_string()).unwrap(),
+                MapInfo::new("MAP03".to_string(), "The Gantlet".to_string()).unwrap(),
+                MapInfo::new("MAP04".to_string(), "The Focus".to_string()).unwrap(),
+            ];
+            maps[1].next = Some("MAP04".to_string());
+            maps[2].next = Some("EndGame1".to_string());
+            WadEntry::new(
+                "DOOM2".to_string(),
+                "DOOM2.WAD".to_string(),
+                "Doom II: Hell on Earth".to_string(),
+                "1994-09-30".to_string(),
+                "id Software".to_string(),
+                maps,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn should_get_the_following_map_in_the_wad() {
+            let entry = get_wad_entry();
+            assert_eq!(entry.get_next_map("MAP01").unwrap().number, "MAP02");
+        }
+
+        #[test]
+        fn should_follow_the_next_map_defined_by_the_wad() {
+            let entry = get_wad_entry();
+            assert_eq!(entry.get_next_map("MAP02").unwrap().number, "MAP04");
+        }
+
+        #[test]
+        fn should_ignore_a_next_map_that_is_not_in_the_wad() {
+            let entry = get_wad_entry();
+            assert_eq!(entry.get_next_map("MAP03").unwrap().number, "MAP04");
+        }
+
+        #[test]
+        fn should_return_none_for_the_last_map() {
+            let entry = get_wad_entry();
+            assert!(entry.get_next_map("MAP04").is_none());
+        }
+
+        #[test]
+        fn should_return_none_for_a_map_that_is_not_in_the_wad() {
+            let entry = get_wad_entry();
+            assert!(entry.get_next_map("MAP32").is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod supplementaryfile {
+    mod is_dehacked {
+        use super::super::{LoadOrder, SupplementaryFile};
+
+        #[test]
+        fn should_identify_deh_and_bex_patches_by_their_extension() {
+            let file = |name: &str| SupplementaryFile {
+                name: name.to_string(),
+                load_order: LoadOrder::AfterWad,
+            };
+            assert!(file("patch.deh").is_dehacked());
+            assert!(file("PATCH.BEX").is_dehacked());
+            assert!(!file("music.wad").is_dehacked());
+            assert!(!file("deh.pk3").is_dehacked());
+        }
+    }
 }
@@ -1,4 +1,6 @@
 pub mod source_port {
+    use std::path::{Path, PathBuf};
+
     #[cfg(target_family = "unix")]
     const FAKE_SOURCE_PORT_BIN_NAME: &str = "fake_source_port";
     #[cfg(target_family = "windows")]
@@ -11,6 +13,18 @@ pub mod source_port {
         fake_source_port_path.push(FAKE_SOURCE_PORT_BIN_NAME);
         String::from(fake_source_port_path.as_path().to_str().unwrap())
     }
+
+    /// Copies the fake source port into a `port` directory under `dir_path`, for tests that need
+    /// their own copy of it, e.g. when it writes files next to itself.
+    pub fn copy_fake_source_port(dir_path: &Path) -> PathBuf {
+        let fake_source_port_path = PathBuf::from(get_fake_source_port_path());
+        let path = dir_path
+            .join("port")
+            .join(fake_source_port_path.file_name().unwrap());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::copy(&fake_source_port_path, &path).unwrap();
+        path
+    }
 }
 
 pub mod date_time {
@@ -58,3 +72,320 @@ pub mod cache {
         Ok(())
     }
 }
+
+pub mod wad {
+    use std::io::Write;
+    use std::path::Path;
+
+    /// Builds a WAD file from a list of lumps.
+    ///
+    /// The lump data is written directly after the header, in the order supplied, and the
+    /// directory is placed at the end of the file. This is the same layout the id tools used, so
+    /// it's good enough for testing the parsing code without needing to distribute any real WADs.
+    pub fn build_wad(wad_type: &str, lumps: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut directory: Vec<u8> = Vec::new();
+        let mut offset: u32 = 12;
+        for (name, lump) in lumps {
+            data.extend_from_slice(lump);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(&(lump.len() as u32).to_le_bytes());
+            let mut lump_name = [0u8; 8];
+            lump_name[..name.len()].copy_from_slice(name.as_bytes());
+            directory.extend_from_slice(&lump_name);
+            offset += lump.len() as u32;
+        }
+        let mut wad: Vec<u8> = Vec::new();
+        wad.extend_from_slice(wad_type.as_bytes());
+        wad.extend_from_slice(&(lumps.len() as u32).to_le_bytes());
+        wad.extend_from_slice(&offset.to_le_bytes());
+        wad.extend_from_slice(&data);
+        wad.extend_from_slice(&directory);
+        wad
+    }
+
+    pub fn write_wad(path: impl AsRef<Path>, wad_type: &str, lumps: &[(&str, &[u8])]) {
+        std::fs::write(path, build_wad(wad_type, lumps)).unwrap();
+    }
+
+    /// Builds a PK3 package from a list of files. The files aren't compressed, so the sizes are
+    /// predictable.
+    pub fn build_pk3(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, data) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    pub fn write_pk3(path: impl AsRef<Path>, files: &[(&str, &[u8])]) {
+        std::fs::write(path, build_pk3(files)).unwrap();
+    }
+
+    /// Builds a THINGS lump from the type and flags of each thing. The position and angle don't
+    /// matter for any of the tests, so the things are all placed at the origin.
+    pub fn build_things(things: &[(u16, u16)]) -> Vec<u8> {
+        let mut lump = Vec::new();
+        for (thing_type, flags) in things {
+            lump.extend_from_slice(&0i16.to_le_bytes());
+            lump.extend_from_slice(&0i16.to_le_bytes());
+            lump.extend_from_slice(&90u16.to_le_bytes());
+            lump.extend_from_slice(&thing_type.to_le_bytes());
+            lump.extend_from_slice(&flags.to_le_bytes());
+        }
+        lump
+    }
+
+    /// Builds a SECTORS lump with a sector for each of the specials.
+    pub fn build_sectors(specials: &[u16]) -> Vec<u8> {
+        let mut lump = Vec::new();
+        for special in specials {
+            lump.extend_from_slice(&0i16.to_le_bytes());
+            lump.extend_from_slice(&128i16.to_le_bytes());
+            lump.extend_from_slice(b"FLOOR4_8");
+            lump.extend_from_slice(b"CEIL3_5\0");
+            lump.extend_from_slice(&160i16.to_le_bytes());
+            lump.extend_from_slice(&special.to_le_bytes());
+            lump.extend_from_slice(&0u16.to_le_bytes());
+        }
+        lump
+    }
+
+    /// Builds the LINEDEFS, SIDEDEFS and VERTEXES lumps for a square room, with its bottom left
+    /// corner at the origin, which uses the first sector.
+    pub fn build_square_room(size: i16) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut vertexes = Vec::new();
+        for (x, y) in [(0, 0), (0, size), (size, size), (size, 0)] {
+            vertexes.extend_from_slice(&x.to_le_bytes());
+            vertexes.extend_from_slice(&y.to_le_bytes());
+        }
+        let mut linedefs = Vec::new();
+        let mut sidedefs = Vec::new();
+        for index in 0..4u16 {
+            // Start vertex, end vertex, flags (impassable), special, tag, front and back sidedefs.
+            for value in [index, (index + 1) % 4, 1, 0, 0, index, 0xFFFF] {
+                linedefs.extend_from_slice(&value.to_le_bytes());
+            }
+            sidedefs.extend_from_slice(&0i16.to_le_bytes());
+            sidedefs.extend_from_slice(&0i16.to_le_bytes());
+            sidedefs.extend_from_slice(b"-\0\0\0\0\0\0\0");
+            sidedefs.extend_from_slice(b"-\0\0\0\0\0\0\0");
+            sidedefs.extend_from_slice(b"STARTAN3");
+            sidedefs.extend_from_slice(&0u16.to_le_bytes());
+        }
+        (linedefs, sidedefs, vertexes)
+    }
+
+    /// Builds a THINGS lump in the Hexen format, which has a thing ID, height, special and
+    /// arguments as well. Those are all zero.
+    pub fn build_hexen_things(things: &[(u16, u16)]) -> Vec<u8> {
+        let mut lump = Vec::new();
+        for (thing_type, flags) in things {
+            lump.extend_from_slice(&[0u8; 8]);
+            lump.extend_from_slice(&90u16.to_le_bytes());
+            lump.extend_from_slice(&thing_type.to_le_bytes());
+            lump.extend_from_slice(&flags.to_le_bytes());
+            lump.extend_from_slice(&[0u8; 6]);
+        }
+        lump
+    }
+
+    /// Builds the same square room as `build_square_room`, but with the lines in the Hexen format.
+    /// The first line has special 80 (ACS_Execute) with the script number as its first argument.
+    pub fn build_hexen_square_room(size: i16) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let (_, sidedefs, vertexes) = build_square_room(size);
+        let mut linedefs = Vec::new();
+        for index in 0..4u16 {
+            linedefs.extend_from_slice(&index.to_le_bytes());
+            linedefs.extend_from_slice(&((index + 1) % 4).to_le_bytes());
+            linedefs.extend_from_slice(&1u16.to_le_bytes());
+            if index == 0 {
+                linedefs.extend_from_slice(&[80, 1, 0, 0, 0, 0]);
+            } else {
+                linedefs.extend_from_slice(&[0u8; 6]);
+            }
+            linedefs.extend_from_slice(&index.to_le_bytes());
+            linedefs.extend_from_slice(&0xFFFFu16.to_le_bytes());
+        }
+        (linedefs, sidedefs, vertexes)
+    }
+}
+
+pub mod demo {
+    /// Builds a 1.9 demo of one player on UV, on the given map of the first episode, with the
+    /// given number of tics.
+    pub fn build_vanilla_demo(map: u8, tics: usize) -> Vec<u8> {
+        let mut demo = vec![109, 3, 1, map, 0, 0, 0, 0, 0, 1, 0, 0, 0];
+        demo.extend(vec![0x19; tics * 4]);
+        demo.push(0x80);
+        demo
+    }
+
+    /// Builds the header of a PrBoom+ 2.5 demo, for complevel 17, with a few tics.
+    pub fn build_prboom_demo() -> Vec<u8> {
+        let mut demo = vec![214, 0x1D, b'P', b'r', b'B', b'o', 0xE6, 0, 3, 1, 1, 0, 0];
+        demo.extend(vec![0; 64]);
+        let mut players = vec![0; 32];
+        players[0] = 1;
+        demo.extend(players);
+        // Complevel 17 demos have longtics, so each tic is 5 bytes.
+        demo.extend(vec![0x19; 5 * 35]);
+        demo.push(0x80);
+        demo
+    }
+
+    /// Builds the header of an MBF21 demo, which only DSDA-Doom and Woof! can play.
+    pub fn build_mbf21_demo() -> Vec<u8> {
+        let mut demo = vec![221, 0x1D, b'M', b'B', b'F', 0x02, 0xE6, 0, 3, 1, 1, 0, 0];
+        demo.extend(vec![0; 46]);
+        let mut players = vec![0; 32];
+        players[0] = 1;
+        demo.extend(players);
+        demo.push(0x80);
+        demo
+    }
+}
+
+pub mod http {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    /// Starts a local stand-in for a web service, which can be used in place of the real thing by
+    /// anything that has a configurable base URL.
+    ///
+    /// Each entry in `routes` is a path, including any query string, and the body to respond with.
+    /// Requests for any other path get a 404. The server handles `requests` requests, then stops.
+    ///
+    /// Returns the base URL of the server, e.g. `http://127.0.0.1:12345`.
+    pub fn serve(routes: Vec<(String, Vec<u8>)>, requests: usize) -> (String, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                }
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let response = match routes.iter().find(|(route, _)| route == path) {
+                    Some((_, body)) => {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(body);
+                        response
+                    }
+                    None => {
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_vec()
+                    }
+                };
+                stream.write_all(&response).unwrap();
+            }
+        });
+        (base_url, handle)
+    }
+}
+
+/// Fixtures for the integration tests, which set up the settings for `tdl` by running its own
+/// commands against the settings file and Doom home directory for the test.
+pub mod tdl {
+    use crate::source_port::get_fake_source_port_path;
+    use crate::wad::write_wad;
+    use assert_cmd::Command;
+    use std::path::Path;
+
+    /// Adds the fake source port as the given source port.
+    pub fn add_source_port(settings_path: &Path, doom_home_path: &Path, source_port: &str) {
+        add_source_port_at(
+            settings_path,
+            doom_home_path,
+            source_port,
+            Path::new(&get_fake_source_port_path()),
+        );
+    }
+
+    /// Adds the binary at `source_port_path` as the given source port.
+    ///
+    /// This is for tests that need their own copy of the fake source port, e.g. when it writes
+    /// files next to itself.
+    pub fn add_source_port_at(
+        settings_path: &Path,
+        doom_home_path: &Path,
+        source_port: &str,
+        source_port_path: &Path,
+    ) {
+        let mut cmd = Command::cargo_bin("tdl").unwrap();
+        cmd.arg("source-port")
+            .arg("add")
+            .arg(source_port)
+            .arg(source_port_path.to_str().unwrap())
+            .arg("1.0")
+            .env("TDL_SETTINGS_PATH", settings_path.to_str().unwrap())
+            .env("TDL_DOOM_HOME_PATH", doom_home_path.to_str().unwrap())
+            .assert()
+            .success();
+    }
+
+    /// Adds a default profile that plays on UltraViolence with the given source port.
+    pub fn add_default_profile(settings_path: &Path, doom_home_path: &Path, source_port: &str) {
+        let mut cmd = Command::cargo_bin("tdl").unwrap();
+        cmd.arg("profile")
+            .arg("add")
+            .arg("--name")
+            .arg("default")
+            .arg("--source-port")
+            .arg(source_port)
+            .arg("--version")
+            .arg("1.0")
+            .arg("--skill")
+            .arg("UltraViolence")
+            .env("TDL_SETTINGS_PATH", settings_path.to_str().unwrap())
+            .env("TDL_DOOM_HOME_PATH", doom_home_path.to_str().unwrap())
+            .assert()
+            .success();
+    }
+
+    /// Adds the fake source port, along with a default profile that uses it.
+    pub fn add_fake_source_port(settings_path: &Path, doom_home_path: &Path, source_port: &str) {
+        add_source_port(settings_path, doom_home_path, source_port);
+        add_default_profile(settings_path, doom_home_path, source_port);
+    }
+
+    /// Imports a minimal DOOM II IWAD with a single map, since PWADs can't be played without
+    /// their IWAD.
+    pub fn import_test_iwad(settings_path: &Path, doom_home_path: &Path) {
+        import_test_iwad_with_maps(settings_path, doom_home_path, &["MAP01"]);
+    }
+
+    /// Imports a minimal DOOM II IWAD with the given maps, each of which is empty.
+    pub fn import_test_iwad_with_maps(settings_path: &Path, doom_home_path: &Path, maps: &[&str]) {
+        let iwad_path = settings_path.with_file_name("doom2.wad");
+        let lumps = maps
+            .iter()
+            .flat_map(|map| vec![(*map, &b""[..]), ("THINGS", &b""[..])])
+            .collect::<Vec<_>>();
+        write_wad(&iwad_path, "IWAD", &lumps);
+        let mut cmd = Command::cargo_bin("tdl").unwrap();
+        cmd.arg("iwad")
+            .arg("import")
+            .arg(iwad_path.to_str().unwrap())
+            .env("TDL_SETTINGS_PATH", settings_path.to_str().unwrap())
+            .env("TDL_DOOM_HOME_PATH", doom_home_path.to_str().unwrap())
+            .assert()
+            .success();
+    }
+}
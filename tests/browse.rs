@@ -0,0 +1,19 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+#[test]
+fn browse_should_fail_without_a_terminal() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("browse")
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The library browser can only be used from a terminal",
+        ))
+        .stderr(predicate::str::contains("panicked").not());
+}
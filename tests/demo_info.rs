@@ -0,0 +1,141 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::demo::build_vanilla_demo;
+use test_helpers::wad::write_wad;
+
+#[test]
+fn demo_info_should_show_the_header_of_a_demo() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let demo = doom_home_dir.child("test.lmp");
+    demo.write_binary(&build_vanilla_demo(7, 2135)).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("info")
+        .arg(demo.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Version: 1.9"))
+        .stderr(predicate::str::contains("Skill: Ultra-Violence"))
+        .stderr(predicate::str::contains("Map: E1M7 or MAP07"))
+        .stderr(predicate::str::contains("Players: 1"))
+        .stderr(predicate::str::contains("Tics: 2135"))
+        .stderr(predicate::str::contains("Duration: 1:01.00"));
+}
+
+#[test]
+fn demo_info_should_fail_for_a_file_that_is_not_a_demo() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let demo = doom_home_dir.child("test.lmp");
+    demo.write_binary(b"PWAD").unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("info")
+        .arg(demo.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "80 is not a supported demo version",
+        ));
+}
+
+#[test]
+fn demo_ls_should_show_the_skill_and_map_of_each_demo() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    doom_home_dir
+        .child("demos/DOOM2/doom207m-001.lmp")
+        .write_binary(&build_vanilla_demo(7, 70))
+        .unwrap();
+    settings_dir
+        .child("demos/doom207m-001.json")
+        .write_str(
+            r#"{
+                "id": "doom207m-001",
+                "file_name": "doom207m-001.lmp",
+                "wad_id": "DOOM2",
+                "map": "MAP07",
+                "category": "UvMax",
+                "attempt": 1,
+                "source_port": "Dsda",
+                "source_port_version": "0.24.3",
+                "profile": "default",
+                "recorded": "2026-10-16T12:00:00Z"
+            }"#,
+        )
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("ls")
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"doom207m-001\s*\|\s*DOOM2\s*\|\s*E1M7 or MAP07\s*\|\s*Ultra-Violence\s*\|\s*UV-Max\s*\|\s*0:02.00\s*\|\s*DSDA Doom 0.24.3").unwrap());
+}
+
+#[test]
+fn demo_ls_should_use_the_map_format_of_the_wad() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    // The first map starts with an E, but it's a custom map in a WAD that otherwise uses MAPxx.
+    let wad = settings_dir.child("entry.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[
+            ("ENTRY", b""),
+            ("THINGS", b""),
+            ("MAP07", b""),
+            ("THINGS", b""),
+        ],
+    );
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+    doom_home_dir
+        .child("demos/entry/entry07m-001.lmp")
+        .write_binary(&build_vanilla_demo(7, 70))
+        .unwrap();
+    settings_dir
+        .child("demos/entry07m-001.json")
+        .write_str(
+            r#"{
+                "id": "entry07m-001",
+                "file_name": "entry07m-001.lmp",
+                "wad_id": "entry",
+                "map": "MAP07",
+                "category": "UvMax",
+                "attempt": 1,
+                "source_port": "Dsda",
+                "source_port_version": "0.24.3",
+                "profile": "default",
+                "recorded": "2026-10-16T12:00:00Z"
+            }"#,
+        )
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("ls")
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"entry07m-001\s*\|\s*entry\s*\|\s*MAP07\s*\|").unwrap());
+}
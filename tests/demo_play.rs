@@ -0,0 +1,112 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::demo::{build_mbf21_demo, build_prboom_demo};
+use test_helpers::tdl::{add_default_profile, add_source_port, import_test_iwad};
+
+#[test]
+fn demo_play_should_use_an_installed_source_port_that_supports_the_demo() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "Chocolate");
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "Chocolate");
+    add_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let demo = doom_home_dir.child("prboom.lmp");
+    demo.write_binary(&build_prboom_demo()).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("play")
+        .arg(demo.path().to_str().unwrap())
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Chocolate Doom can't play this demo, so DSDA Doom will be used instead",
+        ))
+        .stdout(predicate::str::contains(format!(
+            "Game called with -playdemo: {}",
+            demo.path().display()
+        )))
+        .stdout(predicate::str::contains("Game called with -skill").not())
+        .stdout(predicate::str::contains("Game called with -complevel").not());
+}
+
+#[test]
+fn demo_play_should_time_a_demo() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let demo = doom_home_dir.child("mbf21.lmp");
+    demo.write_binary(&build_mbf21_demo()).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("play")
+        .arg(demo.path().to_str().unwrap())
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--timedemo")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Game called with -timedemo: {}",
+            demo.path().display()
+        )));
+}
+
+#[test]
+fn demo_play_should_say_which_source_port_is_needed() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let demo = doom_home_dir.child("mbf21.lmp");
+    demo.write_binary(&build_mbf21_demo()).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("play")
+        .arg(demo.path().to_str().unwrap())
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "None of the installed source ports can play",
+        ))
+        .stderr(predicate::str::contains(
+            "Install or add one of these source ports: DSDA Doom, Woof!",
+        ));
+}
+
+#[test]
+fn demo_play_should_require_the_wad_for_a_demo_not_recorded_with_tdl() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "Dsda");
+    let demo = doom_home_dir.child("prboom.lmp");
+    demo.write_binary(&build_prboom_demo()).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("play")
+        .arg(demo.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("so its WAD isn't known"));
+}
@@ -0,0 +1,143 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::demo::build_vanilla_demo;
+use test_helpers::tdl::{add_default_profile, add_source_port, import_test_iwad};
+
+#[test]
+fn demo_render_should_render_a_video_with_the_encoder_options_from_the_profile() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "Woof");
+    add_source_port(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("profile")
+        .arg("add")
+        .arg("--name")
+        .arg("render")
+        .arg("--source-port")
+        .arg("Woof")
+        .arg("--version")
+        .arg("1.0")
+        .arg("--skill")
+        .arg("UltraViolence")
+        .arg("--render-fps")
+        .arg("35")
+        .arg("--mux-command")
+        .arg("mkvmerge -o %f output.mp4 output.ogg")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let demo = doom_home_dir.child("test.lmp");
+    demo.write_binary(&build_vanilla_demo(1, 70)).unwrap();
+    // A video from an earlier render, which is replaced.
+    let video = doom_home_dir.child("test.mkv");
+    video.write_str("an earlier render").unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("render")
+        .arg(demo.path().to_str().unwrap())
+        .arg("--output")
+        .arg(video.path().to_str().unwrap())
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Woof! can't render this demo, so PrBoom Plus will be used instead",
+        ))
+        .stderr(predicate::str::contains(format!(
+            "Replacing {}",
+            video.path().display()
+        )))
+        .stderr(predicate::str::is_match(r"-config \S+\.cfg").unwrap())
+        .stderr(predicate::str::contains(format!(
+            "-timedemo {} -viddump {}",
+            demo.path().display(),
+            video.path().display()
+        )))
+        .stderr(predicate::str::contains(format!(
+            "Rendered {} to {}",
+            demo.path().display(),
+            video.path().display()
+        )));
+    video.assert("");
+}
+
+#[test]
+fn demo_render_should_say_which_source_port_is_needed() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "Chocolate");
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("profile")
+        .arg("add")
+        .arg("--name")
+        .arg("default")
+        .arg("--source-port")
+        .arg("Chocolate")
+        .arg("--version")
+        .arg("1.0")
+        .arg("--skill")
+        .arg("UltraViolence")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let demo = doom_home_dir.child("test.lmp");
+    demo.write_binary(&build_vanilla_demo(1, 70)).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("render")
+        .arg(demo.path().to_str().unwrap())
+        .arg("--output")
+        .arg(doom_home_dir.path().join("test.mkv").to_str().unwrap())
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "None of the installed source ports can render",
+        ))
+        .stderr(predicate::str::contains(
+            "Install or add one of these source ports: DSDA Doom, PrBoom Plus",
+        ));
+}
+
+#[test]
+fn demo_render_should_show_the_exit_status_and_output_of_a_crash() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_source_port(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let demo = doom_home_dir.child("test.lmp");
+    demo.write_binary(&build_vanilla_demo(1, 70)).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("demo")
+        .arg("render")
+        .arg(demo.path().to_str().unwrap())
+        .arg("--output")
+        .arg(doom_home_dir.path().join("test.mkv").to_str().unwrap())
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .env("TDL_FAKE_SOURCE_PORT_EXIT_CODE", "3")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("PrBoom Plus exited with status 3"))
+        .stderr(predicate::str::contains(
+            "I_Error: R_TextureNumForName: SKY4 not found",
+        ));
+}
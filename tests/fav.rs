@@ -0,0 +1,152 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use test_helpers::tdl::import_test_iwad_with_maps;
+
+#[test]
+fn fav_should_add_list_and_remove_favourite_wads_and_maps() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    import_test_iwad_with_maps(
+        settings_file.path(),
+        doom_home_dir.path(),
+        &["MAP01", "MAP02"],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("add")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--map")
+        .arg("MAP02")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Added MAP02 in DOOM2 to your favourites",
+        ));
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("add")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Added DOOM2 to your favourites"));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("ls")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"DOOM2 +\|  +\|").unwrap())
+        .stdout(predicate::str::is_match(r"DOOM2 +\| MAP02 +\|").unwrap());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("rm")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Removed DOOM2 from your favourites",
+        ));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("ls")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"DOOM2 +\|  +\|").unwrap().not())
+        .stdout(predicate::str::contains("MAP02"));
+}
+
+#[test]
+fn fav_add_should_fail_for_a_map_that_is_not_in_the_wad() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    import_test_iwad_with_maps(
+        settings_file.path(),
+        doom_home_dir.path(),
+        &["MAP01", "MAP02"],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("add")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--map")
+        .arg("MAP32")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Could not find MAP32 in DOOM2"));
+}
+
+#[test]
+fn fav_add_should_fail_for_a_favourite_that_was_already_added() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    import_test_iwad_with_maps(
+        settings_file.path(),
+        doom_home_dir.path(),
+        &["MAP01", "MAP02"],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("add")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("fav")
+        .arg("add")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "DOOM2 is already one of your favourites",
+        ));
+}
+
+#[test]
+fn fav_finder_should_fail_if_there_are_no_favourites() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    import_test_iwad_with_maps(
+        settings_file.path(),
+        doom_home_dir.path(),
+        &["MAP01", "MAP02"],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--favourites")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "You don't have any favourites yet",
+        ));
+}
@@ -0,0 +1,97 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use test_helpers::tdl::{add_fake_source_port, import_test_iwad};
+
+#[test]
+fn history_should_show_the_sessions_that_have_been_played() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--map")
+        .arg("MAP01")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .env("TDL_FAKE_SOURCE_PORT_EXIT_CODE", "3")
+        .assert()
+        .failure();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("history")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MAP01"))
+        .stdout(predicate::str::contains("DSDA Doom 1.0"))
+        .stdout(predicate::str::is_match(r"\| 0 +\|").unwrap())
+        .stdout(predicate::str::is_match(r"\| 3 +\|").unwrap())
+        .stderr(predicate::str::contains("Total play time: 0:00:00"));
+}
+
+#[test]
+fn history_should_only_show_the_sessions_that_match_the_filters() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("history")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--source-port")
+        .arg("Dsda")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DOOM2"))
+        .stderr(predicate::str::contains("Total play time"));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("history")
+        .arg("--source-port")
+        .arg("Woof")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "No games have been played that match the filters.",
+        ));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("history")
+        .arg("--to")
+        .arg("2000-01-01")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "No games have been played that match the filters.",
+        ));
+}
@@ -1,6 +1,7 @@
 use assert_cmd::Command;
 use assert_fs::prelude::*;
 use predicates::prelude::*;
+use test_helpers::wad::write_wad;
 
 #[test]
 fn doom2_iwad_import_should_print_the_correct_information() {
@@ -252,3 +253,180 @@ fn tnt_iwad_import_should_print_the_correct_information() {
     let iwad_file = doom_home_dir.child("iwads/TNT.WAD");
     iwad_file.assert(predicate::path::is_file());
 }
+
+#[test]
+fn iwad_import_should_identify_a_renamed_iwad_from_its_contents() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let iwad = source_dir.child("doom2.wad");
+    write_wad(
+        iwad.path(),
+        "IWAD",
+        &[
+            ("MAP01", b""),
+            ("THINGS", b""),
+            ("MAP33", b""),
+            ("THINGS", b""),
+        ],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(iwad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("ID: DOOM2"))
+        .stderr(predicate::str::contains("WAD Name: DOOM2.WAD"))
+        .stderr(predicate::str::contains("Title: Doom II: Hell on Earth"))
+        .stderr(predicate::str::contains("Version: Unknown"))
+        .stderr(predicate::str::contains("MAP01: Entryway"))
+        .stderr(predicate::str::contains("MAP33: Betray"));
+
+    let wad_entry_file = settings_dir.child("wads/DOOM2.json");
+    wad_entry_file.assert(predicate::str::contains("\"game\":\"Doom2\""));
+    let iwad_file = doom_home_dir.child("iwads/DOOM2.WAD");
+    iwad_file.assert(predicate::path::eq_file(iwad.path()));
+}
+
+#[test]
+fn iwad_import_should_fail_for_an_unrecognised_iwad() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let iwad = source_dir.child("custom.wad");
+    write_wad(iwad.path(), "IWAD", &[("STARTUP", b"")]);
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(iwad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a recognised IWAD"));
+}
+
+#[test]
+fn iwad_import_should_import_all_the_iwads_in_a_directory() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    write_wad(
+        source_dir.child("doom2.wad").path(),
+        "IWAD",
+        &[("MAP01", b""), ("THINGS", b"")],
+    );
+    let sub_dir = source_dir.child("final");
+    sub_dir.create_dir_all().unwrap();
+    write_wad(
+        sub_dir.child("PLUTONIA.WAD").path(),
+        "IWAD",
+        &[("MAP01", b""), ("THINGS", b""), ("CAMO1", b"")],
+    );
+    write_wad(
+        source_dir.child("megawad.wad").path(),
+        "PWAD",
+        &[("MAP01", b""), ("THINGS", b"")],
+    );
+    write_wad(
+        source_dir.child("custom.wad").path(),
+        "IWAD",
+        &[("STARTUP", b"")],
+    );
+    source_dir
+        .child("broken.wad")
+        .write_str("This is not a WAD file")
+        .unwrap();
+    source_dir.child("readme.txt").write_str("IWADs").unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(source_dir.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Imported 2 IWADs, skipped 0 and rejected 2",
+        ))
+        .stdout(
+            predicate::str::is_match(
+                r"doom2.wad\s+\| Imported \| Doom II: Hell on Earth \(Unknown\)",
+            )
+            .unwrap(),
+        )
+        .stdout(
+            predicate::str::is_match(
+                r"final/PLUTONIA.WAD\s+\| Imported \| The Plutonia Experiment",
+            )
+            .unwrap(),
+        )
+        .stdout(
+            predicate::str::is_match(r"custom.wad\s+\| Rejected \| .*is not a recognised IWAD")
+                .unwrap(),
+        )
+        .stdout(predicate::str::is_match(r"broken.wad\s+\| Rejected \| Failed to parse").unwrap())
+        .stdout(predicate::str::contains("megawad.wad").not())
+        .stdout(predicate::str::contains("readme.txt").not());
+
+    settings_dir
+        .child("wads/DOOM2.json")
+        .assert(predicate::path::is_file());
+    settings_dir
+        .child("wads/PLUTONIA.json")
+        .assert(predicate::path::is_file());
+    doom_home_dir
+        .child("iwads/PLUTONIA.WAD")
+        .assert(predicate::path::is_file());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(source_dir.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Imported 0 IWADs, skipped 2 and rejected 2",
+        ))
+        .stdout(
+            predicate::str::is_match(r"doom2.wad\s+\| Skipped\s+\| DOOM2 is already imported")
+                .unwrap(),
+        );
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn iwad_import_should_not_follow_a_symlink_that_loops_back_to_a_parent_directory() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    write_wad(
+        source_dir.child("doom2.wad").path(),
+        "IWAD",
+        &[("MAP01", b""), ("THINGS", b"")],
+    );
+    let sub_dir = source_dir.child("final");
+    sub_dir.create_dir_all().unwrap();
+    std::os::unix::fs::symlink(source_dir.path(), sub_dir.child("loop").path()).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(source_dir.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Imported 1 IWADs, skipped 0 and rejected 0",
+        ))
+        .stdout(predicate::str::contains("final/loop").not());
+}
@@ -166,7 +166,7 @@ fn get_directory_from_tdl(wad_path: &impl AsRef<Path>) -> Vec<(String, u32, u32)
     cmd.arg("wad")
         .arg("lsdir")
         .arg("--path")
-        .arg(&wad_path.as_ref().to_str().unwrap())
+        .arg(wad_path.as_ref().to_str().unwrap())
         .env("RUST_LOG", "debug")
         .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
         .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
@@ -204,7 +204,7 @@ fn setup_lswad() -> Result<PathBuf> {
         std::fs::create_dir_all(&dir_path)?;
         download_archive(archive_path)?;
         extract_archive(archive_path, &dir_path)?;
-        std::fs::remove_file(&archive_path)?;
+        std::fs::remove_file(archive_path)?;
     }
     Ok(file_path)
 }
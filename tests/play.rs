@@ -1,8 +1,12 @@
 use assert_cmd::Command;
 use assert_fs::prelude::*;
 use predicates::prelude::*;
-use std::path::PathBuf;
-use test_helpers::source_port::get_fake_source_port_path;
+use std::path::{Path, PathBuf};
+use test_helpers::source_port::{copy_fake_source_port, get_fake_source_port_path};
+use test_helpers::tdl::{
+    add_default_profile, add_fake_source_port, add_source_port_at, import_test_iwad,
+};
+use test_helpers::wad::{build_wad, write_pk3, write_wad};
 
 #[test]
 fn play_should_run_the_game_with_the_default_profile() {
@@ -448,3 +452,604 @@ fn play_should_run_the_game_with_the_selected_iwad_and_doom_map() {
         .stdout(predicate::str::contains("Game called with -warp: 1 7"))
         .stdout(predicate::str::contains("Game called with -skill: 4"));
 }
+
+#[test]
+fn play_should_fail_if_the_source_port_cannot_play_the_map_format() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let fake_source_port_path = get_fake_source_port_path();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("udmf.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[
+            ("MAP01", b""),
+            ("TEXTMAP", b"namespace = \"zdoom\";"),
+            ("ENDMAP", b""),
+        ],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("source-port")
+        .arg("add")
+        .arg("PrBoomPlus")
+        .arg(fake_source_port_path)
+        .arg("2.6")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("profile")
+        .arg("add")
+        .arg("--name")
+        .arg("default")
+        .arg("--source-port")
+        .arg("PrBoomPlus")
+        .arg("--version")
+        .arg("2.6")
+        .arg("--skill")
+        .arg("UltraViolence")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("udmf")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "MAP01 in udmf is a UDMF (zdoom) map, which PrBoom Plus can't play",
+        ))
+        .stderr(predicate::str::contains("GZDoom, LZDoom, Zandronum"));
+}
+
+#[test]
+fn play_should_start_a_map_with_a_custom_name_using_its_lump_name() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let fake_source_port_path = get_fake_source_port_path();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("hub.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[
+            ("START", b""),
+            ("THINGS", b""),
+            ("MAP100", b""),
+            ("THINGS", b""),
+        ],
+    );
+
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    for (source_port, version) in [("GzDoom", "4.7.1"), ("PrBoomPlus", "2.6")] {
+        let mut cmd = Command::cargo_bin("tdl").unwrap();
+        cmd.arg("source-port")
+            .arg("add")
+            .arg(source_port)
+            .arg(&fake_source_port_path)
+            .arg(version)
+            .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+            .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+            .assert()
+            .success();
+
+        let mut cmd = Command::cargo_bin("tdl").unwrap();
+        cmd.arg("profile")
+            .arg("add")
+            .arg("--name")
+            .arg(source_port)
+            .arg("--source-port")
+            .arg(source_port)
+            .arg("--version")
+            .arg(version)
+            .arg("--skill")
+            .arg("UltraViolence")
+            .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+            .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+            .assert()
+            .success();
+    }
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("hub")
+        .arg("--map")
+        .arg("START")
+        .arg("--profile")
+        .arg("GzDoom")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game called with +map: START"));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("hub")
+        .arg("--map")
+        .arg("MAP100")
+        .arg("--profile")
+        .arg("GzDoom")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game called with -warp: 100"));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("hub")
+        .arg("--map")
+        .arg("START")
+        .arg("--profile")
+        .arg("PrBoomPlus")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "PrBoom Plus can't be started on START, since it can only be loaded by its lump name",
+        ));
+}
+
+#[test]
+fn play_should_load_a_pwad_with_its_iwad_and_supplementary_files() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("episode.wad");
+    write_wad(wad.path(), "PWAD", &[("E1M1", b""), ("THINGS", b"")]);
+    let textures = source_dir.child("textures.wad");
+    write_wad(textures.path(), "PWAD", &[("TEXTURE1", b"")]);
+    let music = source_dir.child("music.wad");
+    write_wad(music.path(), "PWAD", &[("D_E1M1", b"")]);
+    let patch = source_dir.child("episode.deh");
+    patch.write_str("Patch File for DeHackEd v3.0").unwrap();
+
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .arg("--file")
+        .arg(patch.path().to_str().unwrap())
+        .arg("--file")
+        .arg(music.path().to_str().unwrap())
+        .arg("--resource")
+        .arg(textures.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("IWAD: DOOM"))
+        .stderr(predicate::str::contains("Supplementary file: music.wad"));
+
+    // The ExMx maps mean the WAD is for DOOM, which hasn't been imported.
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("episode")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "episode is played with the DOOM IWAD, which hasn't been imported",
+        ));
+
+    let iwad = source_dir.child("doom.wad");
+    write_wad(
+        iwad.path(),
+        "IWAD",
+        &[
+            ("E1M1", b""),
+            ("THINGS", b""),
+            ("E2M1", b""),
+            ("THINGS", b""),
+        ],
+    );
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(iwad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let iwads_path = doom_home_dir.path().join("iwads");
+    let wads_path = doom_home_dir.path().join("wads");
+    let files_path = wads_path.join("episode");
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("episode")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Game called with arguments: -iwad {} -file {} {} {} -deh {} -complevel 2 -skill",
+            iwads_path.join("DOOM.WAD").display(),
+            files_path.join("textures.wad").display(),
+            wads_path.join("episode.wad").display(),
+            files_path.join("music.wad").display(),
+            files_path.join("episode.deh").display(),
+        )));
+}
+
+#[test]
+fn play_should_fail_if_the_source_port_cannot_load_a_pk3() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let pk3 = source_dir.child("mod.pk3");
+    let map = build_wad("PWAD", &[("MAP01", b""), ("THINGS", b"")]);
+    write_pk3(pk3.path(), &[("maps/MAP01.wad", &map)]);
+
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "PrBoomPlus");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(pk3.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("mod")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "mod.pk3 is a PK3 package, which PrBoom Plus can't load",
+        ))
+        .stderr(predicate::str::contains("Eternity Engine, GZDoom"));
+}
+
+#[test]
+fn play_should_record_a_demo_named_after_the_wad_map_and_category() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let demos_path = doom_home_dir.path().join("demos").join("DOOM2");
+    for (category, demo_name) in [
+        ("UvMax", "doom201m-001"),
+        ("UvMax", "doom201m-002"),
+        ("Tyson", "doom201t-001"),
+    ] {
+        let mut cmd = Command::cargo_bin("tdl").unwrap();
+        cmd.arg("play")
+            .arg("--megawad")
+            .arg("DOOM2")
+            .arg("--map")
+            .arg("MAP01")
+            .arg("--record")
+            .arg("--category")
+            .arg(category)
+            .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+            .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "Game called with -record: {}",
+                demos_path.join(format!("{}.lmp", demo_name)).display()
+            )));
+        let demo_entry_path = settings_file
+            .path()
+            .join("demos")
+            .join(format!("{}.json", demo_name));
+        let demo_entry = std::fs::read_to_string(demo_entry_path).unwrap();
+        assert!(demo_entry.contains("\"wad_id\":\"DOOM2\""));
+    }
+}
+
+#[test]
+fn play_should_fail_to_record_a_demo_with_doom_retro() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "DoomRetro");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--record")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Doom Retro can't record demos"));
+}
+
+#[test]
+fn play_should_log_the_output_of_the_session() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game called with -iwad"))
+        .stderr(predicate::str::contains("The session was logged to"));
+
+    let logs = get_session_logs(settings_file.path());
+    assert_eq!(logs.len(), 1);
+    let log = std::fs::read_to_string(&logs[0]).unwrap();
+    assert!(log.contains("Game called with -iwad"));
+    assert!(log.ends_with("Exit status: 0\n"));
+}
+
+#[test]
+fn play_should_show_the_exit_status_and_output_of_a_crash() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .env("TDL_FAKE_SOURCE_PORT_EXIT_CODE", "3")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("DSDA Doom exited with status 3"))
+        .stderr(predicate::str::contains(
+            "I_Error: R_TextureNumForName: SKY4 not found",
+        ));
+
+    let log = std::fs::read_to_string(&get_session_logs(settings_file.path())[0]).unwrap();
+    assert!(log.contains("I_Error: R_TextureNumForName: SKY4 not found"));
+    assert!(log.ends_with("Exit status: 3\n"));
+}
+
+#[test]
+fn play_should_not_record_a_session_if_the_source_port_cannot_be_launched() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_port_path = copy_fake_source_port(doom_home_dir.path());
+    add_source_port_at(
+        settings_file.path(),
+        doom_home_dir.path(),
+        "Dsda",
+        &source_port_path,
+    );
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+    std::fs::remove_file(&source_port_path).unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure();
+
+    assert!(get_session_logs(settings_file.path()).is_empty());
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("history")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("still running").not())
+        .stderr(predicate::str::contains(
+            "No games have been played that match the filters",
+        ));
+}
+
+#[test]
+fn play_should_launch_the_game_in_the_background() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--detach")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game called with").not())
+        .stderr(predicate::str::contains(
+            "The game is running in the background",
+        ));
+
+    // The session writes the exit status once the game has exited.
+    let mut log = String::new();
+    for _ in 0..100 {
+        if let Some(path) = get_session_logs(settings_file.path()).first() {
+            log = std::fs::read_to_string(path).unwrap();
+            if log.contains("Exit status") {
+                break;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(log.contains("Game called with -iwad"));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("log")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game called with -iwad"))
+        .stderr(predicate::str::contains("Exit status: 0"));
+}
+
+#[test]
+fn play_should_continue_the_last_session() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad(settings_file.path(), doom_home_dir.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--map")
+        .arg("MAP01")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--continue")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Continuing DOOM2 on MAP01"))
+        .stdout(predicate::str::contains("-warp 1"));
+}
+
+#[test]
+fn play_should_move_on_to_the_next_map_from_the_last_session() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+    let iwad_path = settings_file.path().with_file_name("doom2.wad");
+    write_wad(
+        &iwad_path,
+        "IWAD",
+        &[
+            ("MAP01", b""),
+            ("THINGS", b""),
+            ("MAP02", b""),
+            ("THINGS", b""),
+        ],
+    );
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("iwad")
+        .arg("import")
+        .arg(iwad_path.to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--map")
+        .arg("MAP01")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--next")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Moving on from MAP01 to MAP02 in DOOM2",
+        ))
+        .stdout(predicate::str::contains("-warp 2"));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--next")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("MAP02 is the last map in DOOM2"));
+}
+
+#[test]
+fn play_should_fail_to_continue_if_nothing_has_been_played() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    add_fake_source_port(settings_file.path(), doom_home_dir.path(), "Dsda");
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--continue")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No games have been played yet, so there's nothing to continue",
+        ));
+}
+
+fn get_session_logs(settings_path: &Path) -> Vec<PathBuf> {
+    let logs_path = settings_path.join("logs");
+    if !logs_path.exists() {
+        return Vec::new();
+    }
+    std::fs::read_dir(logs_path)
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .collect()
+}
@@ -6,7 +6,9 @@
 /// Due to the fact that these tests hit the Github API, they need to be prevented from running as
 /// part of the normal test run, and that's why they used the `rate-limiting-tests` feature. Github
 /// are actually fairly stringent about the number of unathenticated hits on the API.
+#[cfg(feature = "rate-limiting-tests")]
 use assert_cmd::Command;
+#[cfg(feature = "rate-limiting-tests")]
 use predicates::prelude::*;
 
 #[cfg(feature = "rate-limiting-tests")]
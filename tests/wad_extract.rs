@@ -0,0 +1,87 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::wad::write_wad;
+
+#[test]
+fn wad_extract_should_write_the_lump_to_the_output_file() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("test.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[("DEHACKED", b"old patch"), ("DEHACKED", b"new patch")],
+    );
+    let output = source_dir.child("DEHACKED.deh");
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("extract")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .arg("--lump")
+        .arg("DEHACKED")
+        .arg("--output")
+        .arg(output.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Extracted 9 bytes from DEHACKED"));
+    output.assert("new patch");
+}
+
+#[test]
+fn wad_extract_should_write_the_lump_for_the_map_to_the_output_file() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("test.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[
+            ("MAP01", b""),
+            ("THINGS", b"map01 things"),
+            ("MAP02", b""),
+            ("THINGS", b"map02 things"),
+        ],
+    );
+    let output = source_dir.child("THINGS.lmp");
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("extract")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .arg("--map")
+        .arg("MAP01")
+        .arg("--lump")
+        .arg("THINGS")
+        .arg("--output")
+        .arg(output.path().to_str().unwrap())
+        .assert()
+        .success();
+    output.assert("map01 things");
+}
+
+#[test]
+fn wad_extract_should_fail_when_the_lump_does_not_exist() {
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("test.wad");
+    write_wad(wad.path(), "PWAD", &[("MAP01", b"")]);
+    let output = source_dir.child("COLORMAP.lmp");
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("extract")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .arg("--lump")
+        .arg("COLORMAP")
+        .arg("--output")
+        .arg(output.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "{} does not have a COLORMAP lump",
+            wad.path().display()
+        )));
+    output.assert(predicate::path::missing());
+}
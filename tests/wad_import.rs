@@ -0,0 +1,275 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::wad::{
+    build_sectors, build_square_room, build_things, build_wad, write_pk3, write_wad,
+};
+
+#[test]
+fn wad_import_should_save_the_wad_with_information_from_the_text_file() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("testwad.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[
+            ("MAP01", b""),
+            ("THINGS", b"things"),
+            ("MAP02", b""),
+            ("THINGS", b"things"),
+        ],
+    );
+    let text_file = source_dir.child("testwad.txt");
+    text_file
+        .write_str(
+            "Title                   : Test Megawad\n\
+             Release date            : 2021-10-01\n\
+             Author                  : Some Mapper\n",
+        )
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("RUST_LOG", "debug")
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("ID: testwad"))
+        .stderr(predicate::str::contains("WAD Name: testwad.wad"))
+        .stderr(predicate::str::contains("Title: Test Megawad"))
+        .stderr(predicate::str::contains("Released: 2021-10-01"))
+        .stderr(predicate::str::contains("Author: Some Mapper"))
+        .stderr(predicate::str::contains("MAP01: Unknown"))
+        .stderr(predicate::str::contains("MAP02: Unknown"));
+
+    let wad_entry_file = settings_dir.child("wads/testwad.json");
+    wad_entry_file.assert(predicate::path::is_file());
+    let wad_file = doom_home_dir.child("wads/testwad.wad");
+    wad_file.assert(predicate::path::eq_file(wad.path()));
+    let imported_text_file = doom_home_dir.child("wads/testwad.txt");
+    imported_text_file.assert(predicate::path::eq_file(text_file.path()));
+}
+
+#[test]
+fn wad_import_should_use_defaults_when_there_is_no_text_file() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("notext.wad");
+    write_wad(wad.path(), "PWAD", &[("E1M1", b""), ("THINGS", b"things")]);
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("RUST_LOG", "debug")
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Title: notext"))
+        .stderr(predicate::str::contains("Released: Unknown"))
+        .stderr(predicate::str::contains("Author: Unknown"))
+        .stderr(predicate::str::contains("E1M1: Unknown"));
+
+    let wad_file = doom_home_dir.child("wads/notext.wad");
+    wad_file.assert(predicate::path::is_file());
+}
+
+#[test]
+fn wad_import_should_use_the_map_names_from_the_umapinfo_lump() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("mapnames.wad");
+    write_wad(
+        wad.path(),
+        "PWAD",
+        &[
+            (
+                "UMAPINFO",
+                b"MAP MAP01\n{\n  levelname = \"Outpost\"\n  episode = \"M_EPI1\", \"Invasion\", \"i\"\n}\n",
+            ),
+            ("MAP01", b""),
+            ("THINGS", b"things"),
+            ("MAP02", b""),
+            ("THINGS", b"things"),
+        ],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Episode: Invasion (starts on MAP01)",
+        ))
+        .stderr(predicate::str::contains("MAP01: Outpost"))
+        .stderr(predicate::str::contains("MAP02: Unknown"));
+
+    let wad_entry_file = settings_dir.child("wads/mapnames.json");
+    wad_entry_file.assert(predicate::str::contains("\"name\":\"Outpost\""));
+}
+
+#[test]
+fn wad_import_should_work_out_the_complevel() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let things = build_things(&[(1, 7), (3004, 0x87)]);
+    let (linedefs, sidedefs, vertexes) = build_square_room(256);
+    let sectors = build_sectors(&[0]);
+    let map_lumps: [(&str, &[u8]); 6] = [
+        ("MAP01", b""),
+        ("THINGS", &things),
+        ("LINEDEFS", &linedefs),
+        ("SIDEDEFS", &sidedefs),
+        ("VERTEXES", &vertexes),
+        ("SECTORS", &sectors),
+    ];
+
+    // The friendly zombieman means the map needs MBF.
+    let wad = source_dir.child("friendly.wad");
+    write_wad(wad.path(), "PWAD", &map_lumps);
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Complevel: MBF"));
+    let wad_entry_file = settings_dir.child("wads/friendly.json");
+    wad_entry_file.assert(predicate::str::contains("\"complevel\":\"Mbf\""));
+
+    // The COMPLVL lump takes precedence over the text file and the map.
+    let wad = source_dir.child("stated.wad");
+    let mut lumps = vec![("COMPLVL", b"mbf21\n".as_slice())];
+    lumps.extend(map_lumps);
+    write_wad(wad.path(), "PWAD", &lumps);
+    source_dir
+        .child("stated.txt")
+        .write_str("Advanced engine needed  : Boom\n")
+        .unwrap();
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Complevel: MBF21"));
+}
+
+#[test]
+fn wad_import_should_reject_an_iwad() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("IWAD.WAD");
+    write_wad(wad.path(), "IWAD", &[("MAP01", b"")]);
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "{} is an IWAD",
+            wad.path().display()
+        )))
+        .stderr(predicate::str::contains(
+            "Use the `iwad import` command to import an IWAD",
+        ));
+}
+
+#[test]
+fn wad_import_should_import_the_maps_from_a_pk3_package() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let map = build_wad(
+        "PWAD",
+        &[
+            ("MAP01", b""),
+            ("TEXTMAP", b"namespace = \"zdoom\";"),
+            ("ENDMAP", b""),
+        ],
+    );
+    let extra = build_wad("PWAD", &[("MAP02", b""), ("THINGS", b"things")]);
+    let pk3 = source_dir.child("testmod.pk3");
+    write_pk3(
+        pk3.path(),
+        &[
+            (
+                "MAPINFO.txt",
+                b"map START \"The Hub\" { next = \"MAP02\" }\nmap MAP02 \"Extra\" { }",
+            ),
+            ("maps/START.wad", &map),
+            ("extra.wad", &extra),
+            ("zscript.txt", b"version \"4.0\""),
+        ],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(pk3.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("WAD Name: testmod.pk3"))
+        .stderr(predicate::str::contains("START: The Hub"))
+        .stderr(predicate::str::contains("MAP02: Extra"));
+
+    settings_dir
+        .child("wads/testmod.json")
+        .assert(predicate::path::is_file());
+    doom_home_dir
+        .child("wads/testmod.pk3")
+        .assert(predicate::path::eq_file(pk3.path()));
+}
+
+#[test]
+fn wad_import_should_reject_a_7zip_package() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let pk7 = source_dir.child("testmod.pk7");
+    pk7.write_binary(b"7z\xBC\xAF\x27\x1C").unwrap();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(pk7.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "is a 7-Zip package, which isn't supported",
+        ));
+}
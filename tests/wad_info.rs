@@ -0,0 +1,97 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::wad::{build_sectors, build_square_room, build_things, write_wad};
+
+fn write_test_wad(path: &std::path::Path) {
+    let things = build_things(&[(1, 7), (3004, 7), (3001, 6), (3003, 4), (2013, 7)]);
+    let (linedefs, sidedefs, vertexes) = build_square_room(512);
+    let sectors = build_sectors(&[0, 9]);
+    write_wad(
+        path,
+        "PWAD",
+        &[
+            (
+                "UMAPINFO",
+                b"MAP MAP01 { levelname = \"Outpost\" partime = 95 }\n",
+            ),
+            ("MAP01", b""),
+            ("THINGS", &things),
+            ("LINEDEFS", &linedefs),
+            ("SIDEDEFS", &sidedefs),
+            ("VERTEXES", &vertexes),
+            ("SECTORS", &sectors),
+            ("MAP02", b""),
+            (
+                "TEXTMAP",
+                b"namespace = \"zdoom\";\n\
+                  thing { type = 3004; skill4 = true; single = true; }\n\
+                  sector { secret = true; }\n",
+            ),
+            ("ENDMAP", b""),
+            // A Hexen format map without any of the geometry, so there aren't any stats for it.
+            ("MAP03", b""),
+            ("THINGS", b""),
+            ("BEHAVIOR", b"ACS\0"),
+        ],
+    );
+}
+
+#[test]
+fn wad_info_should_show_the_map_stats_for_an_imported_wad() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("stats.wad");
+    write_test_wad(wad.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("import")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("info")
+        .arg("--name")
+        .arg("stats")
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("ID: stats"))
+        .stdout(predicate::str::is_match(r"MAP01\s*\|\s*Outpost\s*\|\s*Doom\s*\|\s*1/2/3\s*\|\s*1\s*\|\s*1\s*\|\s*4\s*\|\s*2\s*\|\s*1:35").unwrap())
+        .stdout(predicate::str::is_match(r"MAP02\s*\|\s*Unknown\s*\|\s*UDMF \(zdoom\)\s*\|\s*0/0/1\s*\|\s*0\s*\|\s*1\s*\|\s*0\s*\|\s*1").unwrap())
+        .stdout(predicate::str::is_match(r"MAP03\s*\|\s*Unknown\s*\|\s*Hexen\s*\|\s*-").unwrap());
+}
+
+#[test]
+fn wad_info_should_show_the_map_stats_for_a_wad_file() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("stats.wad");
+    write_test_wad(wad.path());
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("info")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Title: stats"))
+        .stdout(
+            predicate::str::is_match(r"MAP01\s*\|\s*Outpost\s*\|\s*Doom\s*\|\s*1/2/3").unwrap(),
+        );
+    settings_dir
+        .child("wads/stats.json")
+        .assert(predicate::path::missing());
+}
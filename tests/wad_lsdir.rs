@@ -0,0 +1,53 @@
+use assert_cmd::Command;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use test_helpers::wad::{write_pk3, write_wad};
+
+#[test]
+fn wad_lsdir_should_list_the_lumps_in_a_wad() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let wad = source_dir.child("test.wad");
+    write_wad(wad.path(), "PWAD", &[("MAP01", b""), ("THINGS", b"things")]);
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("lsdir")
+        .arg("--path")
+        .arg(wad.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Directory has 2 entries"))
+        .stdout(predicate::str::is_match(r"THINGS\s*\|\s*6\s*\|\s*12").unwrap());
+}
+
+#[test]
+fn wad_lsdir_should_list_the_files_in_a_pk3_package() {
+    let settings_dir = assert_fs::TempDir::new().unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    let source_dir = assert_fs::TempDir::new().unwrap();
+    let pk3 = source_dir.child("test.pk3");
+    write_pk3(
+        pk3.path(),
+        &[
+            ("MAPINFO.txt", b"map MAP01 { }"),
+            ("maps/MAP01.wad", b"PWAD"),
+        ],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("lsdir")
+        .arg("--path")
+        .arg(pk3.path().to_str().unwrap())
+        .env("TDL_SETTINGS_PATH", settings_dir.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Package has 2 files"))
+        .stdout(predicate::str::is_match(r"MAPINFO\.txt\s*\|\s*13\s*\|\s*13").unwrap())
+        .stdout(predicate::str::is_match(r"maps/MAP01\.wad\s*\|\s*4\s*\|\s*4").unwrap());
+}
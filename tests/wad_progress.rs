@@ -0,0 +1,79 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use test_helpers::source_port::copy_fake_source_port;
+use test_helpers::tdl::{add_default_profile, add_source_port_at, import_test_iwad_with_maps};
+
+#[test]
+fn wad_progress_should_show_the_maps_finished_with_a_port_that_writes_levelstats() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    // The levelstat file is written to the port's directory, so each test needs its own copy of
+    // the port.
+    let source_port_path = copy_fake_source_port(doom_home_dir.path());
+    add_source_port_at(
+        settings_file.path(),
+        doom_home_dir.path(),
+        "Dsda",
+        &source_port_path,
+    );
+    add_default_profile(settings_file.path(), doom_home_dir.path(), "Dsda");
+    import_test_iwad_with_maps(
+        settings_file.path(),
+        doom_home_dir.path(),
+        &["MAP01", "MAP02"],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("play")
+        .arg("--megawad")
+        .arg("DOOM2")
+        .arg("--map")
+        .arg("MAP01")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game called with -levelstat"))
+        .stderr(predicate::str::contains(
+            "Finished MAP01 in 0:31.97 with 14/14 kills and 2/5 secrets",
+        ));
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("progress")
+        .arg("--name")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"MAP01 .*Ultra-Violence .*0:31.97 .*Yes .*No").unwrap())
+        .stdout(predicate::str::is_match(r"MAP02 .*Not finished").unwrap())
+        .stderr(predicate::str::contains(
+            "Finished 1 of the 2 maps in DOOM2",
+        ));
+}
+
+#[test]
+fn wad_progress_should_show_no_maps_finished_for_a_wad_that_has_not_been_played() {
+    let settings_file = assert_fs::NamedTempFile::new("tdl.json").unwrap();
+    let doom_home_dir = assert_fs::TempDir::new().unwrap();
+    import_test_iwad_with_maps(
+        settings_file.path(),
+        doom_home_dir.path(),
+        &["MAP01", "MAP02"],
+    );
+
+    let mut cmd = Command::cargo_bin("tdl").unwrap();
+    cmd.arg("wad")
+        .arg("progress")
+        .arg("--name")
+        .arg("DOOM2")
+        .env("TDL_SETTINGS_PATH", settings_file.path().to_str().unwrap())
+        .env("TDL_DOOM_HOME_PATH", doom_home_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Finished 0 of the 2 maps in DOOM2",
+        ));
+}